@@ -0,0 +1,64 @@
+//! Compares the parallel evaluation of operations with the sequential one, on the conjunction of
+//! the identity and the order relation on `n`-bit numbers. The bits of `x` come before the bits of
+//! `y`, so both diagrams have about `2^n` nodes that share most of their structure.
+//!
+//! Run with `cargo bench --features sync --bench parallel`.
+
+use std::time::{Duration, Instant};
+
+use rayon::ThreadPoolBuilder;
+use rel_lang_dd::{Manager, Node};
+
+fn identity(dd: &Manager, n: u64) -> Node {
+    let mut node = dd.true_node();
+    for i in 0..n {
+        node &= !(dd.bit(i) ^ dd.bit(n + i));
+    }
+    node
+}
+
+/// Returns `x <= y`, comparing from the least significant bit up.
+fn less_equal(dd: &Manager, n: u64) -> Node {
+    let mut node = dd.true_node();
+    for i in 0..n {
+        let (x, y) = (dd.bit(i), dd.bit(n + i));
+        node = (!x.clone() & y.clone()) | (!(x ^ y) & node);
+    }
+    node
+}
+
+/// Returns the time to conjoin the relations on `n`-bit numbers with the given number of threads,
+/// on a fresh manager so that no results are reused.
+fn conjoin(n: u64, num_threads: usize) -> (Duration, usize) {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let dd = Manager::new();
+        let (f, g) = (identity(&dd, n), less_equal(&dd, n));
+        let start = Instant::now();
+        let result = f & g;
+        (start.elapsed(), result.size())
+    })
+}
+
+fn main() {
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    println!(
+        "{:>4} {:>10} {:>12} {:>12} {:>8}",
+        "n", "size", "sequential", "parallel", "speedup"
+    );
+    for n in [12, 14, 16, 18] {
+        let (sequential, size) = conjoin(n, 1);
+        let (parallel, parallel_size) = conjoin(n, num_threads);
+        assert_eq!(size, parallel_size);
+        println!(
+            "{n:>4} {size:>10} {sequential:>12.2?} {parallel:>12.2?} {:>8.2}",
+            sequential.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+    if num_threads == 1 {
+        println!("Only one thread is available, so the parallel evaluation cannot be faster.");
+    }
+}
@@ -0,0 +1,607 @@
+//! Algebraic decision diagrams (ADDs), also known as multi-terminal BDDs, whose terminals hold
+//! values of an arbitrary type instead of booleans.
+//!
+//! ADDs have their own [`AddManager`] per terminal type, with one unique table for the terminals
+//! and one for the non-terminal nodes, like the BDD [`Manager`]. Their variables are ordered by
+//! index and are never reordered.
+
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    vec,
+    vec::Vec,
+};
+use core::{ops, ptr};
+
+use crate::{
+    Error, Lock, Manager, Node, Rc, Weak,
+    cofactor::cofactor,
+    manager::CacheKey,
+    node::Edge,
+    ops::{Step, apply},
+    write,
+};
+
+/// Creates and shares the nodes of ADDs with terminals of type `T`.
+#[derive(Debug)]
+pub struct AddManager<T: Ord> {
+    cache: Rc<AddCache<T>>,
+}
+
+#[derive(Debug)]
+struct AddCache<T: Ord> {
+    terminals: Lock<BTreeMap<T, Weak<Inner<T>>>>,
+    unique_cache: Lock<AddUniqueCache<T>>,
+}
+
+type AddUniqueCache<T> = BTreeMap<(u64, AddKey, AddKey), Weak<Inner<T>>>;
+
+/// The variable and the children of a non-terminal node.
+type Children<T> = (u64, Rc<Inner<T>>, Rc<Inner<T>>);
+
+/// A function from assignments of the variables to values of type `T`.
+#[derive(Debug)]
+pub struct Add<T: Ord> {
+    cache: Rc<AddCache<T>>,
+    inner: Rc<Inner<T>>,
+}
+
+#[derive(Debug)]
+struct Inner<T: Ord> {
+    // Only `None` while the node is torn down in the `Drop` implementation.
+    kind: Option<Kind<T>>,
+}
+
+#[derive(Debug)]
+enum Kind<T: Ord> {
+    Terminal {
+        value: T,
+        cache: Weak<AddCache<T>>,
+    },
+    NonTerminal {
+        var: u64,
+        then_child: Rc<Inner<T>>,
+        else_child: Rc<Inner<T>>,
+        cache: Weak<AddCache<T>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AddKey(*const ());
+
+#[cfg(feature = "sync")]
+unsafe impl Send for AddKey {}
+
+#[cfg(feature = "sync")]
+unsafe impl Sync for AddKey {}
+
+impl<T: Ord + Clone> AddManager<T> {
+    pub fn new() -> Self {
+        Self {
+            cache: Rc::new(AddCache {
+                terminals: Lock::new(BTreeMap::new()),
+                unique_cache: Lock::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Returns the ADD with the same value for all assignments.
+    pub fn constant(&self, value: T) -> Add<T> {
+        Add {
+            cache: Rc::clone(&self.cache),
+            inner: self.cache.terminal(value),
+        }
+    }
+
+    /// Returns the ADD that maps the assignments that satisfy the node to `then_value`, and all
+    /// others to `else_value`.
+    ///
+    /// Fails if an operation of the BDD manager has been aborted, see [`Error`], since the ADD
+    /// would be built from placeholder results.
+    pub fn from_node(&self, node: &Node, then_value: T, else_value: T) -> Result<Add<T>, Error> {
+        let _nodes = node.cache.read_nodes();
+        // The BDD may have been reordered, so it is split on its variables in index order.
+        let vars = support(&node.edge);
+        let inner = apply(
+            (node.edge.clone(), 0),
+            |(f, index)| (CacheKey::from(f), *index),
+            |(f, index)| {
+                if f.is_terminal() {
+                    let value = if f.is_true() {
+                        then_value.clone()
+                    } else {
+                        else_value.clone()
+                    };
+                    return Step::Done(self.cache.terminal(value));
+                }
+
+                let var = vars[index];
+                let then_child = cofactor(&node.cache, &f, var, true);
+                let else_child = cofactor(&node.cache, &f, var, false);
+                Step::Split(var, (then_child, index + 1), (else_child, index + 1))
+            },
+            |var, new_then, new_else| self.cache.node(var, &new_then, &new_else),
+        );
+        node.cache.check()?;
+        Ok(Add {
+            cache: Rc::clone(&self.cache),
+            inner,
+        })
+    }
+}
+
+impl<T: Ord + Clone> Default for AddManager<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Clone for AddManager<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+        }
+    }
+}
+
+impl<T: Ord> Clone for Add<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Ord> PartialEq for Add<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // ADDs are never reordered, so equal functions are the same node.
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T: Ord> Eq for Add<T> {}
+
+impl<T: Ord + Clone> Add<T> {
+    /// Returns the value for the assignment, or `None` if there are not enough variables.
+    pub fn eval(&self, variables: impl IntoIterator<Item = bool>) -> Option<T> {
+        let variables = variables.into_iter().collect::<Vec<_>>();
+        let mut current = Rc::clone(&self.inner);
+        loop {
+            let next = match current.kind() {
+                Kind::Terminal { value, .. } => return Some(value.clone()),
+                Kind::NonTerminal {
+                    var,
+                    then_child,
+                    else_child,
+                    ..
+                } => {
+                    if *variables.get(*var as usize)? {
+                        Rc::clone(then_child)
+                    } else {
+                        Rc::clone(else_child)
+                    }
+                }
+            };
+            current = next;
+        }
+    }
+
+    /// Returns the number of non-terminal nodes in the diagram.
+    pub fn size(&self) -> usize {
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![Rc::clone(&self.inner)];
+        while let Some(node) = stack.pop() {
+            if let Some((_, then_child, else_child)) = node.children()
+                && visited.insert(AddKey::from(&node))
+            {
+                stack.push(then_child);
+                stack.push(else_child);
+            }
+        }
+        visited.len()
+    }
+
+    /// Combines the values of both ADDs pointwise with `op`.
+    pub fn apply(&self, rhs: &Self, op: impl Fn(&T, &T) -> T) -> Self {
+        let cache = &self.cache;
+        let inner = apply(
+            (Rc::clone(&self.inner), Rc::clone(&rhs.inner)),
+            |(f, g)| (AddKey::from(f), AddKey::from(g)),
+            |(f, g)| {
+                if let (Some(lhs), Some(rhs)) = (f.value(), g.value()) {
+                    return Step::Done(cache.terminal(op(lhs, rhs)));
+                }
+
+                // Terminals are below all variables.
+                let f_var = f.var().unwrap_or(u64::MAX);
+                let g_var = g.var().unwrap_or(u64::MAX);
+                let var = f_var.min(g_var);
+                let (fv, fnv) = cofactors(&f, var);
+                let (gv, gnv) = cofactors(&g, var);
+                Step::Split(var, (fv, gv), (fnv, gnv))
+            },
+            |var, new_then, new_else| cache.node(var, &new_then, &new_else),
+        );
+        Self {
+            cache: Rc::clone(&self.cache),
+            inner,
+        }
+    }
+
+    /// Returns the BDD of the assignments whose values satisfy the predicate.
+    ///
+    /// Fails instead of returning a placeholder if an operation of the manager has been aborted,
+    /// see [`Error`].
+    pub fn to_node(
+        &self,
+        manager: &Manager,
+        predicate: impl Fn(&T) -> bool,
+    ) -> Result<Node, Error> {
+        let cache = &manager.cache;
+        let edge = apply(
+            Rc::clone(&self.inner),
+            |node| AddKey::from(node),
+            |node| match node.kind() {
+                Kind::Terminal { value, .. } if predicate(value) => Step::Done(cache.true_node()),
+                Kind::Terminal { .. } => Step::Done(cache.false_node()),
+                Kind::NonTerminal {
+                    var,
+                    then_child,
+                    else_child,
+                    ..
+                } => Step::Split(*var, Rc::clone(then_child), Rc::clone(else_child)),
+            },
+            // Falls back to `if_then_else` if the manager has been reordered.
+            |var, new_then, new_else| cache.var_node(var, &new_then, &new_else),
+        );
+        cache.check()?;
+        Ok(Node {
+            cache: Rc::clone(cache),
+            edge,
+        }
+        .reorder_if_needed())
+    }
+}
+
+impl<T: Ord + Clone + ops::Add<Output = T>> Add<T> {
+    pub fn plus(&self, rhs: &Self) -> Self {
+        self.apply(rhs, |lhs, rhs| lhs.clone() + rhs.clone())
+    }
+}
+
+impl<T: Ord + Clone + ops::Mul<Output = T>> Add<T> {
+    pub fn times(&self, rhs: &Self) -> Self {
+        self.apply(rhs, |lhs, rhs| lhs.clone() * rhs.clone())
+    }
+}
+
+impl<T: Ord + Clone> Add<T> {
+    pub fn min(&self, rhs: &Self) -> Self {
+        self.apply(rhs, |lhs, rhs| lhs.min(rhs).clone())
+    }
+}
+
+impl<T: Ord + Clone> AddCache<T> {
+    fn terminal(self: &Rc<Self>, value: T) -> Rc<Inner<T>> {
+        // The lookup and the insertion happen under the same lock, so threads racing to create the
+        // same terminal end up with the same one, see `UniqueTable::get_or_insert_with`.
+        let mut terminals = write(&self.terminals);
+        if let Some(node) = terminals.get(&value).and_then(Weak::upgrade) {
+            return node;
+        }
+
+        let new_node = Rc::new(Inner {
+            kind: Some(Kind::Terminal {
+                value: value.clone(),
+                cache: Rc::downgrade(self),
+            }),
+        });
+        terminals.insert(value, Rc::downgrade(&new_node));
+        new_node
+    }
+
+    /// Returns the unique node with the given variable and children, whose variables must be
+    /// greater than `var`.
+    fn node(
+        self: &Rc<Self>,
+        var: u64,
+        then_child: &Rc<Inner<T>>,
+        else_child: &Rc<Inner<T>>,
+    ) -> Rc<Inner<T>> {
+        if Rc::ptr_eq(then_child, else_child) {
+            return Rc::clone(then_child);
+        }
+
+        let key = (var, AddKey::from(then_child), AddKey::from(else_child));
+        let mut unique_cache = write(&self.unique_cache);
+        if let Some(node) = unique_cache.get(&key).and_then(Weak::upgrade) {
+            return node;
+        }
+
+        let new_node = Rc::new(Inner {
+            kind: Some(Kind::NonTerminal {
+                var,
+                then_child: Rc::clone(then_child),
+                else_child: Rc::clone(else_child),
+                cache: Rc::downgrade(self),
+            }),
+        });
+        unique_cache.insert(key, Rc::downgrade(&new_node));
+        new_node
+    }
+}
+
+impl<T: Ord> Inner<T> {
+    fn kind(&self) -> &Kind<T> {
+        self.kind.as_ref().expect("node is alive")
+    }
+
+    fn value(&self) -> Option<&T> {
+        match self.kind() {
+            Kind::Terminal { value, .. } => Some(value),
+            Kind::NonTerminal { .. } => None,
+        }
+    }
+
+    fn var(&self) -> Option<u64> {
+        match self.kind() {
+            Kind::Terminal { .. } => None,
+            Kind::NonTerminal { var, .. } => Some(*var),
+        }
+    }
+
+    fn children(&self) -> Option<Children<T>> {
+        match self.kind() {
+            Kind::Terminal { .. } => None,
+            Kind::NonTerminal {
+                var,
+                then_child,
+                else_child,
+                ..
+            } => Some((*var, Rc::clone(then_child), Rc::clone(else_child))),
+        }
+    }
+}
+
+impl<T: Ord> Inner<T> {
+    /// Removes the node, whose allocation is at `ptr`, from its unique table, and moves its
+    /// children onto the stack.
+    ///
+    /// The entry is left alone if it points to another node, which happens if another thread
+    /// created a new node for the same key while this one was dropped.
+    fn take_children(&mut self, ptr: *const Self, stack: &mut Vec<Rc<Inner<T>>>) {
+        match self.kind.take() {
+            None => {}
+            Some(Kind::Terminal { value, cache }) => {
+                if let Some(cache) = cache.upgrade() {
+                    let mut terminals = write(&cache.terminals);
+                    if terminals
+                        .get(&value)
+                        .is_some_and(|entry| ptr::eq(entry.as_ptr(), ptr))
+                    {
+                        terminals.remove(&value);
+                    }
+                }
+            }
+            Some(Kind::NonTerminal {
+                var,
+                then_child,
+                else_child,
+                cache,
+            }) => {
+                if let Some(cache) = cache.upgrade() {
+                    let key = (var, AddKey::from(&then_child), AddKey::from(&else_child));
+                    let mut unique_cache = write(&cache.unique_cache);
+                    if unique_cache
+                        .get(&key)
+                        .is_some_and(|entry| ptr::eq(entry.as_ptr(), ptr))
+                    {
+                        unique_cache.remove(&key);
+                    }
+                }
+                stack.push(then_child);
+                stack.push(else_child);
+            }
+        }
+    }
+}
+
+impl<T: Ord> Drop for Inner<T> {
+    fn drop(&mut self) {
+        // Tear down the nodes that are no longer referenced without recursion, see `node::Inner`.
+        let mut stack = Vec::new();
+        let ptr: *const Self = self;
+        self.take_children(ptr, &mut stack);
+        while let Some(node) = stack.pop() {
+            let ptr = Rc::as_ptr(&node);
+            if let Some(mut node) = Rc::into_inner(node) {
+                node.take_children(ptr, &mut stack);
+            }
+        }
+    }
+}
+
+impl<T: Ord> From<&Rc<Inner<T>>> for AddKey {
+    fn from(node: &Rc<Inner<T>>) -> Self {
+        Self(Rc::as_ptr(node).cast())
+    }
+}
+
+/// Returns the cofactors of the node with respect to `var`, which is at or above its variable.
+fn cofactors<T: Ord>(node: &Rc<Inner<T>>, var: u64) -> (Rc<Inner<T>>, Rc<Inner<T>>) {
+    match node.children() {
+        Some((node_var, then_child, else_child)) if node_var == var => (then_child, else_child),
+        _ => (Rc::clone(node), Rc::clone(node)),
+    }
+}
+
+/// Returns the variables of the BDD in index order.
+fn support(root: &Edge) -> Vec<u64> {
+    let mut vars = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![Rc::clone(&root.node)];
+    while let Some(node) = stack.pop() {
+        if let Some((var, then_child, else_child)) = node.children()
+            && visited.insert(CacheKey::from(&node))
+        {
+            vars.insert(var);
+            stack.push(then_child.node);
+            stack.push(else_child.node);
+        }
+    }
+    vars.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{AddManager, Error, Manager};
+
+    #[test]
+    fn test_constants() {
+        let adds = AddManager::new();
+
+        assert_eq!(adds.constant(3).plus(&adds.constant(4)), adds.constant(7));
+        assert_eq!(adds.constant(3).times(&adds.constant(4)), adds.constant(12));
+        assert_eq!(adds.constant(3).min(&adds.constant(4)), adds.constant(3));
+        assert_eq!(adds.constant(3).eval([]), Some(3));
+        assert_eq!(adds.constant(3).size(), 0);
+    }
+
+    #[test]
+    fn test_from_node() {
+        let dd = Manager::new();
+        let adds = AddManager::new();
+        let node = dd.bit(0) & !dd.bit(2);
+
+        let add = adds.from_node(&node, 5, 1).unwrap();
+
+        assert_eq!(add.eval([true, false, false]), Some(5));
+        assert_eq!(add.eval([true, true, true]), Some(1));
+        assert_eq!(add.eval([false; 3]), Some(1));
+        assert_eq!(add.eval([true]), None);
+        assert_eq!(add.size(), 2);
+        assert_eq!(add.to_node(&dd, |&value| value > 1).unwrap(), node);
+        assert_eq!(adds.from_node(&node, 2, 2).unwrap(), adds.constant(2));
+    }
+
+    #[test]
+    fn test_shortest_paths() {
+        const INFINITY: u64 = u64::MAX / 2;
+
+        let dd = Manager::new();
+        let adds = AddManager::new();
+        // Edge weights of two alternative routes, indexed by the bits of a destination.
+        let direct = adds.from_node(&dd.bit(0), 7, INFINITY).unwrap();
+        let first = adds
+            .from_node(&(dd.bit(0) | dd.bit(1)), 2, INFINITY)
+            .unwrap();
+        let second = adds.from_node(&dd.bit(0), 3, 1).unwrap();
+
+        let shortest = direct.min(&first.plus(&second));
+
+        assert_eq!(shortest.eval([true, false]), Some(5));
+        assert_eq!(shortest.eval([false, true]), Some(3));
+        assert_eq!(shortest.eval([false, false]), Some(INFINITY));
+        assert_eq!(
+            shortest.to_node(&dd, |&weight| weight < INFINITY).unwrap(),
+            dd.bit(0) | dd.bit(1)
+        );
+    }
+
+    #[test]
+    fn test_counting() {
+        let dd = Manager::new();
+        let adds = AddManager::new();
+        let a = adds.from_node(&(dd.bit(0) ^ dd.bit(1)), 1u64, 0).unwrap();
+        let b = adds.from_node(&dd.bit(1), 2, 3).unwrap();
+
+        let product = a.times(&b);
+
+        assert_eq!(product.eval([true, false]), Some(3));
+        assert_eq!(product.eval([false, true]), Some(2));
+        assert_eq!(product.eval([true, true]), Some(0));
+        assert_eq!(product.plus(&product), product.times(&adds.constant(2)));
+    }
+
+    #[test]
+    fn test_reordered() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & dd.bit(3)) | (dd.bit(1) & dd.bit(4)) | (dd.bit(2) & dd.bit(5));
+        let adds = AddManager::new();
+        let add = adds.from_node(&node, true, false).unwrap();
+        dd.reorder();
+
+        assert_eq!(adds.from_node(&node, true, false).unwrap(), add);
+        assert_eq!(add.to_node(&dd, |&value| value).unwrap(), node);
+    }
+
+    #[test]
+    fn test_deep() {
+        const LEVELS: u64 = 5000;
+
+        let dd = Manager::new();
+        let node = (0..LEVELS).rev().fold(dd.true_node(), |node, i| {
+            dd.get_or_insert(i, &node, &dd.false_node())
+        });
+        let adds = AddManager::new();
+
+        let add = adds.from_node(&node, 1, 0).unwrap().plus(&adds.constant(1));
+
+        assert_eq!(add.size(), LEVELS as usize);
+        assert_eq!(add.to_node(&dd, |&value| value == 2).unwrap(), node);
+        // Tearing down the chain must not overflow the stack either.
+        drop(add);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_concurrent_nodes() {
+        let dd = Manager::new();
+        let adds = AddManager::new();
+        let node = dd.bit(0) ^ dd.bit(1);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for i in 0..200 {
+                        // The nodes are dropped right away, while other threads create them again.
+                        let add = adds.from_node(&node, i % 4, 4).unwrap();
+                        assert_eq!(adds.from_node(&node, i % 4, 4).unwrap(), add);
+                        assert_eq!(add.eval([true, false]), Some(i % 4));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(adds.constant(4), adds.constant(4));
+        assert_eq!(
+            adds.from_node(&node, 1, 4).unwrap(),
+            adds.from_node(&node, 1, 4).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let dd = Manager::new();
+        let adds = AddManager::new();
+        let node = dd.bit(0) & !dd.bit(2);
+        let flag = Arc::new(AtomicBool::new(true));
+        dd.set_interrupt_flag(Some(Arc::clone(&flag)));
+
+        assert_eq!(adds.from_node(&node, 5, 1), Err(Error::Interrupted));
+        flag.store(false, Ordering::Relaxed);
+        let add = adds.from_node(&node, 5, 1).unwrap();
+
+        let sum = add.plus(&adds.from_node(&dd.bit(1), 1, 0).unwrap());
+        dd.set_node_limit(Some(dd.node_count()));
+        assert!(matches!(
+            sum.to_node(&dd, |&value| value % 2 == 0),
+            Err(Error::NodeLimit { .. })
+        ));
+        assert_eq!(dd.take_error(), None);
+        assert_eq!(add.to_node(&dd, |&value| value > 1), Ok(node));
+    }
+}
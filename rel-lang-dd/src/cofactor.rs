@@ -0,0 +1,196 @@
+//! Cofactors and simplification against care sets.
+//!
+//! [`Node::restrict`] and [`Node::constrain`] return functions that agree with the node wherever
+//! the care set holds, and are usually smaller outside of it.
+
+use crate::{
+    Node, Rc,
+    manager::{Cache, CacheKey},
+    node::Edge,
+    ops::{Step, or, split_on_next_var},
+};
+
+impl Node {
+    /// Returns the function with the variable fixed to the value.
+    pub fn cofactor(&self, var: u64, value: bool) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: cofactor(&self.cache, &self.edge, var, value),
+        }
+        .reorder_if_needed()
+    }
+
+    /// Simplifies the node against the care set with Coudert and Madre's restrict operator, which
+    /// never introduces variables that the node does not depend on. Returns false if the care set
+    /// is empty.
+    pub fn restrict(&self, care: &Self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: restrict(&self.cache, &self.edge, &care.edge),
+        }
+        .reorder_if_needed()
+    }
+
+    /// Returns the generalized cofactor of the node with respect to the care set, as defined by
+    /// Coudert and Madre. Returns false if the care set is empty.
+    ///
+    /// Unlike [`Node::restrict`], the result is an image of the node under a projection onto the
+    /// care set, so it distributes over the boolean operations.
+    pub fn constrain(&self, care: &Self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: constrain(&self.cache, &self.edge, &care.edge),
+        }
+        .reorder_if_needed()
+    }
+}
+
+pub(crate) fn cofactor(cache: &Rc<Cache>, edge: &Edge, var: u64, value: bool) -> Edge {
+    let level = cache.level(var);
+    cache.apply(
+        edge.clone(),
+        |edge| CacheKey::from(edge),
+        |edge| match edge.children() {
+            Some((edge_var, then_child, else_child)) if edge_var == var => {
+                Step::Done(if value { then_child } else { else_child })
+            }
+            Some((edge_var, then_child, else_child)) if cache.level(edge_var) < level => {
+                Step::Split(edge_var, then_child, else_child)
+            }
+            // The variable is not tested below this node.
+            _ => Step::Done(edge),
+        },
+        |edge_var, new_then, new_else| cache.get_or_insert(edge_var, &new_then, &new_else),
+    )
+}
+
+fn restrict(cache: &Rc<Cache>, f: &Edge, c: &Edge) -> Edge {
+    cache.apply(
+        (f.clone(), c.clone()),
+        |(f, c)| (CacheKey::from(f), CacheKey::from(c)),
+        |(mut f, mut c)| loop {
+            if c.is_false() {
+                return Step::Done(cache.false_node());
+            }
+            if c.is_true() || f.is_terminal() {
+                return Step::Done(f);
+            }
+            if f.ptr_eq(&c) {
+                return Step::Done(cache.true_node());
+            }
+            if f.ptr_eq(&c.not()) {
+                return Step::Done(cache.false_node());
+            }
+
+            let f_var = f.var().expect("f is non-terminal");
+            let (c_var, c_then, c_else) = c.children().expect("c is non-terminal");
+            if cache.level(c_var) < cache.level(f_var) {
+                // The node does not depend on the variable, so it is quantified out of the care set.
+                c = or(cache, &c_then, &c_else);
+                continue;
+            }
+
+            let (var, fv, fnv, cv, cnv) = split_on_next_var(cache, &f, &c);
+            if cv.is_false() {
+                (f, c) = (fnv, cnv);
+            } else if cnv.is_false() {
+                (f, c) = (fv, cv);
+            } else {
+                return Step::Split(var, (fv, cv), (fnv, cnv));
+            }
+        },
+        |var, new_then, new_else| cache.get_or_insert(var, &new_then, &new_else),
+    )
+}
+
+fn constrain(cache: &Rc<Cache>, f: &Edge, c: &Edge) -> Edge {
+    cache.apply(
+        (f.clone(), c.clone()),
+        |(f, c)| (CacheKey::from(f), CacheKey::from(c)),
+        |(mut f, mut c)| loop {
+            if c.is_false() {
+                return Step::Done(cache.false_node());
+            }
+            if c.is_true() || f.is_terminal() {
+                return Step::Done(f);
+            }
+            if f.ptr_eq(&c) {
+                return Step::Done(cache.true_node());
+            }
+            if f.ptr_eq(&c.not()) {
+                return Step::Done(cache.false_node());
+            }
+
+            let (var, fv, fnv, cv, cnv) = split_on_next_var(cache, &f, &c);
+            if cv.is_false() {
+                (f, c) = (fnv, cnv);
+            } else if cnv.is_false() {
+                (f, c) = (fv, cv);
+            } else {
+                return Step::Split(var, (fv, cv), (fnv, cnv));
+            }
+        },
+        |var, new_then, new_else| cache.get_or_insert(var, &new_then, &new_else),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Manager, Node};
+
+    fn example(dd: &Manager) -> Node {
+        (dd.bit(0) & dd.bit(1)) | dd.bit(2)
+    }
+
+    #[test]
+    fn test_cofactor() {
+        let dd = Manager::new();
+        let node = example(&dd);
+
+        assert_eq!(node.cofactor(0, true), dd.bit(1) | dd.bit(2));
+        assert_eq!(node.cofactor(0, false), dd.bit(2));
+        assert_eq!(node.cofactor(1, false), dd.bit(2));
+        assert!(node.cofactor(2, true).is_true());
+        assert_eq!(node.cofactor(5, true), node);
+        assert_eq!((!node.clone()).cofactor(2, false), !(dd.bit(0) & dd.bit(1)));
+    }
+
+    #[test]
+    fn test_restrict() {
+        let dd = Manager::new();
+        let node = example(&dd);
+        let care = dd.bit(0) ^ dd.bit(3);
+
+        assert_eq!(node.restrict(&care) & care.clone(), node.clone() & care);
+        assert_eq!(node.restrict(&dd.bit(0)), node.cofactor(0, true));
+        assert_eq!(node.restrict(&dd.true_node()), node);
+        assert!(node.restrict(&node).is_true());
+        assert!(node.restrict(&dd.false_node()).is_false());
+
+        // The variables of the care set that the node does not depend on are quantified out.
+        assert_eq!(dd.bit(1).restrict(&(dd.bit(0) ^ dd.bit(1))), dd.bit(1));
+    }
+
+    #[test]
+    fn test_constrain() {
+        let dd = Manager::new();
+        let node = example(&dd);
+        let care = dd.bit(0) ^ dd.bit(3);
+
+        assert_eq!(
+            node.constrain(&care) & care.clone(),
+            node.clone() & care.clone()
+        );
+        assert_eq!(node.constrain(&dd.bit(0)), node.cofactor(0, true));
+        assert_eq!(node.constrain(&dd.true_node()), node);
+        assert!(node.constrain(&!node.clone()).is_false());
+        assert!(node.constrain(&dd.false_node()).is_false());
+
+        // Unlike restrict, constrain maps the node onto the care set.
+        assert_eq!(dd.bit(1).constrain(&(dd.bit(0) ^ dd.bit(1))), !dd.bit(0));
+        assert_eq!(
+            (node.clone() & dd.bit(3)).constrain(&care),
+            node.constrain(&care) & dd.bit(3).constrain(&care)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Counting the satisfying assignments of a function directly on its BDD.
+//!
+//! A path from the root to the true terminal stands for `2^k` assignments, where `k` is the number
+//! of counted variables that the path skips. The variables may have been reordered, so they are
+//! ranked by their levels rather than by their indices.
+
+use alloc::{collections::btree_map::BTreeMap, vec, vec::Vec};
+
+use crate::{
+    Node, Rc,
+    manager::{Cache, CacheKey},
+    node::Edge,
+};
+
+impl Node {
+    /// Returns the number of assignments of the variables below `num_vars` that satisfy the
+    /// function, saturating at `u128::MAX`. The function must not depend on other variables.
+    pub fn sat_count(&self, num_vars: u64) -> u128 {
+        let _nodes = self.cache.read_nodes();
+        sat_count(&self.cache, &self.edge, num_vars)
+    }
+}
+
+fn sat_count(cache: &Cache, root: &Edge, num_vars: u64) -> u128 {
+    let mut levels = (0..num_vars)
+        .map(|var| cache.level(var))
+        .collect::<Vec<_>>();
+    levels.sort_unstable();
+    // The number of counted variables above the edge, which is all of them for the terminal.
+    let rank = |edge: &Edge| match edge.var() {
+        Some(var) => levels.partition_point(|&level| level < cache.level(var)) as u64,
+        None => num_vars,
+    };
+
+    // The counts of the regular nodes, over the variables from their rank on.
+    let mut counts = BTreeMap::new();
+    let count = |counts: &BTreeMap<CacheKey, u128>, edge: &Edge| {
+        let regular = if edge.is_terminal() {
+            1
+        } else {
+            *counts.get(&CacheKey::from(&edge.node))?
+        };
+        Some(if edge.complement {
+            pow2(num_vars - rank(edge)).saturating_sub(regular)
+        } else {
+            regular
+        })
+    };
+
+    // Children are pushed onto the stack until both of them have been counted. Only the regular
+    // nodes are counted, so the children are the stored edges rather than the cofactors.
+    let mut stack = vec![Edge::regular(Rc::clone(&root.node))];
+    while let Some(edge) = stack.last() {
+        if count(&counts, edge).is_some() {
+            stack.pop();
+            continue;
+        }
+        let (_, then_child, else_child) = edge.node.children().expect("terminals are counted");
+
+        match (count(&counts, &then_child), count(&counts, &else_child)) {
+            (Some(then_count), Some(else_count)) => {
+                let skipped = |child: &Edge| pow2((rank(child) - rank(edge)).saturating_sub(1));
+                let node_count = then_count
+                    .saturating_mul(skipped(&then_child))
+                    .saturating_add(else_count.saturating_mul(skipped(&else_child)));
+                counts.insert(CacheKey::from(&edge.node), node_count);
+                stack.pop();
+            }
+            (then_count, else_count) => {
+                if then_count.is_none() {
+                    stack.push(Edge::regular(then_child.node));
+                }
+                if else_count.is_none() {
+                    stack.push(Edge::regular(else_child.node));
+                }
+            }
+        }
+    }
+
+    let root_count = count(&counts, root).expect("root has been counted");
+    root_count.saturating_mul(pow2(rank(root)))
+}
+
+/// Returns `2^exponent`, saturating at `u128::MAX`.
+fn pow2(exponent: u64) -> u128 {
+    u32::try_from(exponent)
+        .ok()
+        .and_then(|exponent| 1u128.checked_shl(exponent))
+        .unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Manager;
+
+    #[test]
+    fn test_sat_count() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & !dd.bit(2)) | (dd.bit(1) ^ dd.bit(3));
+
+        let expected = (0..1u64 << 4)
+            .filter(|bits| node.eval((0..4).map(|i| bits & (1 << i) != 0)) == Some(true))
+            .count() as u128;
+        assert_eq!(node.sat_count(4), expected);
+        assert_eq!(node.sat_count(6), 4 * expected);
+        assert_eq!((!node.clone()).sat_count(4), 16 - expected);
+
+        dd.reorder();
+        assert_eq!(node.sat_count(4), expected);
+        assert_eq!(dd.true_node().sat_count(3), 8);
+        assert_eq!(dd.false_node().sat_count(3), 0);
+        assert_eq!(dd.true_node().sat_count(200), u128::MAX);
+    }
+
+    #[test]
+    fn test_sat_count_reordered() {
+        let dd = Manager::new();
+        // The identity relation on 4-bit numbers, which has 16 pairs.
+        let mut node = dd.true_node();
+        for i in 0..4 {
+            node &= !(dd.bit(i) ^ dd.bit(4 + i));
+        }
+        dd.reorder();
+
+        assert_eq!(node.sat_count(8), 16);
+        assert_eq!((node.clone() & dd.bit(0)).sat_count(8), 8);
+        assert_eq!(node.to_zdd(8).unwrap().count(), 16);
+    }
+}
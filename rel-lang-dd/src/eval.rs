@@ -1,32 +1,28 @@
-use crate::{Node, Rc, node};
+use alloc::vec::Vec;
+
+use crate::{Node, node::Edge};
 
 impl Node {
     pub fn eval(&self, variables: impl IntoIterator<Item = bool>) -> Option<bool> {
-        eval(&self.inner, variables.into_iter())
+        let _nodes = self.cache.read_nodes();
+        eval(&self.edge, variables.into_iter())
     }
 }
 
-fn eval(node: &Rc<node::Inner>, variables: impl Iterator<Item = bool>) -> Option<bool> {
-    let mut current_node = Rc::clone(node);
-    let mut variables = variables.enumerate();
+fn eval(edge: &Edge, variables: impl Iterator<Item = bool>) -> Option<bool> {
+    // The variables are not necessarily tested in order, so they are collected up front.
+    let variables = variables.collect::<Vec<_>>();
+    let mut current_edge = edge.clone();
     loop {
-        match &current_node.kind {
-            node::Kind::True => return Some(true),
-            node::Kind::False => return Some(false),
-            node::Kind::NonTerminal {
-                cache: _,
-                level,
-                then_child,
-                else_child,
-            } => {
-                let (_, variable) = variables.find(|&(i, _)| i as u64 == *level)?;
-                if variable {
-                    current_node = Rc::clone(then_child);
-                } else {
-                    current_node = Rc::clone(else_child);
-                }
-            }
-        }
+        // The cofactors of a complemented edge are complemented as well.
+        let Some((var, then_child, else_child)) = current_edge.children() else {
+            return Some(current_edge.is_true());
+        };
+        current_edge = if *variables.get(var as usize)? {
+            then_child
+        } else {
+            else_child
+        };
     }
 }
 
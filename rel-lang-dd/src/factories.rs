@@ -1,11 +1,11 @@
-use crate::{Manager, Node, Rc, manager::Cache, node};
+use crate::{Manager, Node, Rc, manager::Cache, node::Edge};
 
 impl Manager {
     /// Returns a node for the function: `f(b) = b[i]`.
     pub fn bit(&self, i: u64) -> Node {
         Node {
             cache: Rc::clone(&self.cache),
-            inner: bit(&self.cache, i),
+            edge: bit(&self.cache, i),
         }
     }
 
@@ -17,13 +17,13 @@ impl Manager {
         let false_node = self.cache.false_node();
         for j in (0..n).rev() {
             if j == i {
-                node = self.cache.get_or_insert(j, &node, &false_node);
+                node = self.cache.var_node(j, &node, &false_node);
             } else {
-                node = self.cache.get_or_insert(j, &false_node, &node);
+                node = self.cache.var_node(j, &false_node, &node);
             }
         }
         Node {
-            inner: node,
+            edge: node,
             cache: Rc::clone(&self.cache),
         }
     }
@@ -38,13 +38,13 @@ impl Manager {
         let false_node = self.cache.false_node();
         for (i, b) in v.into_iter().enumerate().rev() {
             if b {
-                node = self.cache.get_or_insert(i as u64, &node, &false_node);
+                node = self.cache.var_node(i as u64, &node, &false_node);
             } else {
-                node = self.cache.get_or_insert(i as u64, &false_node, &node);
+                node = self.cache.var_node(i as u64, &false_node, &node);
             }
         }
         Node {
-            inner: node,
+            edge: node,
             cache: Rc::clone(&self.cache),
         }
     }
@@ -61,19 +61,19 @@ impl Manager {
         let false_node = self.cache.false_node();
         for (i, b) in v.into_iter().enumerate().rev() {
             if b {
-                node = self.cache.get_or_insert(i as u64, &node, &true_node);
+                node = self.cache.var_node(i as u64, &node, &true_node);
             } else {
-                node = self.cache.get_or_insert(i as u64, &false_node, &node);
+                node = self.cache.var_node(i as u64, &false_node, &node);
             }
         }
         Node {
-            inner: node,
+            edge: node,
             cache: Rc::clone(&self.cache),
         }
     }
 }
 
-pub(crate) fn bit(cache: &Rc<Cache>, i: u64) -> Rc<node::Inner> {
+pub(crate) fn bit(cache: &Rc<Cache>, i: u64) -> Edge {
     let then_child = cache.true_node();
     let else_child = cache.false_node();
     cache.get_or_insert(i, &then_child, &else_child)
@@ -0,0 +1,513 @@
+//! Garbage collection of nodes that are no longer referenced, and a hard limit on the number of
+//! nodes.
+//!
+//! With the eager policy, a node is freed as soon as its last [`Node`](crate::Node) or parent is
+//! dropped. The deferred policy keeps every node alive until the number of nodes crosses a
+//! threshold, so that operations can reuse dead nodes and dropping a diagram does not cascade
+//! through the unique table.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt, mem, ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::{Manager, Rc, manager::Cache, node};
+
+/// When the nodes that are no longer referenced are freed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GcPolicy {
+    /// Frees every node as soon as the last reference to it is dropped.
+    #[default]
+    Eager,
+    /// Keeps unreferenced nodes in the unique tables, where operations can reuse them, and frees
+    /// them all at once whenever the number of nodes exceeds the threshold. The threshold then
+    /// grows to twice the number of nodes that are still referenced.
+    Deferred { threshold: usize },
+}
+
+/// An error that aborted an operation of a [`Manager`].
+///
+/// Once an error has occurred, all operations of the manager return placeholder results until the
+/// error is taken with [`Manager::take_error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An operation needed more nodes than the limit set with [`Manager::set_node_limit`].
+    NodeLimit { limit: usize },
+    /// The flag set with [`Manager::set_interrupt_flag`] was raised during an operation.
+    Interrupted,
+    /// The deadline set with `Manager::set_deadline` passed during an operation.
+    Timeout,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NodeLimit { limit } => write!(f, "node limit of {limit} exceeded"),
+            Self::Interrupted => write!(f, "operation interrupted"),
+            Self::Timeout => write!(f, "operation timed out"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The number of checks for errors between two readings of the clock.
+#[cfg(feature = "std")]
+const DEADLINE_INTERVAL: usize = 64;
+
+#[derive(Debug, Default)]
+pub(crate) struct Memory {
+    policy: GcPolicy,
+    /// The number of nodes at which garbage is collected next with the deferred policy.
+    next_collection: usize,
+    /// Keeps all nodes alive with the deferred policy, so that only garbage collection frees them.
+    retained: Vec<Rc<node::Inner>>,
+    /// Keeps every flag that was set with [`Manager::set_interrupt_flag`] alive, so that
+    /// `Status::interrupt_flag` never dangles.
+    interrupt_flags: Vec<Arc<AtomicBool>>,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+    error: Option<Error>,
+}
+
+/// The state that is read whenever a node is created or an operation takes a step, which is kept
+/// in atomics so that it does not need the lock of the [`Memory`].
+#[derive(Debug)]
+pub(crate) struct Status {
+    /// The number of nodes that were ever created, including the ones that were freed.
+    created: AtomicUsize,
+    /// Whether the policy is [`GcPolicy::Deferred`], in which case new nodes are retained.
+    deferred: AtomicBool,
+    /// The node limit, or `usize::MAX` if there is none.
+    node_limit: AtomicUsize,
+    /// The flag set with [`Manager::set_interrupt_flag`], or null if there is none.
+    interrupt_flag: AtomicPtr<AtomicBool>,
+    /// Whether `Memory::deadline` is set.
+    #[cfg(feature = "std")]
+    has_deadline: AtomicBool,
+    /// Counts the checks for errors, so that the clock is only read every few steps.
+    #[cfg(feature = "std")]
+    checks: AtomicUsize,
+    /// Whether `Memory::error` holds an error.
+    failed: AtomicBool,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            created: AtomicUsize::new(0),
+            deferred: AtomicBool::new(false),
+            node_limit: AtomicUsize::new(usize::MAX),
+            interrupt_flag: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "std")]
+            has_deadline: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            checks: AtomicUsize::new(0),
+            failed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Manager {
+    pub fn set_gc_policy(&self, policy: GcPolicy) {
+        self.cache.set_gc_policy(policy);
+    }
+
+    pub fn gc_policy(&self) -> GcPolicy {
+        self.cache.memory().policy
+    }
+
+    /// Frees the unreferenced nodes that the deferred policy keeps alive, and returns their number.
+    pub fn collect_garbage(&self) -> usize {
+        self.cache.collect_garbage()
+    }
+
+    /// Limits the number of BDD and ZDD nodes. Operations that would exceed the limit fail with
+    /// [`Error::NodeLimit`] instead, see [`Error`].
+    pub fn set_node_limit(&self, limit: Option<usize>) {
+        let limit = limit.unwrap_or(usize::MAX);
+        self.cache
+            .status()
+            .node_limit
+            .store(limit, Ordering::Relaxed);
+    }
+
+    pub fn node_limit(&self) -> Option<usize> {
+        self.cache.node_limit()
+    }
+
+    /// Makes operations fail with [`Error::Interrupted`] while the flag is set, for example by a
+    /// signal handler. The flag is only read, so the caller has to clear it again.
+    pub fn set_interrupt_flag(&self, flag: Option<Arc<AtomicBool>>) {
+        let ptr = flag
+            .as_ref()
+            .map_or(ptr::null_mut(), |flag| Arc::as_ptr(flag).cast_mut());
+        if let Some(flag) = flag {
+            let mut memory = self.cache.memory_mut();
+            if !memory.interrupt_flags.iter().any(|f| Arc::ptr_eq(f, &flag)) {
+                memory.interrupt_flags.push(flag);
+            }
+        }
+        self.cache
+            .status()
+            .interrupt_flag
+            .store(ptr, Ordering::Release);
+    }
+
+    /// Makes operations fail with [`Error::Timeout`] once the deadline has passed. The clock is
+    /// read every few steps of an operation, so even a single long operation stops in time.
+    #[cfg(feature = "std")]
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        self.cache.memory_mut().deadline = deadline;
+        let status = self.cache.status();
+        status
+            .has_deadline
+            .store(deadline.is_some(), Ordering::Release);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.cache.memory().deadline
+    }
+
+    /// Returns the number of BDD and ZDD nodes that were created so far, including the ones that
+    /// were freed since. The difference between two calls is the work of the operations in between.
+    pub fn created_node_count(&self) -> usize {
+        self.cache.status().created.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether an operation has been aborted since the last call to [`Manager::take_error`].
+    pub fn has_error(&self) -> bool {
+        self.cache.has_error()
+    }
+
+    /// Returns the error that aborted an operation since the last call, if any, and resumes normal
+    /// operation. The results of all operations since the error are meaningless.
+    pub fn take_error(&self) -> Option<Error> {
+        self.cache.take_error()
+    }
+}
+
+impl Cache {
+    fn set_gc_policy(&self, policy: GcPolicy) {
+        let retained = {
+            let mut memory = self.memory_mut();
+            memory.policy = policy;
+            let deferred = matches!(policy, GcPolicy::Deferred { .. });
+            self.status().deferred.store(deferred, Ordering::Relaxed);
+            match policy {
+                GcPolicy::Eager => mem::take(&mut memory.retained),
+                GcPolicy::Deferred { threshold } => {
+                    memory.next_collection = threshold;
+                    Vec::new()
+                }
+            }
+        };
+        // Frees the nodes that are no longer referenced, outside of the borrow of the memory.
+        drop(retained);
+        if matches!(policy, GcPolicy::Deferred { .. }) {
+            self.retain_all();
+        }
+    }
+
+    /// Counts the new node, and keeps it alive with the deferred policy.
+    pub(crate) fn retain(&self, node: &Rc<node::Inner>) {
+        let status = self.status();
+        status.created.fetch_add(1, Ordering::Relaxed);
+        if status.deferred.load(Ordering::Relaxed) {
+            self.memory_mut().retained.push(Rc::clone(node));
+        }
+    }
+
+    /// Keeps all nodes that are alive with the deferred policy.
+    pub(crate) fn retain_all(&self) {
+        let mut nodes = self.unique_table().nodes();
+        nodes.extend(self.zdd_table().nodes());
+        let mut memory = self.memory_mut();
+        if matches!(memory.policy, GcPolicy::Deferred { .. }) {
+            memory.retained = nodes;
+        }
+    }
+
+    /// Stops keeping nodes alive, which frees the ones that are no longer referenced. Returns
+    /// whether the nodes need to be retained again with [`Cache::retain_all`].
+    pub(crate) fn release_all(&self) -> bool {
+        let (deferred, retained) = {
+            let mut memory = self.memory_mut();
+            let deferred = matches!(memory.policy, GcPolicy::Deferred { .. });
+            (deferred, mem::take(&mut memory.retained))
+        };
+        drop(retained);
+        deferred
+    }
+
+    pub(crate) fn collect_garbage(&self) -> usize {
+        let mut memory = self.memory_mut();
+        let mut freed = 0;
+        loop {
+            // Nodes are usually created after their children, so visiting them backwards frees
+            // whole dead subgraphs in a single pass. Only the children of nodes that were rewritten
+            // by reordering may need another one.
+            let before = freed;
+            for i in (0..memory.retained.len()).rev() {
+                if Rc::strong_count(&memory.retained[i]) == 1 {
+                    // Dropping the node only takes the locks of the unique tables.
+                    drop(memory.retained.swap_remove(i));
+                    freed += 1;
+                }
+            }
+            if freed == before {
+                return freed;
+            }
+        }
+    }
+
+    /// Makes room for a new node, which fails if the node limit has been reached.
+    pub(crate) fn reserve_node(&self) -> Result<(), Error> {
+        let node_limit = self.node_limit();
+        let is_full = |node_count: usize| node_limit.is_some_and(|limit| node_count >= limit);
+
+        if self.status().deferred.load(Ordering::Relaxed) {
+            let (policy, next_collection) = {
+                let memory = self.memory();
+                (memory.policy, memory.next_collection)
+            };
+            if let GcPolicy::Deferred { threshold } = policy {
+                let node_count = self.total_node_count();
+                if node_count >= next_collection || is_full(node_count) {
+                    self.collect_garbage();
+                    let node_count = self.total_node_count();
+                    self.memory_mut().next_collection = threshold.max(2 * node_count);
+                }
+            }
+        }
+
+        match node_limit {
+            Some(limit) if is_full(self.total_node_count()) => Err(Error::NodeLimit { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn node_limit(&self) -> Option<usize> {
+        let limit = self.status().node_limit.load(Ordering::Relaxed);
+        (limit != usize::MAX).then_some(limit)
+    }
+
+    /// Returns whether an error has occurred, which includes the interrupt flag being set.
+    pub(crate) fn has_error(&self) -> bool {
+        let status = self.status();
+        if status.failed.load(Ordering::Acquire) {
+            return true;
+        }
+        let flag = status.interrupt_flag.load(Ordering::Acquire);
+        // SAFETY: the flags that were set are kept alive in `Memory::interrupt_flags`.
+        if !flag.is_null() && unsafe { &*flag }.load(Ordering::Relaxed) {
+            self.fail(Error::Interrupted);
+            return true;
+        }
+        #[cfg(feature = "std")]
+        if status.has_deadline.load(Ordering::Acquire)
+            && status
+                .checks
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(DEADLINE_INTERVAL)
+            && self
+                .memory()
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.fail(Error::Timeout);
+            return true;
+        }
+        false
+    }
+
+    pub(crate) fn take_error(&self) -> Option<Error> {
+        let mut memory = self.memory_mut();
+        self.status().failed.store(false, Ordering::Release);
+        memory.error.take()
+    }
+
+    /// Fails with the error that aborted an operation since the last call to
+    /// [`Manager::take_error`], for the operations that return their errors instead of placeholder
+    /// results.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self.take_error() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the error, which makes all further operations return placeholder results.
+    pub(crate) fn fail(&self, error: Error) {
+        let mut memory = self.memory_mut();
+        memory.error.get_or_insert(error);
+        self.status().failed.store(true, Ordering::Release);
+    }
+
+    fn total_node_count(&self) -> usize {
+        self.node_count() + self.zdd_table().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{Error, GcPolicy, Manager, Node};
+
+    fn chain(dd: &Manager, n: u64) -> Node {
+        (0..n).fold(dd.true_node(), |node, i| node & dd.bit(i))
+    }
+
+    #[test]
+    fn test_deferred() {
+        let dd = Manager::new();
+        dd.set_gc_policy(GcPolicy::Deferred { threshold: 1000 });
+
+        let node = chain(&dd, 10);
+        drop(node);
+        // The nodes are kept alive and reused.
+        let node_count = dd.node_count();
+        assert!(node_count >= 10);
+        assert_eq!(dd.created_node_count(), node_count);
+        let node = chain(&dd, 10);
+        assert_eq!(dd.node_count(), node_count);
+        assert_eq!(dd.created_node_count(), node_count);
+
+        let freed = dd.collect_garbage();
+        assert_eq!(dd.node_count(), 10);
+        assert_eq!(freed, node_count - 10);
+        assert_eq!(node, chain(&dd, 10));
+
+        drop(node);
+        dd.collect_garbage();
+        assert_eq!(dd.node_count(), 0);
+    }
+
+    #[test]
+    fn test_deferred_threshold() {
+        let dd = Manager::new();
+        dd.set_gc_policy(GcPolicy::Deferred { threshold: 50 });
+
+        for _ in 0..10 {
+            drop(chain(&dd, 20));
+            drop(chain(&dd, 20).shift(20));
+        }
+
+        assert!(dd.node_count() <= 100);
+    }
+
+    #[test]
+    fn test_switch_policy() {
+        let dd = Manager::new();
+        let node = chain(&dd, 5);
+        dd.set_gc_policy(GcPolicy::Deferred { threshold: 1000 });
+        drop(node);
+        assert_eq!(dd.node_count(), 5);
+
+        dd.set_gc_policy(GcPolicy::Eager);
+
+        assert_eq!(dd.gc_policy(), GcPolicy::Eager);
+        assert_eq!(dd.node_count(), 0);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let dd = Manager::new();
+        dd.set_node_limit(Some(10));
+
+        let small = chain(&dd, 5);
+        assert_eq!(dd.take_error(), None);
+
+        let large = chain(&dd, 20);
+        assert_eq!(dd.take_error(), Some(Error::NodeLimit { limit: 10 }));
+        assert_eq!(dd.take_error(), None);
+        drop(large);
+
+        dd.set_node_limit(None);
+        assert_eq!(chain(&dd, 20).size(), 20);
+        assert_eq!(small, chain(&dd, 5));
+    }
+
+    #[test]
+    fn test_interrupt_flag() {
+        let dd = Manager::new();
+        let flag = Arc::new(AtomicBool::new(false));
+        dd.set_interrupt_flag(Some(Arc::clone(&flag)));
+
+        let node = chain(&dd, 5);
+        assert_eq!(dd.take_error(), None);
+
+        flag.store(true, Ordering::Relaxed);
+        drop(chain(&dd, 10));
+        assert_eq!(dd.take_error(), Some(Error::Interrupted));
+
+        flag.store(false, Ordering::Relaxed);
+        assert_eq!(chain(&dd, 5), node);
+        assert_eq!(dd.take_error(), None);
+    }
+
+    #[test]
+    fn test_replace_interrupt_flag() {
+        let dd = Manager::new();
+        let old_flag = Arc::new(AtomicBool::new(true));
+        let new_flag = Arc::new(AtomicBool::new(false));
+        dd.set_interrupt_flag(Some(Arc::clone(&old_flag)));
+        dd.set_interrupt_flag(Some(Arc::clone(&new_flag)));
+        drop(old_flag);
+
+        assert_eq!(chain(&dd, 5).size(), 5);
+        assert!(!dd.has_error());
+
+        new_flag.store(true, Ordering::Relaxed);
+        assert!(dd.has_error());
+        dd.set_interrupt_flag(None);
+        assert_eq!(dd.take_error(), Some(Error::Interrupted));
+        assert!(!dd.has_error());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_deadline() {
+        use std::time::{Duration, Instant};
+
+        let dd = Manager::new();
+        let n = 20;
+        // The bits of x and y are interleaved, which takes three nodes per bit.
+        let interleaved = (0..n).fold(dd.true_node(), |node, i| {
+            node & !(dd.bit(2 * i) ^ dd.bit(2 * i + 1))
+        });
+        assert_eq!(dd.take_error(), None);
+
+        // Moving all bits of y after the bits of x takes millions of nodes in a single operation.
+        let start = Instant::now();
+        dd.set_deadline(Some(start + Duration::from_millis(50)));
+        drop(interleaved.rename(|var| if var % 2 == 0 { var / 2 } else { n + var / 2 }));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(dd.take_error(), Some(Error::Timeout));
+
+        dd.set_deadline(None);
+        assert_eq!(interleaved.rename(|var| var + 1).size(), 3 * n as usize - 1);
+        assert_eq!(dd.take_error(), None);
+    }
+
+    #[test]
+    fn test_node_limit_collects_garbage() {
+        let dd = Manager::new();
+        dd.set_gc_policy(GcPolicy::Deferred { threshold: 1000 });
+        dd.set_node_limit(Some(50));
+
+        for _ in 0..5 {
+            drop(chain(&dd, 20).shift(1));
+        }
+
+        assert_eq!(dd.take_error(), None);
+    }
+}
@@ -11,11 +11,44 @@ use core::cell::RefCell as Lock;
 #[cfg(feature = "sync")]
 use std::sync::{Arc as Rc, RwLock as Lock, Weak};
 
+use core::ops::{Deref, DerefMut};
+
+mod add;
+mod cofactor;
+mod count;
 mod eval;
 mod factories;
+mod gc;
 mod manager;
 mod node;
 mod ops;
+mod parallel;
+mod quantify;
+mod reorder;
+mod serialize;
 mod shift;
+mod unique;
+mod zdd;
+
+pub use crate::{
+    add::{Add, AddManager},
+    gc::{Error, GcPolicy},
+    manager::Manager,
+    node::{Node, NodeId},
+    serialize::{DecodeError, FORMAT_VERSION},
+    zdd::Zdd,
+};
+
+pub(crate) fn read<U>(lock: &Lock<U>) -> impl Deref<Target = U> + '_ {
+    #[cfg(not(feature = "sync"))]
+    return lock.borrow();
+    #[cfg(feature = "sync")]
+    return lock.read().unwrap();
+}
 
-pub use crate::{manager::Manager, node::Node};
+pub(crate) fn write<U>(lock: &Lock<U>) -> impl DerefMut<Target = U> + '_ {
+    #[cfg(not(feature = "sync"))]
+    return lock.borrow_mut();
+    #[cfg(feature = "sync")]
+    return lock.write().unwrap();
+}
@@ -1,10 +1,15 @@
 use core::ops::{Deref, DerefMut};
 
-use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
 
 use crate::{
-    Lock, Rc, Weak,
-    node::{self, Kind, Node},
+    Lock, Rc,
+    gc::{Memory, Status},
+    node::{self, Diagram, Edge, Kind, Node},
+    ops::if_then_else,
+    read,
+    unique::UniqueTable,
+    write,
 };
 
 #[derive(Debug, Clone)]
@@ -15,15 +20,30 @@ pub struct Manager {
 #[derive(Debug)]
 pub(crate) struct Cache {
     true_node: Rc<node::Inner>,
-    false_node: Rc<node::Inner>,
-    unique_cache: Lock<UniqueCache>,
+    unique_table: UniqueTable,
+    zdd_table: UniqueTable,
+    order: Lock<Order>,
+    memory: Lock<Memory>,
+    status: Status,
+    /// Held shared by the operations that read nodes, and exclusively by reordering, which
+    /// rewrites nodes in place.
+    nodes: Lock<()>,
 }
 
-pub(crate) type UniqueCache = BTreeMap<(u64, CacheKey, CacheKey), Weak<node::Inner>>;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct CacheKey {
     ptr: *const node::Inner,
+    complement: bool,
+}
+
+/// The permutation between variables and the levels at which they are tested, which is the identity
+/// beyond the end of the vectors.
+#[derive(Debug, Default)]
+pub(crate) struct Order {
+    pub(crate) var_to_level: Vec<u64>,
+    pub(crate) level_to_var: Vec<u64>,
+    /// Sift the variables whenever the number of nodes exceeds this threshold.
+    pub(crate) reorder_threshold: Option<usize>,
 }
 
 #[cfg(feature = "sync")]
@@ -42,23 +62,22 @@ impl Manager {
     pub fn true_node(&self) -> Node {
         Node {
             cache: Rc::clone(&self.cache),
-            inner: Rc::clone(&self.cache.true_node),
+            edge: self.cache.true_node(),
         }
     }
 
     pub fn false_node(&self) -> Node {
         Node {
             cache: Rc::clone(&self.cache),
-            inner: Rc::clone(&self.cache.false_node),
+            edge: self.cache.false_node(),
         }
     }
 
-    pub fn get_or_insert(&self, level: u64, then_child: &Node, else_child: &Node) -> Node {
+    /// Returns a node for the function `if b[var] then then_child else else_child`.
+    pub fn get_or_insert(&self, var: u64, then_child: &Node, else_child: &Node) -> Node {
         Node {
             cache: Rc::clone(&self.cache),
-            inner: self
-                .cache
-                .get_or_insert(level, &then_child.inner, &else_child.inner),
+            edge: self.cache.var_node(var, &then_child.edge, &else_child.edge),
         }
     }
 }
@@ -72,110 +91,278 @@ impl Default for Manager {
 impl Cache {
     fn new() -> Self {
         Self {
-            unique_cache: Lock::new(BTreeMap::new()),
-            true_node: Rc::new(node::Inner { kind: Kind::True }),
-            false_node: Rc::new(node::Inner { kind: Kind::False }),
+            unique_table: UniqueTable::new(),
+            zdd_table: UniqueTable::new(),
+            order: Lock::new(Order::default()),
+            memory: Lock::new(Memory::default()),
+            status: Status::default(),
+            nodes: Lock::new(()),
+            true_node: Rc::new(node::Inner::new(Kind::True)),
         }
     }
 
-    pub(crate) fn true_node(&self) -> Rc<node::Inner> {
-        Rc::clone(&self.true_node)
+    pub(crate) fn true_node(&self) -> Edge {
+        Edge::regular(Rc::clone(&self.true_node))
+    }
+
+    pub(crate) fn false_node(&self) -> Edge {
+        self.true_node().not()
     }
 
-    pub(crate) fn false_node(&self) -> Rc<node::Inner> {
-        Rc::clone(&self.false_node)
+    /// Returns the level at which the variable is tested.
+    pub(crate) fn level(&self, var: u64) -> u64 {
+        let order = self.order();
+        order.var_to_level.get(var as usize).copied().unwrap_or(var)
     }
 
+    /// Returns the variable that is tested at the level.
+    pub(crate) fn var(&self, level: u64) -> u64 {
+        let order = self.order();
+        order
+            .level_to_var
+            .get(level as usize)
+            .copied()
+            .unwrap_or(level)
+    }
+
+    /// Returns the node for `if b[var] then then_child else else_child`, even if the children are
+    /// not below the level of `var`.
+    pub(crate) fn var_node(
+        self: &Rc<Self>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+    ) -> Edge {
+        let _nodes = self.read_nodes();
+        let level = self.level(var);
+        let is_below = |child: &Edge| child.var().is_none_or(|v| self.level(v) > level);
+        if is_below(then_child) && is_below(else_child) {
+            self.get_or_insert(var, then_child, else_child)
+        } else {
+            let condition = self.get_or_insert(var, &self.true_node(), &self.false_node());
+            if_then_else(self, &condition, then_child, else_child)
+        }
+    }
+
+    /// Returns the unique node with the given variable and children, which must be below the level
+    /// of the variable.
+    ///
+    /// Returns a placeholder if the node limit is reached or another error occurred, see
+    /// [`crate::Error`].
     pub(crate) fn get_or_insert(
         self: &Rc<Self>,
-        level: u64,
-        then_child: &Rc<node::Inner>,
-        else_child: &Rc<node::Inner>,
-    ) -> Rc<node::Inner> {
-        if Rc::ptr_eq(then_child, else_child) {
-            return Rc::clone(then_child);
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+    ) -> Edge {
+        if self.has_error() {
+            return self.false_node();
+        }
+        self.get_or_insert_with(var, then_child, else_child, true)
+    }
+
+    /// Like [`Cache::get_or_insert`], but ignores the node limit. Used by reordering, which must
+    /// not fail halfway.
+    pub(crate) fn get_or_insert_unchecked(
+        self: &Rc<Self>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+    ) -> Edge {
+        self.get_or_insert_with(var, then_child, else_child, false)
+    }
+
+    fn get_or_insert_with(
+        self: &Rc<Self>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+        checked: bool,
+    ) -> Edge {
+        if then_child.ptr_eq(else_child) {
+            return then_child.clone();
+        }
+        if then_child.complement {
+            // Keep the then edge regular by moving the complement to the incoming edge.
+            return self
+                .get_or_insert_with(var, &then_child.not(), &else_child.not(), checked)
+                .not();
         }
 
-        if let Some(node) = self.get(level, then_child, else_child) {
-            return node;
+        if let Some(node) = self.get(var, then_child, else_child) {
+            return Edge::regular(node);
+        }
+        if checked && let Err(error) = self.reserve_node() {
+            self.fail(error);
+            return self.false_node();
         }
 
-        let new_node = Rc::new(node::Inner {
-            kind: Kind::NonTerminal {
-                level,
-                then_child: Rc::clone(then_child),
-                else_child: Rc::clone(else_child),
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        let (node, inserted) = self.unique_table.get_or_insert_with(key, || {
+            Rc::new(node::Inner::new(Kind::NonTerminal {
+                var,
+                then_child: then_child.clone(),
+                else_child: else_child.clone(),
+                diagram: Diagram::Bdd,
                 cache: Rc::downgrade(self),
-            },
+            }))
         });
-        self.insert(level, then_child, else_child, &new_node);
+        if inserted {
+            self.retain(&node);
+        }
 
-        new_node
+        Edge::regular(node)
     }
 
-    fn get(
-        &self,
-        level: u64,
-        then_child: &Rc<node::Inner>,
-        else_child: &Rc<node::Inner>,
-    ) -> Option<Rc<node::Inner>> {
-        let key = (
-            level,
-            CacheKey::from(then_child),
-            CacheKey::from(else_child),
-        );
-        let unique_cache = self.unique_cache();
-        unique_cache.get(&key).and_then(Weak::upgrade)
-    }
-
-    fn insert(
+    /// Returns the unique ZDD node with the given variable and children, whose variables must be
+    /// greater than `var`. ZDD nodes are ordered by their variables rather than by levels.
+    pub(crate) fn zdd_node(
+        self: &Rc<Self>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+    ) -> Edge {
+        // The sets that contain the variable are suppressed if there are none.
+        if then_child.is_false() {
+            return else_child.clone();
+        }
+        if self.has_error() {
+            return self.false_node();
+        }
+
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        if let Some(node) = self.zdd_table.get(&key) {
+            return Edge::regular(node);
+        }
+        if let Err(error) = self.reserve_node() {
+            self.fail(error);
+            return self.false_node();
+        }
+
+        let (node, inserted) = self.zdd_table.get_or_insert_with(key, || {
+            Rc::new(node::Inner::new(Kind::NonTerminal {
+                var,
+                then_child: then_child.clone(),
+                else_child: else_child.clone(),
+                diagram: Diagram::Zdd,
+                cache: Rc::downgrade(self),
+            }))
+        });
+        if inserted {
+            self.retain(&node);
+        }
+
+        Edge::regular(node)
+    }
+
+    fn get(&self, var: u64, then_child: &Edge, else_child: &Edge) -> Option<Rc<node::Inner>> {
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        self.unique_table.get(&key)
+    }
+
+    pub(crate) fn insert(
         &self,
-        level: u64,
-        then_child: &Rc<node::Inner>,
-        else_child: &Rc<node::Inner>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
         node: &Rc<node::Inner>,
     ) {
-        let key = (
-            level,
-            CacheKey::from(then_child),
-            CacheKey::from(else_child),
-        );
-        let mut unique_cache = self.unique_cache_mut();
-        unique_cache.insert(key, Rc::downgrade(node));
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        self.unique_table.insert(key, node);
     }
 
     pub(crate) fn remove(
         &self,
-        level: u64,
-        then_child: &Rc<node::Inner>,
-        else_child: &Rc<node::Inner>,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+        node: *const node::Inner,
     ) {
-        let key = (
-            level,
-            CacheKey::from(then_child),
-            CacheKey::from(else_child),
-        );
-        let mut unique_cache = self.unique_cache_mut();
-        unique_cache.remove(&key);
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        self.unique_table.remove(&key, node);
     }
 
-    fn unique_cache(&self) -> impl Deref<Target = UniqueCache> {
-        #[cfg(not(feature = "sync"))]
-        return self.unique_cache.borrow();
-        #[cfg(feature = "sync")]
-        return self.unique_cache.read().unwrap();
+    pub(crate) fn remove_zdd(
+        &self,
+        var: u64,
+        then_child: &Edge,
+        else_child: &Edge,
+        node: *const node::Inner,
+    ) {
+        let key = (var, CacheKey::from(then_child), CacheKey::from(else_child));
+        self.zdd_table.remove(&key, node);
+    }
+
+    pub(crate) fn unique_table(&self) -> &UniqueTable {
+        &self.unique_table
+    }
+
+    pub(crate) fn zdd_table(&self) -> &UniqueTable {
+        &self.zdd_table
+    }
+
+    pub(crate) fn order(&self) -> impl Deref<Target = Order> {
+        read(&self.order)
+    }
+
+    pub(crate) fn order_mut(&self) -> impl DerefMut<Target = Order> {
+        write(&self.order)
+    }
+
+    /// Keeps reordering from rewriting the nodes until the guard is dropped. Operations hold the
+    /// nodes while they read them, and may hold them more than once.
+    pub(crate) fn read_nodes(&self) -> impl Deref<Target = ()> + '_ {
+        read(&self.nodes)
     }
 
-    fn unique_cache_mut(&self) -> impl DerefMut<Target = UniqueCache> {
+    /// Holds the nodes exclusively for reordering, unless an operation is reading them.
+    pub(crate) fn try_write_nodes(&self) -> Option<impl DerefMut<Target = ()> + '_> {
         #[cfg(not(feature = "sync"))]
-        return self.unique_cache.borrow_mut();
+        return self.nodes.try_borrow_mut().ok();
         #[cfg(feature = "sync")]
-        return self.unique_cache.write().unwrap();
+        return self.nodes.try_write().ok();
+    }
+
+    pub(crate) fn memory(&self) -> impl Deref<Target = Memory> {
+        read(&self.memory)
+    }
+
+    pub(crate) fn memory_mut(&self) -> impl DerefMut<Target = Memory> {
+        write(&self.memory)
+    }
+
+    pub(crate) fn status(&self) -> &Status {
+        &self.status
     }
 }
 
+impl CacheKey {
+    /// Returns the address of the node, with the complement in the lowest bit.
+    pub(crate) fn addr(&self) -> usize {
+        self.ptr as usize | self.complement as usize
+    }
+
+    /// Compares less than or equal to the keys of all nodes.
+    pub(crate) const MIN: Self = Self {
+        ptr: core::ptr::null(),
+        complement: false,
+    };
+}
+
 impl From<&Rc<node::Inner>> for CacheKey {
     fn from(node: &Rc<node::Inner>) -> Self {
-        Self { ptr: node.as_ref() }
+        Self {
+            ptr: node.as_ref(),
+            complement: false,
+        }
+    }
+}
+
+impl From<&Edge> for CacheKey {
+    fn from(edge: &Edge) -> Self {
+        Self {
+            ptr: edge.node.as_ref(),
+            complement: edge.complement,
+        }
     }
 }
@@ -1,57 +1,251 @@
-use crate::{Rc, Weak, manager::Cache};
+use alloc::{collections::btree_set::BTreeSet, vec, vec::Vec};
+use core::{cell::UnsafeCell, mem};
+
+use crate::{
+    Rc, Weak,
+    manager::{Cache, CacheKey},
+};
 
 #[derive(Debug, Clone)]
 pub struct Node {
     pub(crate) cache: Rc<Cache>,
-    pub(crate) inner: Rc<Inner>,
+    pub(crate) edge: Edge,
+}
+
+/// Identifies the function of a [`Node`], as long as the node is alive. Nodes are canonical, so
+/// nodes of the same manager have the same id if and only if they represent the same function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// A reference to a node, which represents the negation of the node's function if it is
+/// complemented.
+///
+/// The then edge of a non-terminal node is never complemented, which makes the representation
+/// canonical: `false` is the complemented edge to the single terminal node.
+#[derive(Debug, Clone)]
+pub(crate) struct Edge {
+    pub(crate) node: Rc<Inner>,
+    pub(crate) complement: bool,
 }
 
 #[derive(Debug)]
 pub(crate) struct Inner {
-    pub(crate) kind: Kind,
+    // Only replaced when adjacent levels are swapped during reordering, which rewrites the node in
+    // place so that it keeps representing the same function. Reordering holds the nodes of the
+    // manager exclusively, see `Cache::read_nodes`, so nodes are immutable to everyone else.
+    kind: UnsafeCell<Kind>,
 }
 
 #[derive(Debug)]
 pub(crate) enum Kind {
     True,
-    False,
     NonTerminal {
-        // level == variable index (i.e. variables are not permuted)
-        level: u64,
-        then_child: Rc<Inner>,
-        else_child: Rc<Inner>,
+        // The level of the variable is looked up in the manager, see `Cache::level`.
+        var: u64,
+        then_child: Edge,
+        else_child: Edge,
+        // The unique table that the node belongs to.
+        diagram: Diagram,
         // Only used in the `Drop` implementation to remove the node from the cache.
         // Does not need to be weak, but we're less likely to leak memory later if we use a weak reference.
-        // This is only present for this variant, otherwise the true and false singletons would need to be lazily initialized.
+        // This is only present for this variant, otherwise the true singleton would need to be lazily initialized.
         cache: Weak<Cache>,
     },
 }
 
+/// The kind of decision diagram that a non-terminal node belongs to. BDD and ZDD nodes have
+/// different reduction rules, so they are kept in separate unique tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Diagram {
+    Bdd,
+    Zdd,
+}
+
+// SAFETY: the kind is only replaced while reordering holds the nodes of the manager exclusively,
+// and all other accesses happen while they are held shared.
+#[cfg(feature = "sync")]
+unsafe impl Send for Inner {}
+
+#[cfg(feature = "sync")]
+unsafe impl Sync for Inner {}
+
 impl Node {
     pub fn is_true(&self) -> bool {
-        matches!(self.inner.kind, Kind::True)
+        let _nodes = self.cache.read_nodes();
+        self.edge.is_true()
     }
 
     pub fn is_false(&self) -> bool {
-        matches!(self.inner.kind, Kind::False)
+        let _nodes = self.cache.read_nodes();
+        self.edge.is_false()
+    }
+
+    /// Returns the number of non-terminal nodes in the diagram.
+    pub fn size(&self) -> usize {
+        let _nodes = self.cache.read_nodes();
+        size(&self.edge)
+    }
+
+    pub fn id(&self) -> NodeId {
+        NodeId(CacheKey::from(&self.edge).addr())
+    }
+}
+
+/// Returns the number of non-terminal nodes reachable from the edge.
+pub(crate) fn size(edge: &Edge) -> usize {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![Rc::clone(&edge.node)];
+    while let Some(node) = stack.pop() {
+        if let Some((_, then_child, else_child)) = node.children()
+            && visited.insert(CacheKey::from(&node))
+        {
+            stack.push(then_child.node);
+            stack.push(else_child.node);
+        }
+    }
+    visited.len()
+}
+
+impl Edge {
+    pub(crate) fn regular(node: Rc<Inner>) -> Self {
+        Self {
+            node,
+            complement: false,
+        }
+    }
+
+    pub(crate) fn is_true(&self) -> bool {
+        self.node.is_terminal() && !self.complement
+    }
+
+    pub(crate) fn is_false(&self) -> bool {
+        self.node.is_terminal() && self.complement
+    }
+
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.node.is_terminal()
+    }
+
+    pub(crate) fn var(&self) -> Option<u64> {
+        self.node.var()
+    }
+
+    /// Returns the variable and the cofactors of the function of a non-terminal edge.
+    pub(crate) fn children(&self) -> Option<(u64, Edge, Edge)> {
+        let (var, then_child, else_child) = self.node.children()?;
+        Some((
+            var,
+            then_child.complement_if(self.complement),
+            else_child.complement_if(self.complement),
+        ))
+    }
+
+    pub(crate) fn complement_if(self, complement: bool) -> Self {
+        Self {
+            complement: self.complement ^ complement,
+            ..self
+        }
+    }
+
+    pub(crate) fn not(&self) -> Self {
+        self.clone().complement_if(true)
+    }
+
+    /// Whether both edges point to the same node with the same complement.
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        CacheKey::from(self) == CacheKey::from(other)
+    }
+}
+
+impl Inner {
+    pub(crate) fn new(kind: Kind) -> Self {
+        Self {
+            kind: UnsafeCell::new(kind),
+        }
+    }
+
+    /// Must only be called while the nodes of the manager are held, see `Cache::read_nodes`.
+    pub(crate) fn kind(&self) -> &Kind {
+        // SAFETY: the kind is not replaced while the nodes are held shared.
+        unsafe { &*self.kind.get() }
+    }
+
+    /// Rewrites the node in place and returns its old kind.
+    ///
+    /// # Safety
+    ///
+    /// The nodes of the manager must be held exclusively, see `Cache::try_write_nodes`.
+    pub(crate) unsafe fn replace_kind(&self, kind: Kind) -> Kind {
+        // SAFETY: nobody else reads or writes the kind while the nodes are held exclusively.
+        mem::replace(unsafe { &mut *self.kind.get() }, kind)
+    }
+
+    pub(crate) fn is_terminal(&self) -> bool {
+        matches!(self.kind(), Kind::True)
+    }
+
+    pub(crate) fn var(&self) -> Option<u64> {
+        match *self.kind() {
+            Kind::NonTerminal { var, .. } => Some(var),
+            Kind::True => None,
+        }
+    }
+
+    /// Returns the variable and the children of a non-terminal node.
+    pub(crate) fn children(&self) -> Option<(u64, Edge, Edge)> {
+        match self.kind() {
+            Kind::NonTerminal {
+                var,
+                then_child,
+                else_child,
+                ..
+            } => Some((*var, then_child.clone(), else_child.clone())),
+            Kind::True => None,
+        }
     }
 }
 
 impl Drop for Inner {
     fn drop(&mut self) {
-        match &self.kind {
-            Kind::True | Kind::False => {
+        // Dropping the children could in turn drop their children, so nodes that are no longer
+        // referenced are torn down with an explicit stack instead of recursively.
+        let mut stack = Vec::new();
+        let ptr: *const Self = self;
+        self.take_children(ptr, &mut stack);
+        while let Some(edge) = stack.pop() {
+            // The node is moved out of its allocation, but its entry in the unique table still
+            // refers to the allocation.
+            let ptr = Rc::as_ptr(&edge.node);
+            if let Some(mut node) = Rc::into_inner(edge.node) {
+                node.take_children(ptr, &mut stack);
+            }
+        }
+    }
+}
+
+impl Inner {
+    /// Removes the node, whose allocation is at `ptr`, from the unique table, and moves its children
+    /// onto the stack, which leaves nothing to do when the node itself is dropped.
+    fn take_children(&mut self, ptr: *const Self, stack: &mut Vec<Edge>) {
+        match mem::replace(self.kind.get_mut(), Kind::True) {
+            Kind::True => {
                 // No cleanup needed.
             }
             Kind::NonTerminal {
-                level,
+                var,
                 then_child,
                 else_child,
+                diagram,
                 cache,
             } => {
                 if let Some(cache) = cache.upgrade() {
-                    cache.remove(*level, then_child, else_child);
+                    match diagram {
+                        Diagram::Bdd => cache.remove(var, &then_child, &else_child, ptr),
+                        Diagram::Zdd => cache.remove_zdd(var, &then_child, &else_child, ptr),
+                    }
                 }
+                stack.push(then_child);
+                stack.push(else_child);
             }
         }
     }
@@ -1,3 +1,8 @@
+use alloc::{
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    vec,
+    vec::Vec,
+};
 use core::{cmp::Ordering, ops};
 
 use crate::Rc;
@@ -5,12 +10,13 @@ use crate::Rc;
 use crate::{
     Node,
     manager::{Cache, CacheKey},
-    node,
+    node::Edge,
 };
 
 impl PartialEq for Node {
     fn eq(&self, other: &Self) -> bool {
-        eq(&self.inner, &other.inner)
+        let _nodes = self.cache.read_nodes();
+        eq(&self.edge, &other.edge)
     }
 }
 
@@ -22,14 +28,16 @@ impl ops::BitAnd for Node {
     fn bitand(self, rhs: Self) -> Self::Output {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: and(&self.cache, &self.inner, &rhs.inner),
+            edge: and(&self.cache, &self.edge, &rhs.edge),
         }
+        .reorder_if_needed()
     }
 }
 
 impl ops::BitAndAssign for Node {
     fn bitand_assign(&mut self, rhs: Self) {
-        self.inner = and(&self.cache, &self.inner, &rhs.inner);
+        self.edge = and(&self.cache, &self.edge, &rhs.edge);
+        self.cache.reorder_if_needed();
     }
 }
 
@@ -39,14 +47,16 @@ impl ops::BitOr for Node {
     fn bitor(self, rhs: Self) -> Self::Output {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: or(&self.cache, &self.inner, &rhs.inner),
+            edge: or(&self.cache, &self.edge, &rhs.edge),
         }
+        .reorder_if_needed()
     }
 }
 
 impl ops::BitOrAssign for Node {
     fn bitor_assign(&mut self, rhs: Self) {
-        self.inner = or(&self.cache, &self.inner, &rhs.inner);
+        self.edge = or(&self.cache, &self.edge, &rhs.edge);
+        self.cache.reorder_if_needed();
     }
 }
 
@@ -56,14 +66,16 @@ impl ops::BitXor for Node {
     fn bitxor(self, rhs: Self) -> Self::Output {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: xor(&self.cache, &self.inner, &rhs.inner),
+            edge: xor(&self.cache, &self.edge, &rhs.edge),
         }
+        .reorder_if_needed()
     }
 }
 
 impl ops::BitXorAssign for Node {
     fn bitxor_assign(&mut self, rhs: Self) {
-        self.inner = xor(&self.cache, &self.inner, &rhs.inner);
+        self.edge = xor(&self.cache, &self.edge, &rhs.edge);
+        self.cache.reorder_if_needed();
     }
 }
 
@@ -71,9 +83,10 @@ impl ops::Not for Node {
     type Output = Self;
 
     fn not(self) -> Self::Output {
+        // Only flips the complement bit, so no nodes are created.
         Self {
-            cache: Rc::clone(&self.cache),
-            inner: not(&self.cache, &self.inner),
+            edge: self.edge.not(),
+            ..self
         }
     }
 }
@@ -83,282 +96,356 @@ impl Node {
     pub fn implies(&self, rhs: &Self) -> Self {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: implies(&self.cache, &self.inner, &rhs.inner),
+            edge: implies(&self.cache, &self.edge, &rhs.edge),
         }
+        .reorder_if_needed()
     }
 
     /// Returns a new node representing the function `if f then g else h`.
     pub fn if_then_else(&self, then_value: &Self, else_value: &Self) -> Self {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: if_then_else(
-                &self.cache,
-                &self.inner,
-                &then_value.inner,
-                &else_value.inner,
-            ),
+            edge: if_then_else(&self.cache, &self.edge, &then_value.edge, &else_value.edge),
         }
+        .reorder_if_needed()
     }
 }
 
-fn eq(f: &Rc<node::Inner>, g: &Rc<node::Inner>) -> bool {
-    if CacheKey::from(f) == CacheKey::from(g) {
-        return true;
-    }
+fn eq(f: &Edge, g: &Edge) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![(f.clone(), g.clone())];
+    while let Some((f, g)) = stack.pop() {
+        if f.ptr_eq(&g) || !visited.insert((CacheKey::from(&f), CacheKey::from(&g))) {
+            continue;
+        }
 
-    match (&f.kind, &g.kind) {
-        (node::Kind::True, node::Kind::True) => true,
-        (node::Kind::False, node::Kind::False) => true,
-        (
-            node::Kind::NonTerminal {
-                level: lhs_level,
-                then_child: lhs_then,
-                else_child: lhs_else,
-                cache: _,
-            },
-            node::Kind::NonTerminal {
-                level: rhs_level,
-                then_child: rhs_then,
-                else_child: rhs_else,
-                cache: _,
-            },
-        ) => lhs_level == rhs_level && eq(lhs_then, rhs_then) && eq(lhs_else, rhs_else),
-        _ => false,
+        match (f.children(), g.children()) {
+            (None, None) if f.complement == g.complement => {}
+            (Some((lhs_var, lhs_then, lhs_else)), Some((rhs_var, rhs_then, rhs_else)))
+                if lhs_var == rhs_var =>
+            {
+                stack.push((lhs_then, rhs_then));
+                stack.push((lhs_else, rhs_else));
+            }
+            _ => return false,
+        }
     }
+    true
 }
 
-fn and(cache: &Rc<Cache>, f: &Rc<node::Inner>, g: &Rc<node::Inner>) -> Rc<node::Inner> {
-    if CacheKey::from(f) == CacheKey::from(g) {
-        return Rc::clone(f);
-    }
+fn and(cache: &Rc<Cache>, f: &Edge, g: &Edge) -> Edge {
+    cache.par_apply(
+        (f.clone(), g.clone()),
+        |(f, g)| (CacheKey::from(f), CacheKey::from(g)),
+        |(f, g)| {
+            if f.ptr_eq(&g) {
+                return Step::Done(f);
+            }
+            if f.ptr_eq(&g.not()) || f.is_false() || g.is_false() {
+                return Step::Done(cache.false_node());
+            }
+            if g.is_true() {
+                return Step::Done(f);
+            }
+            if f.is_true() {
+                return Step::Done(g);
+            }
+
+            let (var, fv, fnv, gv, gnv) = split_on_next_var(cache, &f, &g);
+            Step::Split(var, (fv, gv), (fnv, gnv))
+        },
+        |var, new_then, new_else| cache.get_or_insert(var, &new_then, &new_else),
+    )
+}
 
-    match (&f.kind, &g.kind) {
-        (node::Kind::False, _) | (_, node::Kind::False) => cache.false_node(),
-        (_, node::Kind::True) => Rc::clone(f),
-        (node::Kind::True, _) => Rc::clone(g),
-        (node::Kind::NonTerminal { .. }, node::Kind::NonTerminal { .. }) => {
-            let (level, fv, fnv, gv, gnv) = split_on_next_var(f, g);
+pub(crate) fn or(cache: &Rc<Cache>, f: &Edge, g: &Edge) -> Edge {
+    // De Morgan: `f | g == !(!f & !g)`
+    complement_result(cache, and(cache, &f.not(), &g.not()))
+}
 
-            let new_then = and(cache, fv, gv);
-            let new_else = and(cache, fnv, gnv);
-            cache.get_or_insert(level, &new_then, &new_else)
-        }
+fn xor(cache: &Rc<Cache>, f: &Edge, g: &Edge) -> Edge {
+    let result = cache.par_apply(
+        (f.clone(), g.clone()),
+        |(f, g)| (CacheKey::from(f), CacheKey::from(g)),
+        |(f, g)| {
+            if f.ptr_eq(&g) {
+                return Step::Done(cache.false_node());
+            }
+            if f.ptr_eq(&g.not()) {
+                return Step::Done(cache.true_node());
+            }
+            if f.is_terminal() {
+                return Step::Done(g.complement_if(f.is_true()));
+            }
+            if g.is_terminal() {
+                return Step::Done(f.complement_if(g.is_true()));
+            }
+
+            // `!f ^ g == !(f ^ g)`, so only regular edges need to be split.
+            let complement = f.complement ^ g.complement;
+            let (f, g) = (Edge::regular(f.node), Edge::regular(g.node));
+
+            let (var, fv, fnv, gv, gnv) = split_on_next_var(cache, &f, &g);
+            Step::Split((var, complement), (fv, gv), (fnv, gnv))
+        },
+        |(var, complement), new_then, new_else| {
+            cache
+                .get_or_insert(var, &new_then, &new_else)
+                .complement_if(complement)
+        },
+    );
+    // The placeholders may have been complemented on the way up.
+    if cache.has_error() {
+        cache.false_node()
+    } else {
+        result
     }
 }
 
-fn or(cache: &Rc<Cache>, f: &Rc<node::Inner>, g: &Rc<node::Inner>) -> Rc<node::Inner> {
-    if CacheKey::from(f) == CacheKey::from(g) {
-        return Rc::clone(f);
+fn implies(cache: &Rc<Cache>, f: &Edge, g: &Edge) -> Edge {
+    // `f -> g == !(f & !g)`
+    complement_result(cache, and(cache, f, &g.not()))
+}
+
+/// Complements the result of an operation, unless the operation was aborted, whose placeholder
+/// stays `false` like the placeholders of all other operations.
+fn complement_result(cache: &Cache, result: Edge) -> Edge {
+    if cache.has_error() {
+        cache.false_node()
+    } else {
+        result.not()
     }
+}
+
+pub(crate) fn if_then_else(cache: &Rc<Cache>, f: &Edge, g: &Edge, h: &Edge) -> Edge {
+    cache.par_apply(
+        (f.clone(), g.clone(), h.clone()),
+        |(f, g, h)| (CacheKey::from(f), CacheKey::from(g), CacheKey::from(h)),
+        |(f, g, h)| {
+            if f.is_true() || g.ptr_eq(&h) {
+                return Step::Done(g);
+            }
+            if f.is_false() {
+                return Step::Done(h);
+            }
+            if g.is_true() {
+                return Step::Done(or(cache, &f, &h));
+            }
+            if g.is_false() {
+                return Step::Done(and(cache, &f.not(), &h));
+            }
+            if h.is_true() {
+                return Step::Done(or(cache, &f.not(), &g));
+            }
+            if h.is_false() {
+                return Step::Done(and(cache, &f, &g));
+            }
+
+            let (var, fv, fnv, gv, gnv, hv, hnv) = split_on_next_var3(cache, &f, &g, &h);
+            Step::Split(var, (fv, gv, hv), (fnv, gnv, hnv))
+        },
+        |var, new_then, new_else| cache.get_or_insert(var, &new_then, &new_else),
+    )
+}
 
-    match (&f.kind, &g.kind) {
-        (node::Kind::True, _) | (_, node::Kind::True) => cache.true_node(),
-        (_, node::Kind::False) => Rc::clone(f),
-        (node::Kind::False, _) => Rc::clone(g),
-        (node::Kind::NonTerminal { .. }, node::Kind::NonTerminal { .. }) => {
-            let (level, fv, fnv, gv, gnv) = split_on_next_var(f, g);
+/// One step of an operation that is evaluated by [`apply`].
+pub(crate) enum Step<A, C, R = Edge> {
+    /// The result is known without looking at the cofactors of the arguments.
+    Done(R),
+    /// The result is combined from the results for the then and else arguments.
+    Split(C, A, A),
+}
 
-            let new_then = or(cache, fv, gv);
-            let new_else = or(cache, fnv, gnv);
-            cache.get_or_insert(level, &new_then, &new_else)
-        }
-    }
+/// The results of [`apply`], memoized by the keys of their arguments.
+pub(crate) trait Memo<A, K, R> {
+    fn get(&mut self, key: &K) -> Option<R>;
+
+    /// Records the result for the arguments. The keys contain pointers, so the arguments must be
+    /// kept alive as long as the result, to prevent new nodes from reusing their addresses.
+    fn insert(&mut self, key: K, args: A, result: R);
 }
 
-fn xor(cache: &Rc<Cache>, f: &Rc<node::Inner>, g: &Rc<node::Inner>) -> Rc<node::Inner> {
-    if CacheKey::from(f) == CacheKey::from(g) {
-        return cache.false_node();
+impl<A, K: Ord, R: Clone> Memo<A, K, R> for BTreeMap<K, (A, R)> {
+    fn get(&mut self, key: &K) -> Option<R> {
+        BTreeMap::get(self, key).map(|(_, result)| result.clone())
     }
 
-    match (&f.kind, &g.kind) {
-        (node::Kind::True, _) => not(cache, g),
-        (node::Kind::False, _) => Rc::clone(g),
-        (_, node::Kind::True) => not(cache, f),
-        (_, node::Kind::False) => Rc::clone(f),
-        (node::Kind::NonTerminal { .. }, node::Kind::NonTerminal { .. }) => {
-            let (level, fv, fnv, gv, gnv) = split_on_next_var(f, g);
-
-            let new_then = xor(cache, fv, gv);
-            let new_else = xor(cache, fnv, gnv);
-            cache.get_or_insert(level, &new_then, &new_else)
-        }
+    fn insert(&mut self, key: K, args: A, result: R) {
+        BTreeMap::insert(self, key, (args, result));
     }
 }
 
-fn not(cache: &Rc<Cache>, f: &Rc<node::Inner>) -> Rc<node::Inner> {
-    match &f.kind {
-        node::Kind::True => cache.false_node(),
-        node::Kind::False => cache.true_node(),
-        node::Kind::NonTerminal {
-            level,
-            then_child,
-            else_child,
-            cache: _,
-        } => {
-            let new_then = not(cache, then_child);
-            let new_else = not(cache, else_child);
-            cache.get_or_insert(*level, &new_then, &new_else)
-        }
-    }
+/// Evaluates a recursive operation on decision diagrams with an explicit stack, so that deep
+/// diagrams cannot overflow the call stack.
+///
+/// `step` either returns the result for the arguments or splits them into the arguments for the
+/// then and else results, which are then passed to `combine`. Results are memoized by the `key` of
+/// their arguments for the duration of the call, so shared nodes are only visited once.
+pub(crate) fn apply<A: Clone, C, K: Ord, R: Clone>(
+    args: A,
+    key: impl Fn(&A) -> K,
+    step: impl FnMut(A) -> Step<A, C, R>,
+    combine: impl FnMut(C, R, R) -> R,
+) -> R {
+    apply_with_memo(args, key, step, combine, &mut BTreeMap::new())
 }
 
-fn implies(cache: &Rc<Cache>, f: &Rc<node::Inner>, g: &Rc<node::Inner>) -> Rc<node::Inner> {
-    if CacheKey::from(f) == CacheKey::from(g) {
-        return cache.true_node();
+/// Like [`apply`], but with results memoized in `memo`, which may outlive the call.
+pub(crate) fn apply_with_memo<A: Clone, C, K, R: Clone>(
+    args: A,
+    key: impl Fn(&A) -> K,
+    mut step: impl FnMut(A) -> Step<A, C, R>,
+    mut combine: impl FnMut(C, R, R) -> R,
+    memo: &mut impl Memo<A, K, R>,
+) -> R {
+    enum Task<A, C, K> {
+        Visit(A),
+        Combine(K, A, C),
     }
 
-    match (&f.kind, &g.kind) {
-        (node::Kind::False, _) | (_, node::Kind::True) => cache.true_node(),
-        (_, node::Kind::False) => not(cache, f),
-        (node::Kind::True, _) => Rc::clone(g),
-        (node::Kind::NonTerminal { .. }, node::Kind::NonTerminal { .. }) => {
-            let (level, fv, fnv, gv, gnv) = split_on_next_var(f, g);
-
-            let new_then = implies(cache, fv, gv);
-            let new_else = implies(cache, fnv, gnv);
-            cache.get_or_insert(level, &new_then, &new_else)
+    let mut results = Vec::new();
+    let mut tasks = vec![Task::Visit(args)];
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(args) => {
+                let key = key(&args);
+                if let Some(result) = memo.get(&key) {
+                    results.push(result);
+                    continue;
+                }
+                match step(args.clone()) {
+                    Step::Done(result) => {
+                        memo.insert(key, args, result.clone());
+                        results.push(result);
+                    }
+                    Step::Split(context, then_args, else_args) => {
+                        // The then result is computed first, so it ends up below the else result.
+                        tasks.push(Task::Combine(key, args, context));
+                        tasks.push(Task::Visit(else_args));
+                        tasks.push(Task::Visit(then_args));
+                    }
+                }
+            }
+            Task::Combine(key, args, context) => {
+                let new_else = results.pop().expect("else result has been computed");
+                let new_then = results.pop().expect("then result has been computed");
+                let result = combine(context, new_then, new_else);
+                memo.insert(key, args, result.clone());
+                results.push(result);
+            }
         }
     }
+    results.pop().expect("result has been computed")
 }
 
-pub(crate) fn if_then_else(
-    cache: &Rc<Cache>,
-    f: &Rc<node::Inner>,
-    g: &Rc<node::Inner>,
-    h: &Rc<node::Inner>,
-) -> Rc<node::Inner> {
-    match (&f.kind, &g.kind, &h.kind) {
-        (node::Kind::True, _, _) => Rc::clone(g),
-        (node::Kind::False, _, _) => Rc::clone(h),
-        (_, node::Kind::True, _) => or(cache, f, h),
-        (_, node::Kind::False, _) => and(cache, &not(cache, f), h),
-        (_, _, node::Kind::True) => or(cache, &not(cache, f), g),
-        (_, _, node::Kind::False) => and(cache, f, g),
-        (
-            node::Kind::NonTerminal { .. },
-            node::Kind::NonTerminal { .. },
-            node::Kind::NonTerminal { .. },
-        ) => {
-            let (level, fv, fnv, gv, gnv, hv, hnv) = split_on_next_var3(f, g, h);
-
-            let new_then = if_then_else(cache, fv, gv, hv);
-            let new_else = if_then_else(cache, fnv, gnv, hnv);
-            cache.get_or_insert(level, &new_then, &new_else)
-        }
+impl Cache {
+    /// Evaluates an operation on nodes of this manager with [`apply`], stopping early with
+    /// placeholder results once an error occurs, see [`crate::Error`].
+    pub(crate) fn apply<A: Clone, C, K: Ord>(
+        &self,
+        args: A,
+        key: impl Fn(&A) -> K,
+        step: impl FnMut(A) -> Step<A, C>,
+        combine: impl FnMut(C, Edge, Edge) -> Edge,
+    ) -> Edge {
+        self.apply_with_memo(args, key, step, combine, &mut BTreeMap::new())
     }
-}
 
-fn split_on_next_var<'f, 'g>(
-    f: &'f Rc<node::Inner>,
-    g: &'g Rc<node::Inner>,
-) -> (
-    u64,
-    &'f Rc<node::Inner>,
-    &'f Rc<node::Inner>,
-    &'g Rc<node::Inner>,
-    &'g Rc<node::Inner>,
-) {
-    let node::Kind::NonTerminal {
-        level: f_level,
-        then_child: f_then,
-        else_child: f_else,
-        cache: _,
-    } = &f.kind
-    else {
-        panic!("Expected NonTerminal node for lhs");
-    };
-    let node::Kind::NonTerminal {
-        level: g_level,
-        then_child: g_then,
-        else_child: g_else,
-        cache: _,
-    } = &g.kind
-    else {
-        panic!("Expected NonTerminal node for rhs");
-    };
+    /// Like [`Cache::apply`], but with results memoized in `memo`, see [`apply_with_memo`].
+    pub(crate) fn apply_with_memo<A: Clone, C, K>(
+        &self,
+        args: A,
+        key: impl Fn(&A) -> K,
+        mut step: impl FnMut(A) -> Step<A, C>,
+        combine: impl FnMut(C, Edge, Edge) -> Edge,
+        memo: &mut impl Memo<A, K, Edge>,
+    ) -> Edge {
+        let _nodes = self.read_nodes();
+        apply_with_memo(
+            args,
+            key,
+            |args| {
+                if self.has_error() {
+                    Step::Done(self.false_node())
+                } else {
+                    step(args)
+                }
+            },
+            combine,
+            memo,
+        )
+    }
+}
 
-    match f_level.cmp(g_level) {
+/// Splits the nodes on the variable with the lowest level, returning the variable and the
+/// cofactors of both nodes.
+pub(crate) fn split_on_next_var(
+    cache: &Cache,
+    f: &Edge,
+    g: &Edge,
+) -> (u64, Edge, Edge, Edge, Edge) {
+    let (f_var, f_then, f_else) = f.children().expect("Expected NonTerminal node for lhs");
+    let (g_var, g_then, g_else) = g.children().expect("Expected NonTerminal node for rhs");
+
+    match cache.level(f_var).cmp(&cache.level(g_var)) {
         Ordering::Less => {
             // Only split the lhs node (which is evaluated first)
-            (*f_level, f_then, f_else, g, g)
+            (f_var, f_then, f_else, g.clone(), g.clone())
         }
         Ordering::Equal => {
             // Split both nodes (they are evaluated in parallel)
-            (*f_level, f_then, f_else, g_then, g_else)
+            (f_var, f_then, f_else, g_then, g_else)
         }
         Ordering::Greater => {
             // Only split the rhs node (which is evaluated first)
-            (*g_level, f, f, g_then, g_else)
+            (g_var, f.clone(), f.clone(), g_then, g_else)
         }
     }
 }
 
-#[expect(clippy::type_complexity)]
-fn split_on_next_var3<'f, 'g, 'h>(
-    f: &'f Rc<node::Inner>,
-    g: &'g Rc<node::Inner>,
-    h: &'h Rc<node::Inner>,
-) -> (
-    u64,
-    &'f Rc<node::Inner>,
-    &'f Rc<node::Inner>,
-    &'g Rc<node::Inner>,
-    &'g Rc<node::Inner>,
-    &'h Rc<node::Inner>,
-    &'h Rc<node::Inner>,
-) {
-    let node::Kind::NonTerminal {
-        level: f_level,
-        then_child: f_then,
-        else_child: f_else,
-        cache: _,
-    } = &f.kind
-    else {
-        panic!("Expected NonTerminal node for f");
-    };
-    let node::Kind::NonTerminal {
-        level: g_level,
-        then_child: g_then,
-        else_child: g_else,
-        cache: _,
-    } = &g.kind
-    else {
-        panic!("Expected NonTerminal node for g");
-    };
-    let node::Kind::NonTerminal {
-        level: h_level,
-        then_child: h_then,
-        else_child: h_else,
-        cache: _,
-    } = &h.kind
-    else {
-        panic!("Expected NonTerminal node for h");
-    };
-
-    let min_level = f_level.min(g_level).min(h_level);
-
-    let (fv, fnv) = if f_level == min_level {
-        (f_then, f_else)
-    } else {
-        (f, f)
-    };
-    let (gv, gnv) = if g_level == min_level {
-        (g_then, g_else)
-    } else {
-        (g, g)
-    };
-    let (hv, hnv) = if h_level == min_level {
-        (h_then, h_else)
-    } else {
-        (h, h)
+fn split_on_next_var3(
+    cache: &Cache,
+    f: &Edge,
+    g: &Edge,
+    h: &Edge,
+) -> (u64, Edge, Edge, Edge, Edge, Edge, Edge) {
+    let f_children = f.children().expect("Expected NonTerminal node for f");
+    let g_children = g.children().expect("Expected NonTerminal node for g");
+    let h_children = h.children().expect("Expected NonTerminal node for h");
+
+    let min_level = [&f_children, &g_children, &h_children]
+        .map(|(var, _, _)| cache.level(*var))
+        .into_iter()
+        .min()
+        .unwrap();
+    let min_var = cache.var(min_level);
+
+    let split = |edge: &Edge, (var, then_child, else_child)| {
+        if var == min_var {
+            (then_child, else_child)
+        } else {
+            (edge.clone(), edge.clone())
+        }
     };
+    let (fv, fnv) = split(f, f_children);
+    let (gv, gnv) = split(g, g_children);
+    let (hv, hnv) = split(h, h_children);
 
-    (*min_level, fv, fnv, gv, gnv, hv, hnv)
+    (min_var, fv, fnv, gv, gnv, hv, hnv)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Manager, Rc};
+    use core::iter;
+
+    use crate::{Manager, Node, Rc};
+
+    /// Returns the conjunction of the first `n` bits, built from the bottom without any recursion.
+    fn all_bits(dd: &Manager, n: u64) -> Node {
+        (0..n).rev().fold(dd.true_node(), |node, i| {
+            dd.get_or_insert(i, &node, &dd.false_node())
+        })
+    }
 
     #[test]
     fn test_not() {
@@ -384,6 +471,32 @@ mod tests {
         assert_eq!(root.eval([false; 4]), Some(false));
     }
 
+    #[test]
+    fn test_not_shares_nodes() {
+        let dd = Manager::new();
+        let a = dd.bit(0) & !dd.bit(1);
+
+        let not_a = !a.clone();
+
+        assert!(Rc::ptr_eq(&not_a.edge.node, &a.edge.node));
+        assert!((!not_a.clone()).edge.ptr_eq(&a.edge));
+        assert_eq!(not_a.size(), a.size());
+        assert_eq!(not_a, !dd.bit(0) | dd.bit(1));
+        assert!((a.clone() & not_a.clone()).is_false());
+        assert!((a ^ not_a).is_true());
+        assert!((!dd.true_node()).is_false());
+    }
+
+    #[test]
+    fn test_id() {
+        let dd = Manager::new();
+        let a = dd.bit(0) & !dd.bit(1);
+
+        assert_eq!(a.id(), (!(!dd.bit(0) | dd.bit(1))).id());
+        assert_ne!(a.id(), (!a.clone()).id());
+        assert_ne!(dd.true_node().id(), dd.false_node().id());
+    }
+
     #[test]
     fn test_and_or() {
         let dd = Manager::new();
@@ -414,7 +527,7 @@ mod tests {
 
         let result = a.clone() & a.clone();
 
-        assert!(Rc::ptr_eq(&result.inner, &a.inner));
+        assert!(result.edge.ptr_eq(&a.edge));
     }
 
     #[test]
@@ -424,7 +537,7 @@ mod tests {
 
         let result = a.clone() | a.clone();
 
-        assert!(Rc::ptr_eq(&result.inner, &a.inner));
+        assert!(result.edge.ptr_eq(&a.edge));
     }
 
     #[test]
@@ -442,4 +555,45 @@ mod tests {
         assert_eq!(result.eval([false, true, true]), Some(false));
         assert_eq!(result.eval([true, true, true]), Some(false));
     }
+
+    #[test]
+    fn test_aborted_placeholders() {
+        let dd = Manager::new();
+        let a = dd.bit(0) & dd.bit(1);
+        let b = dd.bit(2) & dd.bit(3);
+        dd.set_node_limit(Some(dd.node_count()));
+
+        assert!((a.clone() | b.clone()).is_false());
+        assert!(dd.take_error().is_some());
+        assert!(a.implies(&b).is_false());
+        assert!(dd.take_error().is_some());
+        assert!((a ^ b).is_false());
+        assert!(dd.take_error().is_some());
+    }
+
+    #[test]
+    fn test_deep_diagrams() {
+        const LEVELS: u64 = 5000;
+
+        let dd = Manager::new();
+        let all = all_bits(&dd, LEVELS);
+        let none = (0..LEVELS).rev().fold(dd.true_node(), |node, i| {
+            dd.get_or_insert(i, &dd.false_node(), &node)
+        });
+        let any = !none.clone();
+
+        assert_eq!(all.clone() & any.clone(), all);
+        assert_eq!(all.clone() | none.clone(), !any.clone() | all.clone());
+        assert_eq!(all.clone() ^ none.clone(), all.clone() | none.clone());
+        assert_eq!(all.implies(&none), !all.clone());
+        assert_eq!(all.if_then_else(&none, &all), dd.false_node());
+        assert_eq!(all.shift(1).shift(-1), all);
+        assert_eq!(all.rename(|var| var + 1), all.shift(1));
+        assert_eq!(all.split_shift(LEVELS, 1, 0), all.shift(1));
+        assert_eq!(all.eval(iter::repeat_n(true, LEVELS as usize)), Some(true));
+        assert_eq!(all.size(), LEVELS as usize);
+
+        drop((all, any, none));
+        assert_eq!(dd.node_count(), 0);
+    }
 }
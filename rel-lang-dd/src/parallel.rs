@@ -0,0 +1,225 @@
+//! Parallel evaluation of operations with the `sync` feature.
+//!
+//! Like in Sylvan, an operation is split on the top variable of its arguments, and the then and
+//! else cofactors are computed as separate tasks that idle threads steal from each other. Below a
+//! fixed depth, the tasks are evaluated sequentially with [`Cache::apply_with_memo`], since
+//! splitting small subproblems costs more than it gains.
+//!
+//! All tasks of an operation share a computed table, so that a subproblem reached by several tasks
+//! is only solved once, like with the sequential evaluation.
+
+#[cfg(feature = "sync")]
+use alloc::collections::btree_map::BTreeMap;
+#[cfg(feature = "sync")]
+use core::{array, hash::Hash};
+#[cfg(feature = "sync")]
+use std::hash::{BuildHasher, RandomState};
+
+#[cfg(feature = "sync")]
+use crate::{Lock, ops::Memo, read, write};
+use crate::{manager::Cache, node::Edge, ops::Step};
+
+impl Cache {
+    /// Like [`Cache::apply`], but evaluates the operation in parallel with the `sync` feature.
+    #[cfg(not(feature = "sync"))]
+    pub(crate) fn par_apply<A: Clone, C, K: Ord>(
+        &self,
+        args: A,
+        key: impl Fn(&A) -> K,
+        step: impl Fn(A) -> Step<A, C>,
+        combine: impl Fn(C, Edge, Edge) -> Edge,
+    ) -> Edge {
+        self.apply(args, key, step, combine)
+    }
+
+    /// Like [`Cache::apply`], but evaluates the operation in parallel with the `sync` feature.
+    #[cfg(feature = "sync")]
+    pub(crate) fn par_apply<A, C, K>(
+        &self,
+        args: A,
+        key: impl Fn(&A) -> K + Sync,
+        step: impl Fn(A) -> Step<A, C> + Sync,
+        combine: impl Fn(C, Edge, Edge) -> Edge + Sync,
+    ) -> Edge
+    where
+        A: Clone + Send + Sync,
+        K: Ord + Hash + Send + Sync,
+    {
+        if rayon::current_num_threads() == 1 {
+            return self.apply(args, key, step, combine);
+        }
+
+        // The tasks run on other threads while this one holds the nodes for them.
+        let _nodes = self.read_nodes();
+        let task = Task {
+            cache: self,
+            key,
+            step,
+            combine,
+            memo: SharedMemo::new(),
+        };
+        task.run(args, PARALLEL_DEPTH)
+    }
+}
+
+/// The number of times an operation is split into parallel tasks, which allows for up to
+/// `2^PARALLEL_DEPTH` tasks.
+#[cfg(feature = "sync")]
+const PARALLEL_DEPTH: u32 = 10;
+
+/// The number of separately locked parts of the shared computed table.
+#[cfg(feature = "sync")]
+const NUM_SHARDS: usize = 64;
+
+/// The computed table of a parallel operation, which is split into shards like the unique tables,
+/// so that tasks rarely wait for each other.
+#[cfg(feature = "sync")]
+struct SharedMemo<A, K> {
+    shards: [Lock<BTreeMap<K, (A, Edge)>>; NUM_SHARDS],
+    hasher: RandomState,
+}
+
+#[cfg(feature = "sync")]
+impl<A, K: Ord + Hash> SharedMemo<A, K> {
+    fn new() -> Self {
+        Self {
+            shards: array::from_fn(|_| Lock::new(BTreeMap::new())),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard(&self, key: &K) -> &Lock<BTreeMap<K, (A, Edge)>> {
+        &self.shards[self.hasher.hash_one(key) as usize % NUM_SHARDS]
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<A, K: Ord + Hash> Memo<A, K, Edge> for &SharedMemo<A, K> {
+    fn get(&mut self, key: &K) -> Option<Edge> {
+        read(self.shard(key))
+            .get(key)
+            .map(|(_, result)| result.clone())
+    }
+
+    fn insert(&mut self, key: K, args: A, result: Edge) {
+        write(self.shard(&key)).insert(key, (args, result));
+    }
+}
+
+#[cfg(feature = "sync")]
+struct Task<'a, A, K, KeyFn, StepFn, CombineFn> {
+    cache: &'a Cache,
+    key: KeyFn,
+    step: StepFn,
+    combine: CombineFn,
+    /// The results of all tasks, including the sequential ones.
+    memo: SharedMemo<A, K>,
+}
+
+#[cfg(feature = "sync")]
+impl<A, C, K, KeyFn, StepFn, CombineFn> Task<'_, A, K, KeyFn, StepFn, CombineFn>
+where
+    A: Clone + Send + Sync,
+    K: Ord + Hash + Send + Sync,
+    KeyFn: Fn(&A) -> K + Sync,
+    StepFn: Fn(A) -> Step<A, C> + Sync,
+    CombineFn: Fn(C, Edge, Edge) -> Edge + Sync,
+{
+    fn run(&self, args: A, depth: u32) -> Edge {
+        let mut memo = &self.memo;
+        if depth == 0 {
+            return self.cache.apply_with_memo(
+                args,
+                &self.key,
+                &self.step,
+                &self.combine,
+                &mut memo,
+            );
+        }
+
+        let key = (self.key)(&args);
+        if let Some(result) = memo.get(&key) {
+            return result;
+        }
+        let result = if self.cache.has_error() {
+            self.cache.false_node()
+        } else {
+            match (self.step)(args.clone()) {
+                Step::Done(result) => result,
+                Step::Split(context, then_args, else_args) => {
+                    let (new_then, new_else) = rayon::join(
+                        || self.run(then_args, depth - 1),
+                        || self.run(else_args, depth - 1),
+                    );
+                    (self.combine)(context, new_then, new_else)
+                }
+            }
+        };
+
+        memo.insert(key, args, result.clone());
+        result
+    }
+}
+
+#[cfg(all(test, feature = "sync"))]
+mod tests {
+    use alloc::vec::Vec;
+    use std::thread;
+
+    use crate::{Manager, Node};
+
+    /// The identity relation on `n`-bit numbers, with all bits of `x` before the bits of `y`, which
+    /// is large enough to be split into many tasks.
+    fn identity(dd: &Manager, n: u64) -> Node {
+        let mut node = dd.true_node();
+        for i in 0..n {
+            node &= !(dd.bit(i) ^ dd.bit(n + i));
+        }
+        node
+    }
+
+    #[test]
+    fn test_parallel_ops() {
+        let dd = Manager::new();
+        let f = identity(&dd, 6);
+        let g = (0..12).fold(dd.false_node(), |node, i| {
+            node ^ (dd.bit(i) & dd.bit((i + 5) % 12))
+        });
+
+        let and = f.clone() & g.clone();
+        let or = f.clone() | g.clone();
+        let ite = f.if_then_else(&g, &!g.clone());
+
+        for bits in 0..1u64 << 12 {
+            let vars = || (0..12).map(move |i| bits & (1 << i) != 0);
+            let f = f.eval(vars()).unwrap();
+            let g = g.eval(vars()).unwrap();
+            assert_eq!(and.eval(vars()), Some(f && g));
+            assert_eq!(or.eval(vars()), Some(f || g));
+            assert_eq!(ite.eval(vars()), Some(f == g));
+        }
+    }
+
+    #[test]
+    fn test_concurrent_unique_table() {
+        let dd = Manager::new();
+        let expected = identity(&dd, 8);
+        let node_count = dd.node_count();
+
+        let nodes = thread::scope(|scope| {
+            let threads = (0..8)
+                .map(|_| scope.spawn(|| identity(&dd, 8)))
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Threads that create the same node at the same time must end up sharing it.
+        assert_eq!(dd.node_count(), node_count);
+        for node in nodes {
+            assert_eq!(node, expected);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Existential quantification, and the relational product that fuses it with conjunction.
+//!
+//! [`Node::and_exists`] computes `exists vars. f & g` without building `f & g` first, which is how
+//! the image of a set under a relation is computed.
+
+use crate::{
+    Node, Rc,
+    manager::{Cache, CacheKey},
+    node::Edge,
+    ops::{Step, or, split_on_next_var},
+};
+
+impl Node {
+    /// Returns the function `exists vars. f`, where the quantified variables are the ones for
+    /// which `is_quantified` returns true.
+    pub fn exists(&self, is_quantified: impl Fn(u64) -> bool) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: and_exists(
+                &self.cache,
+                &self.edge,
+                &self.cache.true_node(),
+                is_quantified,
+            ),
+        }
+        .reorder_if_needed()
+    }
+
+    /// Returns the function `exists vars. f & g`, where the quantified variables are the ones for
+    /// which `is_quantified` returns true.
+    pub fn and_exists(&self, other: &Self, is_quantified: impl Fn(u64) -> bool) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: and_exists(&self.cache, &self.edge, &other.edge, is_quantified),
+        }
+        .reorder_if_needed()
+    }
+}
+
+fn and_exists(cache: &Rc<Cache>, f: &Edge, g: &Edge, is_quantified: impl Fn(u64) -> bool) -> Edge {
+    cache.apply(
+        (f.clone(), g.clone()),
+        |(f, g)| (CacheKey::from(f), CacheKey::from(g)),
+        |(f, g)| {
+            if f.is_false() || g.is_false() || f.ptr_eq(&g.not()) {
+                return Step::Done(cache.false_node());
+            }
+            if f.is_true() && g.is_true() {
+                return Step::Done(f);
+            }
+
+            let (var, fv, fnv, gv, gnv) = if f.ptr_eq(&g) {
+                let (var, fv, fnv) = f.children().expect("f is non-terminal");
+                (var, fv.clone(), fnv.clone(), fv, fnv)
+            } else if g.is_true() {
+                let (var, fv, fnv) = f.children().expect("f is non-terminal");
+                (var, fv, fnv, g.clone(), g)
+            } else if f.is_true() {
+                let (var, gv, gnv) = g.children().expect("g is non-terminal");
+                (var, f.clone(), f, gv, gnv)
+            } else {
+                split_on_next_var(cache, &f, &g)
+            };
+            Step::Split((var, is_quantified(var)), (fv, gv), (fnv, gnv))
+        },
+        |(var, quantified), new_then, new_else| {
+            if quantified {
+                or(cache, &new_then, &new_else)
+            } else {
+                cache.get_or_insert(var, &new_then, &new_else)
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Manager, Node};
+
+    fn example(dd: &Manager) -> Node {
+        (dd.bit(0) & dd.bit(1)) | (!dd.bit(1) & dd.bit(2) & dd.bit(3))
+    }
+
+    #[test]
+    fn test_exists() {
+        let dd = Manager::new();
+        let node = example(&dd);
+
+        assert_eq!(
+            node.exists(|var| var == 1),
+            node.cofactor(1, true) | node.cofactor(1, false)
+        );
+        assert_eq!(node.exists(|_| false), node);
+        assert!(node.exists(|_| true).is_true());
+        assert!(dd.false_node().exists(|_| true).is_false());
+    }
+
+    #[test]
+    fn test_and_exists() {
+        let dd = Manager::new();
+        let f = example(&dd);
+        let g = dd.bit(1) ^ dd.bit(3);
+
+        for is_quantified in [|var| var == 1, |var| var % 2 == 1, |_| true, |_| false] {
+            assert_eq!(
+                f.and_exists(&g, is_quantified),
+                (f.clone() & g.clone()).exists(is_quantified)
+            );
+        }
+        assert!(f.and_exists(&!f.clone(), |_| true).is_false());
+        assert_eq!(f.and_exists(&f, |var| var == 0), f.exists(|var| var == 0));
+    }
+}
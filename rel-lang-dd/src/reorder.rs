@@ -0,0 +1,336 @@
+//! Dynamic variable reordering with Rudell's sifting algorithm.
+//!
+//! Every variable is tested at a level given by the permutation in [`Order`]. Reordering swaps
+//! adjacent levels by rewriting the nodes of the upper variable in place, so existing [`Node`]s
+//! keep representing the same functions and only the shape of the diagram changes.
+//!
+//! Nodes are immutable to everything but reordering. Operations hold the nodes of the manager
+//! shared while they read them, and reordering rewrites them while it holds them exclusively, see
+//! [`Cache::read_nodes`].
+
+use alloc::vec::Vec;
+use core::{cmp::Reverse, ops::DerefMut};
+
+use crate::{
+    Manager, Node, Rc,
+    manager::{Cache, Order},
+    node::{Diagram, Edge, Kind},
+};
+
+impl Manager {
+    /// Reorders the variables with Rudell's sifting algorithm to reduce the number of nodes.
+    ///
+    /// With the `sync` feature, this waits for the operations on other threads to finish. It must
+    /// not be called from within an operation of the same manager, for example from the predicate
+    /// of [`Node::exists`].
+    pub fn reorder(&self) {
+        let _nodes = self.cache.write_nodes();
+        self.cache.sift();
+    }
+
+    /// Reorders the variables after an operation whenever the number of nodes exceeds the
+    /// threshold, which then grows to twice the number of nodes after reordering. `None` disables
+    /// automatic reordering.
+    ///
+    /// With the `sync` feature, reordering is skipped while other threads are in an operation of
+    /// the same manager, and tried again after the next operation.
+    pub fn set_reorder_threshold(&self, threshold: Option<usize>) {
+        self.cache.order_mut().reorder_threshold = threshold;
+    }
+
+    /// Returns the number of non-terminal nodes that are alive.
+    pub fn node_count(&self) -> usize {
+        self.cache.node_count()
+    }
+
+    /// Returns the level at which the variable is tested, with the root at level 0.
+    pub fn level(&self, var: u64) -> u64 {
+        self.cache.level(var)
+    }
+
+    /// Returns the variable that is tested at the level.
+    pub fn var_at_level(&self, level: u64) -> u64 {
+        self.cache.var(level)
+    }
+}
+
+impl Node {
+    /// Reorders the variables if the manager's reorder threshold has been exceeded.
+    pub(crate) fn reorder_if_needed(self) -> Self {
+        self.cache.reorder_if_needed();
+        self
+    }
+}
+
+impl Cache {
+    pub(crate) fn node_count(&self) -> usize {
+        self.unique_table().len()
+    }
+
+    pub(crate) fn reorder_if_needed(self: &Rc<Self>) {
+        let Some(threshold) = self.order().reorder_threshold else {
+            return;
+        };
+        if self.node_count() <= threshold || self.has_error() {
+            return;
+        }
+        let Some(_nodes) = self.try_write_nodes() else {
+            return;
+        };
+
+        self.sift();
+        let node_count = self.node_count();
+        self.order_mut().reorder_threshold = Some(threshold.max(2 * node_count));
+    }
+
+    /// Holds the nodes exclusively, waiting for the operations on other threads to finish.
+    #[cfg(feature = "sync")]
+    fn write_nodes(&self) -> impl DerefMut<Target = ()> + '_ {
+        loop {
+            if let Some(nodes) = self.try_write_nodes() {
+                return nodes;
+            }
+            // Operations may hold the nodes more than once, so blocking on the lock could keep
+            // them from finishing.
+            std::thread::yield_now();
+        }
+    }
+
+    /// Holds the nodes exclusively, which no operation can be reading without the `sync` feature.
+    #[cfg(not(feature = "sync"))]
+    fn write_nodes(&self) -> impl DerefMut<Target = ()> + '_ {
+        self.try_write_nodes()
+            .expect("reordering from within an operation")
+    }
+
+    fn sift(self: &Rc<Self>) {
+        // Sifting relies on the number of nodes, so the dead nodes that the deferred policy keeps
+        // are freed first, and nodes are freed eagerly while sifting.
+        let deferred = self.release_all();
+        self.sift_all();
+        if deferred {
+            self.retain_all();
+        }
+    }
+
+    fn sift_all(self: &Rc<Self>) {
+        let node_counts = self.unique_table().count_by_var();
+
+        let Some(num_levels) = node_counts.keys().map(|&var| self.level(var) + 1).max() else {
+            return;
+        };
+        self.order_mut().extend_to(num_levels);
+
+        // Sift the variables with the most nodes first, as they have the largest effect.
+        let mut vars = node_counts.into_iter().collect::<Vec<_>>();
+        vars.sort_by_key(|&(_, count)| Reverse(count));
+        for (var, _) in vars {
+            self.sift_var(var, num_levels);
+        }
+    }
+
+    /// Moves the variable through all levels, and leaves it at the level with the fewest nodes.
+    fn sift_var(self: &Rc<Self>, var: u64, num_levels: u64) {
+        let mut level = self.level(var);
+        let mut best = (self.node_count(), level);
+
+        // Visit the closer end first, and give up on a direction once the diagram grows too much.
+        let ends = if level < num_levels - 1 - level {
+            [0, num_levels - 1]
+        } else {
+            [num_levels - 1, 0]
+        };
+        for end in ends {
+            while level != end {
+                level = self.move_level(level, end);
+                let node_count = self.node_count();
+                if node_count < best.0 {
+                    best = (node_count, level);
+                } else if node_count > best.0 + best.0 / 5 {
+                    break;
+                }
+            }
+        }
+
+        while level != best.1 {
+            level = self.move_level(level, best.1);
+        }
+    }
+
+    /// Swaps the variable at `level` with its neighbour towards `target`, returning its new level.
+    fn move_level(self: &Rc<Self>, level: u64, target: u64) -> u64 {
+        if level < target {
+            self.swap_levels(level);
+            level + 1
+        } else {
+            self.swap_levels(level - 1);
+            level - 1
+        }
+    }
+
+    /// Swaps the variables at `level` and `level + 1`.
+    ///
+    /// The nodes of the upper variable `x` that have children testing the lower variable `y` are
+    /// rewritten in place to test `y`, with new children testing `x`. All other nodes keep their
+    /// shape.
+    fn swap_levels(self: &Rc<Self>, level: u64) {
+        let x = self.var(level);
+        let y = self.var(level + 1);
+
+        let x_nodes = self.unique_table().nodes_of_var(x);
+
+        for node in &x_nodes {
+            let (_, then_child, else_child) = node.children().expect("x nodes are non-terminal");
+            if then_child.var() != Some(y) && else_child.var() != Some(y) {
+                continue;
+            }
+
+            let (then_then, then_else) = cofactors(&then_child, y);
+            let (else_then, else_else) = cofactors(&else_child, y);
+            // The then edge of the node is regular, so `new_then` is regular as well and the node
+            // stays canonical.
+            let new_then = self.get_or_insert_unchecked(x, &then_then, &else_then);
+            let new_else = self.get_or_insert_unchecked(x, &then_else, &else_else);
+
+            self.remove(x, &then_child, &else_child, Rc::as_ptr(node));
+            self.insert(y, &new_then, &new_else, node);
+            // SAFETY: sifting holds the nodes exclusively.
+            let old_kind = unsafe {
+                node.replace_kind(Kind::NonTerminal {
+                    var: y,
+                    then_child: new_then,
+                    else_child: new_else,
+                    diagram: Diagram::Bdd,
+                    cache: Rc::downgrade(self),
+                })
+            };
+            // The old children may be the last references to nodes of `y`, whose `Drop` needs the
+            // unique table.
+            drop(old_kind);
+        }
+        drop(x_nodes);
+
+        let mut order = self.order_mut();
+        order.level_to_var.swap(level as usize, level as usize + 1);
+        order.var_to_level[x as usize] = level + 1;
+        order.var_to_level[y as usize] = level;
+    }
+}
+
+impl Order {
+    /// Makes the identity beyond the end of the permutation explicit up to `num_levels`.
+    fn extend_to(&mut self, num_levels: u64) {
+        for i in self.var_to_level.len() as u64..num_levels {
+            self.var_to_level.push(i);
+            self.level_to_var.push(i);
+        }
+    }
+}
+
+/// Returns the cofactors of the edge with respect to `var`, which is at or above its level.
+fn cofactors(edge: &Edge, var: u64) -> (Edge, Edge) {
+    match edge.children() {
+        Some((edge_var, then_child, else_child)) if edge_var == var => (then_child, else_child),
+        _ => (edge.clone(), edge.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{Manager, Node};
+
+    /// The identity relation on `n`-bit numbers, with all bits of `x` before the bits of `y`.
+    fn identity(dd: &Manager, n: u64) -> Node {
+        let mut node = dd.true_node();
+        for i in 0..n {
+            node &= !(dd.bit(i) ^ dd.bit(n + i));
+        }
+        node
+    }
+
+    fn truth_table(node: &Node, num_vars: u64) -> Vec<Option<bool>> {
+        (0..1u64 << num_vars)
+            .map(|bits| node.eval((0..num_vars).map(|i| bits & (1 << i) != 0)))
+            .collect()
+    }
+
+    #[test]
+    fn test_reorder_identity() {
+        let dd = Manager::new();
+        let node = identity(&dd, 5);
+        let expected = truth_table(&node, 10);
+        // The last bit of y and its complement share a node.
+        assert_eq!(dd.node_count(), 3 * (1 << 5) - 4);
+
+        dd.reorder();
+
+        // Interleaving the bits of x and y needs three nodes per bit, except for the last one.
+        assert_eq!(dd.node_count(), 3 * 5 - 1);
+        assert_eq!(truth_table(&node, 10), expected);
+        assert_eq!(node, identity(&dd, 5));
+        for i in 0..5 {
+            assert_eq!(dd.level(i).abs_diff(dd.level(5 + i)), 1);
+        }
+    }
+
+    #[test]
+    fn test_reorder_threshold() {
+        let dd = Manager::new();
+        dd.set_reorder_threshold(Some(20));
+
+        let node = identity(&dd, 6);
+
+        assert!(dd.node_count() <= 40);
+        assert!((0..12).any(|var| dd.var_at_level(var) != var));
+        assert_eq!(
+            truth_table(&node, 12),
+            truth_table(&identity(&Manager::new(), 6), 12)
+        );
+    }
+
+    #[test]
+    fn test_reorder_shift() {
+        let reordered = Manager::new();
+        let node = identity(&reordered, 3) | reordered.minterm(1, 6);
+        reordered.reorder();
+
+        let dd = Manager::new();
+        let expected = identity(&dd, 3) | dd.minterm(1, 6);
+
+        assert_eq!(
+            truth_table(&node.shift(2), 8),
+            truth_table(&expected.shift(2), 8)
+        );
+        assert_eq!(
+            truth_table(&node.split_shift(3, 3, -3), 6),
+            truth_table(&expected.split_shift(3, 3, -3), 6)
+        );
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_reorder_concurrently() {
+        let dd = Manager::new();
+        let expected = truth_table(&identity(&Manager::new(), 5), 10);
+
+        std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        for _ in 0..10 {
+                            assert_eq!(truth_table(&identity(&dd, 5), 10), expected);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for _ in 0..10 {
+                dd.reorder();
+            }
+            for thread in threads {
+                thread.join().unwrap();
+            }
+        });
+    }
+}
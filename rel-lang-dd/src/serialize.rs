@@ -0,0 +1,508 @@
+//! Portable serialization of decision diagrams that preserves node sharing.
+//!
+//! Both formats list the non-terminal nodes children-first, so that every node only refers to
+//! nodes that were already read. Node ids are `1` for the terminal and `i + 2` for the `i`-th
+//! non-terminal node, and a reference to a node may be complemented: the terminal is true and its
+//! complement is false.
+//!
+//! The binary format is the magic `RLDD`, a version byte, the number of non-terminal nodes, one
+//! `(variable, then, else)` triple per node and the root reference, all encoded as LEB128 varints.
+//! References are written as `2 * id + complement`. Version 1 had no complemented references and
+//! used `0` for false instead, and can still be read.
+//! Nodes are rebuilt with [`Manager::get_or_insert`], so a diagram can be read into a manager with
+//! a different variable order than the one it was written from.
+//!
+//! The text format is modelled after DDDMP, with negative references for complemented ones:
+//!
+//! ```text
+//! .ver RLDD-2
+//! .nnodes 2
+//! .rootid 3
+//! .nodes
+//! 2 1 1 -1
+//! 3 0 2 -1
+//! .end
+//! ```
+
+use alloc::{collections::btree_map::BTreeMap, string::String, vec, vec::Vec};
+use core::fmt::{self, Write};
+
+use crate::{
+    Error, Manager, Node, Rc,
+    manager::{Cache, CacheKey},
+    node::Edge,
+};
+
+const MAGIC: &[u8; 4] = b"RLDD";
+/// The format version written by this release. Readers accept all versions up to this one.
+pub const FORMAT_VERSION: u8 = 2;
+
+/// The id of the false terminal in version 1, which had no complemented references.
+const V1_FALSE_ID: u64 = 0;
+const TERMINAL_ID: u64 = 1;
+const FIRST_NODE_ID: u64 = 2;
+
+/// A node id and whether the reference to it is complemented.
+type Ref = (u64, bool);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidMagic,
+    UnsupportedVersion {
+        version: u8,
+    },
+    UnexpectedEnd,
+    TrailingData,
+    InvalidNumber,
+    InvalidLine {
+        line: usize,
+    },
+    /// A node refers to a node that has not been defined before it.
+    UndefinedNode {
+        id: u64,
+    },
+    /// Building the nodes was aborted, for example by the node limit.
+    Aborted {
+        error: Error,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "not a decision diagram file"),
+            Self::UnsupportedVersion { version } => {
+                write!(f, "unsupported format version {version}")
+            }
+            Self::UnexpectedEnd => write!(f, "unexpected end of data"),
+            Self::TrailingData => write!(f, "unexpected data after the root node"),
+            Self::InvalidNumber => write!(f, "invalid number"),
+            Self::InvalidLine { line } => write!(f, "invalid line {line}"),
+            Self::UndefinedNode { id } => write!(f, "reference to undefined node {id}"),
+            Self::Aborted { error } => write!(f, "decoding aborted: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+impl Node {
+    /// Serializes the diagram to the compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (nodes, root) = flatten(&self.cache, &self.edge);
+
+        let mut bytes = Vec::from(&MAGIC[..]);
+        bytes.push(FORMAT_VERSION);
+        write_varint(&mut bytes, nodes.len() as u64);
+        for &(var, then_ref, else_ref) in &nodes {
+            write_varint(&mut bytes, var);
+            write_varint(&mut bytes, encode_ref(then_ref));
+            write_varint(&mut bytes, encode_ref(else_ref));
+        }
+        write_varint(&mut bytes, encode_ref(root));
+        bytes
+    }
+
+    /// Serializes the diagram to the DDDMP-like text format.
+    pub fn to_text(&self) -> String {
+        let (nodes, root) = flatten(&self.cache, &self.edge);
+
+        let mut text = String::new();
+        let _ = writeln!(text, ".ver RLDD-{FORMAT_VERSION}");
+        let _ = writeln!(text, ".nnodes {}", nodes.len());
+        let _ = writeln!(text, ".rootid {}", TextRef(root));
+        let _ = writeln!(text, ".nodes");
+        for (id, &(var, then_ref, else_ref)) in (FIRST_NODE_ID..).zip(&nodes) {
+            let _ = writeln!(
+                text,
+                "{id} {var} {} {}",
+                TextRef(then_ref),
+                TextRef(else_ref)
+            );
+        }
+        let _ = writeln!(text, ".end");
+        text
+    }
+}
+
+impl Manager {
+    /// Reads a diagram written by [`Node::to_bytes`].
+    pub fn from_bytes(&self, bytes: &[u8]) -> Result<Node, DecodeError> {
+        let rest = bytes
+            .strip_prefix(&MAGIC[..])
+            .ok_or(DecodeError::InvalidMagic)?;
+        let (&version, mut rest) = rest.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        check_version(version)?;
+
+        let count = read_varint(&mut rest)?;
+        let mut builder = Builder::new(self);
+        for _ in 0..count {
+            let var = read_varint(&mut rest)?;
+            let then_ref = decode_ref(version, read_varint(&mut rest)?);
+            let else_ref = decode_ref(version, read_varint(&mut rest)?);
+            builder.push(var, then_ref, else_ref)?;
+        }
+        let root = builder.get(decode_ref(version, read_varint(&mut rest)?))?;
+
+        if !rest.is_empty() {
+            return Err(DecodeError::TrailingData);
+        }
+        Ok(root)
+    }
+
+    /// Reads a diagram written by [`Node::to_text`].
+    pub fn from_text(&self, text: &str) -> Result<Node, DecodeError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let mut header = |key: &str| {
+            let (line, content) = lines.next().ok_or(DecodeError::UnexpectedEnd)?;
+            content
+                .strip_prefix(key)
+                .filter(|value| value.starts_with(' '))
+                .map(str::trim)
+                .ok_or(DecodeError::InvalidLine { line })
+        };
+
+        let version = header(".ver")?;
+        let version = version
+            .strip_prefix("RLDD-")
+            .ok_or(DecodeError::InvalidMagic)?;
+        let version = version.parse().map_err(|_| DecodeError::InvalidNumber)?;
+        check_version(version)?;
+        let count = parse_number(header(".nnodes")?)?;
+        let root = parse_text_ref(version, header(".rootid")?)?;
+
+        match lines.next() {
+            Some((_, ".nodes")) => {}
+            Some((line, _)) => return Err(DecodeError::InvalidLine { line }),
+            None => return Err(DecodeError::UnexpectedEnd),
+        }
+
+        let mut builder = Builder::new(self);
+        for id in (FIRST_NODE_ID..).take(count as usize) {
+            let (line, content) = lines.next().ok_or(DecodeError::UnexpectedEnd)?;
+            let fields = content.split_whitespace().collect::<Vec<_>>();
+            match fields[..] {
+                [node_id, var, then_ref, else_ref] if parse_number(node_id)? == id => builder
+                    .push(
+                        parse_number(var)?,
+                        parse_text_ref(version, then_ref)?,
+                        parse_text_ref(version, else_ref)?,
+                    )?,
+                _ => return Err(DecodeError::InvalidLine { line }),
+            }
+        }
+
+        match lines.next() {
+            Some((_, ".end")) => {}
+            Some((line, _)) => return Err(DecodeError::InvalidLine { line }),
+            None => return Err(DecodeError::UnexpectedEnd),
+        }
+        if lines.next().is_some() {
+            return Err(DecodeError::TrailingData);
+        }
+        builder.get(root)
+    }
+}
+
+/// Lists the non-terminal nodes reachable from `root` children-first, and returns them together
+/// with the reference to the root.
+fn flatten(cache: &Cache, root: &Edge) -> (Vec<(u64, Ref, Ref)>, Ref) {
+    let _nodes = cache.read_nodes();
+    let mut ids = BTreeMap::new();
+    let mut nodes = Vec::new();
+
+    let id = |ids: &BTreeMap<CacheKey, u64>, edge: &Edge| {
+        if edge.is_terminal() {
+            Some((TERMINAL_ID, edge.complement))
+        } else {
+            let id = ids.get(&CacheKey::from(&edge.node)).copied()?;
+            Some((id, edge.complement))
+        }
+    };
+
+    // Children are pushed onto the stack until both of them have an id. Only the regular nodes are
+    // listed, so the children are the stored edges rather than the cofactors.
+    let mut stack = vec![Edge::regular(Rc::clone(&root.node))];
+    while let Some(edge) = stack.last() {
+        if id(&ids, edge).is_some() {
+            stack.pop();
+            continue;
+        }
+        let (var, then_child, else_child) =
+            edge.node.children().expect("terminals always have an id");
+
+        match (id(&ids, &then_child), id(&ids, &else_child)) {
+            (Some(then_ref), Some(else_ref)) => {
+                ids.insert(
+                    CacheKey::from(&edge.node),
+                    FIRST_NODE_ID + nodes.len() as u64,
+                );
+                nodes.push((var, then_ref, else_ref));
+                stack.pop();
+            }
+            (then_ref, else_ref) => {
+                if then_ref.is_none() {
+                    stack.push(Edge::regular(then_child.node));
+                }
+                if else_ref.is_none() {
+                    stack.push(Edge::regular(else_child.node));
+                }
+            }
+        }
+    }
+
+    let root = id(&ids, root).expect("root has been visited");
+    (nodes, root)
+}
+
+/// Rebuilds the nodes of a serialized diagram in the order in which they were written.
+struct Builder<'a> {
+    manager: &'a Manager,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(manager: &'a Manager) -> Self {
+        Self {
+            manager,
+            nodes: Vec::new(),
+        }
+    }
+
+    fn get(&self, (id, complement): Ref) -> Result<Node, DecodeError> {
+        let node = match id {
+            TERMINAL_ID => self.manager.true_node(),
+            _ => id
+                .checked_sub(FIRST_NODE_ID)
+                .and_then(|index| usize::try_from(index).ok())
+                .and_then(|index| self.nodes.get(index))
+                .cloned()
+                .ok_or(DecodeError::UndefinedNode { id })?,
+        };
+        Ok(if complement { !node } else { node })
+    }
+
+    fn push(&mut self, var: u64, then_ref: Ref, else_ref: Ref) -> Result<(), DecodeError> {
+        let then_child = self.get(then_ref)?;
+        let else_child = self.get(else_ref)?;
+        let node = self.manager.get_or_insert(var, &then_child, &else_child);
+        // Stops at the first node that could not be created, instead of building on a placeholder.
+        self.manager
+            .cache
+            .check()
+            .map_err(|error| DecodeError::Aborted { error })?;
+        self.nodes.push(node);
+        Ok(())
+    }
+}
+
+fn check_version(version: u8) -> Result<(), DecodeError> {
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion { version });
+    }
+    Ok(())
+}
+
+fn parse_number(s: &str) -> Result<u64, DecodeError> {
+    s.parse().map_err(|_| DecodeError::InvalidNumber)
+}
+
+fn encode_ref((id, complement): Ref) -> u64 {
+    2 * id + u64::from(complement)
+}
+
+fn decode_ref(version: u8, reference: u64) -> Ref {
+    match version {
+        1 if reference == V1_FALSE_ID => (TERMINAL_ID, true),
+        1 => (reference, false),
+        _ => (reference >> 1, reference & 1 == 1),
+    }
+}
+
+/// Formats a reference for the text format, where complemented references are negative.
+struct TextRef(Ref);
+
+impl fmt::Display for TextRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            (id, true) => write!(f, "-{id}"),
+            (id, false) => write!(f, "{id}"),
+        }
+    }
+}
+
+fn parse_text_ref(version: u8, s: &str) -> Result<Ref, DecodeError> {
+    match version {
+        1 => Ok(decode_ref(version, parse_number(s)?)),
+        _ => match s.strip_prefix('-') {
+            Some(id) => Ok((parse_number(id)?, true)),
+            None => Ok((parse_number(s)?, false)),
+        },
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        *bytes = rest;
+        let bits = u64::from(byte & 0x7f);
+        if bits << shift >> shift != bits {
+            return Err(DecodeError::InvalidNumber);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidNumber)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(manager: &Manager) -> Node {
+        let a = manager.get_or_insert(2, &manager.true_node(), &manager.false_node());
+        let b = manager.get_or_insert(2, &manager.false_node(), &manager.true_node());
+        let c = manager.get_or_insert(1, &a, &b);
+        manager.get_or_insert(0, &c, &b)
+    }
+
+    #[test]
+    fn test_roundtrip_bytes() {
+        let manager = Manager::new();
+        for node in [manager.true_node(), manager.false_node(), example(&manager)] {
+            let bytes = node.to_bytes();
+            assert_eq!(manager.from_bytes(&bytes), Ok(node.clone()));
+
+            let other = Manager::new();
+            assert_eq!(other.from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_text() {
+        let manager = Manager::new();
+        let node = manager.get_or_insert(
+            0,
+            &manager.get_or_insert(1, &manager.true_node(), &manager.false_node()),
+            &manager.false_node(),
+        );
+        let text = node.to_text();
+        assert_eq!(
+            text,
+            ".ver RLDD-2\n.nnodes 2\n.rootid 3\n.nodes\n2 1 1 -1\n3 0 2 -1\n.end\n"
+        );
+        assert_eq!(manager.from_text(&text), Ok(node.clone()));
+        assert_eq!(
+            manager.from_text(&(!node.clone()).to_text()),
+            Ok(!node.clone())
+        );
+        assert_eq!(
+            manager
+                .from_text(".ver RLDD-1\n.nnodes 2\n.rootid 3\n.nodes\n2 1 1 0\n3 0 2 0\n.end\n"),
+            Ok(node)
+        );
+
+        let node = example(&manager);
+        assert_eq!(manager.from_text(&node.to_text()), Ok(node));
+    }
+
+    #[test]
+    fn test_roundtrip_reordered() {
+        let manager = Manager::new();
+        let node = (manager.bit(0) & manager.bit(3))
+            | (manager.bit(1) & manager.bit(4))
+            | (manager.bit(2) & manager.bit(5));
+        manager.reorder();
+        assert_ne!(manager.level(3), 3);
+
+        let other = Manager::new();
+        let loaded = other.from_bytes(&node.to_bytes()).unwrap();
+        for bits in 0..1 << 6 {
+            let variables = || (0..6).map(|i| bits & (1 << i) != 0);
+            assert_eq!(loaded.eval(variables()), node.eval(variables()));
+        }
+    }
+
+    #[test]
+    fn test_shares_nodes() {
+        let manager = Manager::new();
+        let (mut even, mut odd) = (manager.true_node(), manager.false_node());
+        for level in (0..64).rev() {
+            (even, odd) = (
+                manager.get_or_insert(level, &odd, &even),
+                manager.get_or_insert(level, &even, &odd),
+            );
+        }
+        let node = odd;
+        // The parity function has one node per level with complement edges, instead of 2^64.
+        assert!(node.to_text().contains("\n.nnodes 64\n"));
+        assert_eq!(manager.from_bytes(&node.to_bytes()), Ok(node));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let manager = Manager::new();
+        let bytes = example(&manager).to_bytes();
+
+        assert_eq!(manager.from_bytes(b"RLBDD"), Err(DecodeError::InvalidMagic));
+        assert_eq!(
+            manager.from_bytes(b"RLDD\x03"),
+            Err(DecodeError::UnsupportedVersion { version: 3 })
+        );
+        assert_eq!(
+            manager.from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            manager.from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(DecodeError::TrailingData)
+        );
+        assert_eq!(
+            manager.from_bytes(b"RLDD\x01\x01\x00\x02\x00\x02"),
+            Err(DecodeError::UndefinedNode { id: 2 })
+        );
+        assert_eq!(
+            manager.from_bytes(b"RLDD\x02\x01\x00\x04\x03\x04"),
+            Err(DecodeError::UndefinedNode { id: 2 })
+        );
+        assert_eq!(
+            manager.from_text(".ver RLDD-1\n.nnodes 1\n.rootid 2\n.nodes\n2 0 1\n.end\n"),
+            Err(DecodeError::InvalidLine { line: 5 })
+        );
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let manager = Manager::new();
+        let bytes = example(&manager).to_bytes();
+
+        let other = Manager::new();
+        other.set_node_limit(Some(2));
+        assert_eq!(
+            other.from_bytes(&bytes),
+            Err(DecodeError::Aborted {
+                error: Error::NodeLimit { limit: 2 }
+            })
+        );
+        assert_eq!(other.take_error(), None);
+
+        other.set_node_limit(None);
+        assert_eq!(other.from_bytes(&bytes).unwrap().to_bytes(), bytes);
+    }
+}
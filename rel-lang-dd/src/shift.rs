@@ -1,73 +1,170 @@
-use crate::{Node, Rc, factories::bit, manager::Cache, node, ops::if_then_else};
+use crate::{
+    Node, Rc,
+    manager::{Cache, CacheKey},
+    node::Edge,
+    ops::{Step, if_then_else},
+};
 
 impl Node {
     pub fn shift(&self, diff: i64) -> Self {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: shift(&self.cache, &self.inner, diff),
+            edge: shift(&self.cache, &self.edge, diff),
         }
+        .reorder_if_needed()
     }
 
     /// Equivalent to CUDD's `shift_bdd` function.
     pub fn split_shift(&self, border: u64, diff_1: i64, diff_2: i64) -> Self {
         Self {
             cache: Rc::clone(&self.cache),
-            inner: split_shift(&self.cache, &self.inner, border, diff_1, diff_2),
+            edge: split_shift(&self.cache, &self.edge, border, diff_1, diff_2),
         }
+        .reorder_if_needed()
     }
-}
 
-fn shift(cache: &Rc<Cache>, node: &Rc<node::Inner>, diff: i64) -> Rc<node::Inner> {
-    if diff == 0 {
-        return node.clone();
+    /// Replaces every variable `v` by `f(v)`. The renamed variables do not need to keep their
+    /// order, but `f` should be injective on the variables of the node.
+    pub fn rename(&self, f: impl Fn(u64) -> u64) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: rename(&self.cache, &self.edge, f),
+        }
+        .reorder_if_needed()
+    }
+
+    /// Replaces every variable `v` by `mapping[v]`, and keeps the variables beyond the end of the
+    /// mapping. The mapping should be injective on the variables of the node, for example a
+    /// permutation that swaps the components of a product domain.
+    pub fn permute(&self, mapping: &[u64]) -> Self {
+        self.rename(|var| mapping.get(var as usize).copied().unwrap_or(var))
     }
 
-    match &node.kind {
-        node::Kind::True => cache.true_node(),
-        node::Kind::False => cache.false_node(),
-        node::Kind::NonTerminal {
-            level,
-            then_child,
-            else_child,
-            cache: _,
-        } => {
-            let new_then = shift(cache, then_child, diff);
-            let new_else = shift(cache, else_child, diff);
-            cache.get_or_insert((*level as i64 + diff) as u64, &new_then, &new_else)
+    /// Substitutes the function `g` for the variable `var`, that is `f[var := g]`.
+    pub fn compose(&self, var: u64, g: &Self) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge: compose(&self.cache, &self.edge, var, &g.edge),
         }
+        .reorder_if_needed()
+    }
+}
+
+fn shift(cache: &Rc<Cache>, edge: &Edge, diff: i64) -> Edge {
+    if diff == 0 {
+        return edge.clone();
     }
+
+    cache.apply(
+        edge.clone(),
+        |edge| CacheKey::from(edge),
+        split,
+        |var, new_then, new_else| {
+            // Falls back to `if_then_else` if the shifted variables are not in the same order.
+            cache.var_node((var as i64 + diff) as u64, &new_then, &new_else)
+        },
+    )
+}
+
+fn rename(cache: &Rc<Cache>, edge: &Edge, f: impl Fn(u64) -> u64) -> Edge {
+    cache.apply(
+        edge.clone(),
+        |edge| CacheKey::from(edge),
+        split,
+        |var, new_then, new_else| cache.var_node(f(var), &new_then, &new_else),
+    )
 }
 
-fn split_shift(
-    cache: &Rc<Cache>,
-    node: &Rc<node::Inner>,
-    border: u64,
-    diff_1: i64,
-    diff_2: i64,
-) -> Rc<node::Inner> {
+fn split_shift(cache: &Rc<Cache>, edge: &Edge, border: u64, diff_1: i64, diff_2: i64) -> Edge {
     if (diff_1 == 0 || border == 0) && diff_2 == 0 {
-        return node.clone();
+        return edge.clone();
     }
 
-    match &node.kind {
-        node::Kind::True => cache.true_node(),
-        node::Kind::False => cache.false_node(),
-        node::Kind::NonTerminal {
-            level,
-            then_child,
-            else_child,
-            cache: _,
-        } => {
-            let new_then = split_shift(cache, then_child, border, diff_1, diff_2);
-            let new_else = split_shift(cache, else_child, border, diff_1, diff_2);
-
-            let condition = if *level < border {
-                bit(cache, (*level as i64 + diff_1) as u64)
-            } else {
-                bit(cache, (*level as i64 + diff_2) as u64)
-            };
-
-            if_then_else(cache, &condition, &new_then, &new_else)
+    // Only the nodes whose variables end up out of order are rebuilt with `if_then_else`.
+    rename(cache, edge, |var| {
+        if var < border {
+            (var as i64 + diff_1) as u64
+        } else {
+            (var as i64 + diff_2) as u64
         }
+    })
+}
+
+fn compose(cache: &Rc<Cache>, edge: &Edge, var: u64, g: &Edge) -> Edge {
+    let level = cache.level(var);
+    cache.apply(
+        edge.clone(),
+        |edge| CacheKey::from(edge),
+        |edge| match edge.children() {
+            Some((edge_var, then_child, else_child)) if edge_var == var => {
+                Step::Done(if_then_else(cache, g, &then_child, &else_child))
+            }
+            Some((edge_var, then_child, else_child)) if cache.level(edge_var) < level => {
+                Step::Split(edge_var, then_child, else_child)
+            }
+            // The variable is not tested below this node.
+            _ => Step::Done(edge),
+        },
+        |edge_var, new_then, new_else| cache.var_node(edge_var, &new_then, &new_else),
+    )
+}
+
+/// Splits a non-terminal edge into its variable and cofactors, and keeps terminals unchanged.
+fn split(edge: Edge) -> Step<Edge, u64> {
+    match edge.children() {
+        Some((var, then_child, else_child)) => Step::Split(var, then_child, else_child),
+        None => Step::Done(edge),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Manager;
+
+    #[test]
+    fn test_rename() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & !dd.bit(1)) | dd.bit(2);
+
+        assert_eq!(node.rename(|var| var + 1), node.shift(1));
+        assert_eq!(
+            node.rename(|var| 2 - var),
+            (dd.bit(2) & !dd.bit(1)) | dd.bit(0)
+        );
+    }
+
+    #[test]
+    fn test_permute() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & !dd.bit(1)) | (dd.bit(2) & dd.bit(3));
+
+        // Swaps the components of a product of two 2-bit domains.
+        assert_eq!(
+            node.permute(&[2, 3, 0, 1]),
+            (dd.bit(2) & !dd.bit(3)) | (dd.bit(0) & dd.bit(1))
+        );
+        assert_eq!(
+            node.permute(&[1, 0]),
+            (dd.bit(1) & !dd.bit(0)) | (dd.bit(2) & dd.bit(3))
+        );
+        assert_eq!(node.permute(&[]), node);
+    }
+
+    #[test]
+    fn test_compose() {
+        let dd = Manager::new();
+        let node = dd.bit(0) & dd.bit(1);
+
+        assert_eq!(
+            node.compose(1, &(dd.bit(2) | dd.bit(3))),
+            dd.bit(0) & (dd.bit(2) | dd.bit(3))
+        );
+        assert_eq!(node.compose(1, &!dd.bit(0)), dd.false_node());
+        assert_eq!(node.compose(0, &dd.true_node()), dd.bit(1));
+        assert_eq!(node.compose(2, &dd.bit(0)), node);
+
+        let node = dd.bit(1) ^ dd.bit(2);
+        assert_eq!(node.compose(2, &dd.bit(0)), dd.bit(1) ^ dd.bit(0));
+        assert_eq!(node.compose(1, &dd.bit(1)), node);
     }
 }
@@ -0,0 +1,142 @@
+//! The unique tables, which map the variable and children of every node to the node itself, so
+//! that each function is represented by a single node.
+//!
+//! With the `sync` feature, a table is split into shards that are locked separately, so that
+//! threads creating nodes in parallel rarely wait for each other.
+
+use alloc::{collections::btree_map::BTreeMap, vec::Vec};
+use core::{
+    array, ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Lock, Rc, Weak, manager::CacheKey, node, read, write};
+
+pub(crate) type UniqueKey = (u64, CacheKey, CacheKey);
+
+type Shard = BTreeMap<UniqueKey, Weak<node::Inner>>;
+
+#[cfg(not(feature = "sync"))]
+const NUM_SHARDS: usize = 1;
+#[cfg(feature = "sync")]
+const NUM_SHARDS: usize = 64;
+
+#[derive(Debug)]
+pub(crate) struct UniqueTable {
+    shards: [Lock<Shard>; NUM_SHARDS],
+    /// The number of entries in all shards, which is read whenever a node is created.
+    len: AtomicUsize,
+}
+
+impl UniqueTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            shards: array::from_fn(|_| Lock::new(BTreeMap::new())),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &UniqueKey) -> Option<Rc<node::Inner>> {
+        read(self.shard(key)).get(key).and_then(Weak::upgrade)
+    }
+
+    /// Returns the node for the key, or inserts the one returned by `new_node` if there is none.
+    /// The second value is whether the node was inserted.
+    ///
+    /// The lookup and the insertion are atomic, so threads racing to create the same node end up
+    /// with the same one.
+    pub(crate) fn get_or_insert_with(
+        &self,
+        key: UniqueKey,
+        new_node: impl FnOnce() -> Rc<node::Inner>,
+    ) -> (Rc<node::Inner>, bool) {
+        let mut shard = write(self.shard(&key));
+        if let Some(node) = shard.get(&key).and_then(Weak::upgrade) {
+            return (node, false);
+        }
+        let node = new_node();
+        if shard.insert(key, Rc::downgrade(&node)).is_none() {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+        (node, true)
+    }
+
+    /// Inserts a node whose children have been rewritten in place.
+    pub(crate) fn insert(&self, key: UniqueKey, node: &Rc<node::Inner>) {
+        if write(self.shard(&key))
+            .insert(key, Rc::downgrade(node))
+            .is_none()
+        {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Removes the entry of the node. The entry is left alone if it has been replaced by a new node
+    /// for the same key, which happens if another thread creates it while the old node is dropped.
+    pub(crate) fn remove(&self, key: &UniqueKey, node: *const node::Inner) {
+        let mut shard = write(self.shard(key));
+        if shard
+            .get(key)
+            .is_some_and(|entry| ptr::eq(entry.as_ptr(), node))
+        {
+            shard.remove(key);
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of nodes, including the ones that are being dropped.
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns the nodes that are alive.
+    pub(crate) fn nodes(&self) -> Vec<Rc<node::Inner>> {
+        let mut nodes = Vec::new();
+        for shard in &self.shards {
+            nodes.extend(read(shard).values().filter_map(Weak::upgrade));
+        }
+        nodes
+    }
+
+    /// Returns the nodes of the variable that are alive.
+    pub(crate) fn nodes_of_var(&self, var: u64) -> Vec<Rc<node::Inner>> {
+        let range = (var, CacheKey::MIN, CacheKey::MIN)..(var + 1, CacheKey::MIN, CacheKey::MIN);
+        let mut nodes = Vec::new();
+        for shard in &self.shards {
+            nodes.extend(
+                read(shard)
+                    .range(range.clone())
+                    .filter_map(|(_, node)| node.upgrade()),
+            );
+        }
+        nodes
+    }
+
+    /// Returns the number of nodes of each variable.
+    pub(crate) fn count_by_var(&self) -> BTreeMap<u64, usize> {
+        let mut counts = BTreeMap::new();
+        for shard in &self.shards {
+            for &(var, _, _) in read(shard).keys() {
+                *counts.entry(var).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn shard(&self, _key: &UniqueKey) -> &Lock<Shard> {
+        &self.shards[0]
+    }
+
+    #[cfg(feature = "sync")]
+    fn shard(&self, key: &UniqueKey) -> &Lock<Shard> {
+        let (var, then_child, else_child) = key;
+        // Nodes are aligned, so the low bits of their addresses carry little information.
+        let hash = (then_child.addr() >> 4)
+            .wrapping_mul(31)
+            .wrapping_add(else_child.addr() >> 4)
+            .wrapping_mul(31)
+            .wrapping_add(*var as usize);
+        &self.shards[hash % NUM_SHARDS]
+    }
+}
@@ -0,0 +1,451 @@
+//! Zero-suppressed decision diagrams, which represent families of sets of variables.
+//!
+//! A ZDD node for the variable `v` splits a family into the sets that contain `v` (with `v`
+//! removed) and the sets that do not. Nodes whose then child is the empty family are suppressed,
+//! so variables that are absent from all sets cost nothing, which makes sparse families much more
+//! compact than the equivalent BDDs.
+//!
+//! ZDDs share the [`Manager`] with BDDs but have their own unique table. They are ordered by
+//! their variables, and are not affected by reordering.
+
+use alloc::{collections::btree_map::BTreeMap, vec};
+
+use crate::{
+    Error, Manager, Node, Rc,
+    cofactor::cofactor,
+    manager::{Cache, CacheKey},
+    node::{self, Edge},
+    ops::Step,
+};
+
+/// A family of sets of variables.
+#[derive(Debug, Clone)]
+pub struct Zdd {
+    cache: Rc<Cache>,
+    // The empty family is the complemented terminal, and `{{}}` is the regular one. Edges to
+    // non-terminal nodes are never complemented.
+    edge: Edge,
+}
+
+impl Manager {
+    /// Returns the empty family.
+    pub fn zdd_empty(&self) -> Zdd {
+        Zdd {
+            cache: Rc::clone(&self.cache),
+            edge: self.cache.false_node(),
+        }
+    }
+
+    /// Returns the family that only contains the empty set.
+    pub fn zdd_base(&self) -> Zdd {
+        Zdd {
+            cache: Rc::clone(&self.cache),
+            edge: self.cache.true_node(),
+        }
+    }
+
+    /// Returns the family that only contains the set `{var}`.
+    pub fn zdd_singleton(&self, var: u64) -> Zdd {
+        Zdd {
+            cache: Rc::clone(&self.cache),
+            edge: self
+                .cache
+                .zdd_node(var, &self.cache.true_node(), &self.cache.false_node()),
+        }
+    }
+}
+
+impl Node {
+    /// Converts the function to the family of the sets of variables below `num_vars` whose
+    /// characteristic vectors satisfy it. The function must not depend on other variables.
+    ///
+    /// Fails instead of returning a placeholder if an operation of the manager has been aborted,
+    /// see [`Error`].
+    pub fn to_zdd(&self, num_vars: u64) -> Result<Zdd, Error> {
+        let edge = to_zdd(&self.cache, &self.edge, num_vars);
+        self.cache.check()?;
+        Ok(Zdd {
+            cache: Rc::clone(&self.cache),
+            edge,
+        })
+    }
+}
+
+impl PartialEq for Zdd {
+    fn eq(&self, other: &Self) -> bool {
+        // ZDDs are never reordered, so equal families are the same node.
+        self.edge.ptr_eq(&other.edge)
+    }
+}
+
+impl Eq for Zdd {}
+
+impl Zdd {
+    pub fn is_empty(&self) -> bool {
+        let _nodes = self.cache.read_nodes();
+        self.edge.is_false()
+    }
+
+    /// Whether the family only contains the empty set.
+    pub fn is_base(&self) -> bool {
+        let _nodes = self.cache.read_nodes();
+        self.edge.is_true()
+    }
+
+    /// Returns the number of non-terminal nodes in the diagram.
+    pub fn size(&self) -> usize {
+        let _nodes = self.cache.read_nodes();
+        node::size(&self.edge)
+    }
+
+    /// Returns the number of sets in the family, saturating at `u128::MAX`.
+    pub fn count(&self) -> u128 {
+        let _nodes = self.cache.read_nodes();
+        count(&self.edge)
+    }
+
+    /// Converts the family to the function over the variables below `num_vars` that holds for the
+    /// characteristic vectors of its sets. The sets must not contain other variables.
+    ///
+    /// Fails instead of returning a placeholder if an operation of the manager has been aborted,
+    /// see [`Error`].
+    pub fn to_node(&self, num_vars: u64) -> Result<Node, Error> {
+        let edge = to_node(&self.cache, &self.edge, num_vars);
+        self.cache.check()?;
+        Ok(Node {
+            cache: Rc::clone(&self.cache),
+            edge,
+        }
+        .reorder_if_needed())
+    }
+
+    pub fn union(&self, rhs: &Self) -> Self {
+        self.with_edge(union(&self.cache, &self.edge, &rhs.edge))
+    }
+
+    pub fn intersect(&self, rhs: &Self) -> Self {
+        self.with_edge(intersect(&self.cache, &self.edge, &rhs.edge))
+    }
+
+    /// Returns the sets of the family that are not in `rhs`.
+    pub fn diff(&self, rhs: &Self) -> Self {
+        self.with_edge(diff(&self.cache, &self.edge, &rhs.edge))
+    }
+
+    /// Toggles the membership of the variable in every set of the family.
+    pub fn change(&self, var: u64) -> Self {
+        self.with_edge(change(&self.cache, &self.edge, var))
+    }
+
+    /// Returns the sets that do not contain the variable.
+    pub fn subset0(&self, var: u64) -> Self {
+        self.with_edge(subset(&self.cache, &self.edge, var, false))
+    }
+
+    /// Returns the sets that contain the variable, with the variable removed.
+    pub fn subset1(&self, var: u64) -> Self {
+        self.with_edge(subset(&self.cache, &self.edge, var, true))
+    }
+
+    fn with_edge(&self, edge: Edge) -> Self {
+        Self {
+            cache: Rc::clone(&self.cache),
+            edge,
+        }
+    }
+}
+
+/// Returns the variable and the children of a ZDD edge. The family `{{}}` is treated as a node
+/// beyond the last variable, which contains no sets with the variable.
+fn children(cache: &Cache, edge: &Edge) -> (u64, Edge, Edge) {
+    edge.children()
+        .unwrap_or_else(|| (u64::MAX, cache.false_node(), edge.clone()))
+}
+
+/// Splits both families on the smaller of their variables. A family that does not test the
+/// variable has no sets that contain it.
+fn split_on_next_var(cache: &Cache, p: &Edge, q: &Edge) -> (u64, (Edge, Edge), (Edge, Edge)) {
+    let (p_var, p_then, p_else) = children(cache, p);
+    let (q_var, q_then, q_else) = children(cache, q);
+    if p_var < q_var {
+        (p_var, (p_then, cache.false_node()), (p_else, q.clone()))
+    } else if p_var > q_var {
+        (q_var, (cache.false_node(), q_then), (p.clone(), q_else))
+    } else {
+        (p_var, (p_then, q_then), (p_else, q_else))
+    }
+}
+
+fn union(cache: &Rc<Cache>, p: &Edge, q: &Edge) -> Edge {
+    cache.apply(
+        (p.clone(), q.clone()),
+        |(p, q)| (CacheKey::from(p), CacheKey::from(q)),
+        |(p, q)| {
+            if p.is_false() || p.ptr_eq(&q) {
+                return Step::Done(q);
+            }
+            if q.is_false() {
+                return Step::Done(p);
+            }
+
+            let (var, then_args, else_args) = split_on_next_var(cache, &p, &q);
+            Step::Split(var, then_args, else_args)
+        },
+        |var, new_then, new_else| cache.zdd_node(var, &new_then, &new_else),
+    )
+}
+
+fn intersect(cache: &Rc<Cache>, p: &Edge, q: &Edge) -> Edge {
+    cache.apply(
+        (p.clone(), q.clone()),
+        |(p, q)| (CacheKey::from(p), CacheKey::from(q)),
+        |(p, q)| {
+            if p.is_false() || p.ptr_eq(&q) {
+                return Step::Done(p);
+            }
+            if q.is_false() {
+                return Step::Done(q);
+            }
+
+            let (var, then_args, else_args) = split_on_next_var(cache, &p, &q);
+            Step::Split(var, then_args, else_args)
+        },
+        |var, new_then, new_else| cache.zdd_node(var, &new_then, &new_else),
+    )
+}
+
+fn diff(cache: &Rc<Cache>, p: &Edge, q: &Edge) -> Edge {
+    cache.apply(
+        (p.clone(), q.clone()),
+        |(p, q)| (CacheKey::from(p), CacheKey::from(q)),
+        |(p, q)| {
+            if p.is_false() || p.ptr_eq(&q) {
+                return Step::Done(cache.false_node());
+            }
+            if q.is_false() {
+                return Step::Done(p);
+            }
+
+            let (var, then_args, else_args) = split_on_next_var(cache, &p, &q);
+            Step::Split(var, then_args, else_args)
+        },
+        |var, new_then, new_else| cache.zdd_node(var, &new_then, &new_else),
+    )
+}
+
+fn change(cache: &Rc<Cache>, p: &Edge, var: u64) -> Edge {
+    cache.apply(
+        p.clone(),
+        |p| CacheKey::from(p),
+        |p| {
+            let (p_var, p_then, p_else) = children(cache, &p);
+            if p_var > var {
+                // None of the sets contain the variable yet.
+                Step::Done(cache.zdd_node(var, &p, &cache.false_node()))
+            } else if p_var == var {
+                Step::Done(cache.zdd_node(var, &p_else, &p_then))
+            } else {
+                Step::Split(p_var, p_then, p_else)
+            }
+        },
+        |p_var, new_then, new_else| cache.zdd_node(p_var, &new_then, &new_else),
+    )
+}
+
+fn subset(cache: &Rc<Cache>, p: &Edge, var: u64, value: bool) -> Edge {
+    cache.apply(
+        p.clone(),
+        |p| CacheKey::from(p),
+        |p| {
+            let (p_var, p_then, p_else) = children(cache, &p);
+            if p_var > var {
+                // None of the sets contain the variable.
+                Step::Done(if value { cache.false_node() } else { p })
+            } else if p_var == var {
+                Step::Done(if value { p_then } else { p_else })
+            } else {
+                Step::Split(p_var, p_then, p_else)
+            }
+        },
+        |p_var, new_then, new_else| cache.zdd_node(p_var, &new_then, &new_else),
+    )
+}
+
+fn count(root: &Edge) -> u128 {
+    let mut counts = BTreeMap::new();
+    let count = |counts: &BTreeMap<CacheKey, u128>, edge: &Edge| {
+        if edge.is_terminal() {
+            Some(u128::from(edge.is_true()))
+        } else {
+            counts.get(&CacheKey::from(edge)).copied()
+        }
+    };
+
+    // Children are pushed onto the stack until both of them have been counted.
+    let mut stack = vec![root.clone()];
+    while let Some(edge) = stack.last() {
+        if count(&counts, edge).is_some() {
+            stack.pop();
+            continue;
+        }
+        let (_, then_child, else_child) = edge.children().expect("terminals are always counted");
+
+        match (count(&counts, &then_child), count(&counts, &else_child)) {
+            (Some(then_count), Some(else_count)) => {
+                counts.insert(CacheKey::from(edge), then_count.saturating_add(else_count));
+                stack.pop();
+            }
+            (then_count, else_count) => {
+                if then_count.is_none() {
+                    stack.push(then_child);
+                }
+                if else_count.is_none() {
+                    stack.push(else_child);
+                }
+            }
+        }
+    }
+
+    count(&counts, root).expect("root has been counted")
+}
+
+fn to_zdd(cache: &Rc<Cache>, edge: &Edge, num_vars: u64) -> Edge {
+    cache.apply(
+        (edge.clone(), 0),
+        |(f, var)| (CacheKey::from(f), *var),
+        |(f, var)| {
+            if f.is_false() || var == num_vars {
+                return Step::Done(if f.is_true() {
+                    cache.true_node()
+                } else {
+                    cache.false_node()
+                });
+            }
+
+            // The cofactors are cheap unless the variables have been reordered.
+            let then_child = cofactor(cache, &f, var, true);
+            let else_child = cofactor(cache, &f, var, false);
+            Step::Split(var, (then_child, var + 1), (else_child, var + 1))
+        },
+        |var, new_then, new_else| cache.zdd_node(var, &new_then, &new_else),
+    )
+}
+
+fn to_node(cache: &Rc<Cache>, edge: &Edge, num_vars: u64) -> Edge {
+    cache.apply(
+        (edge.clone(), 0),
+        |(p, var)| (CacheKey::from(p), *var),
+        |(p, var)| {
+            if p.is_false() || var == num_vars {
+                return Step::Done(if p.is_true() {
+                    cache.true_node()
+                } else {
+                    cache.false_node()
+                });
+            }
+
+            let (p_var, p_then, p_else) = children(cache, &p);
+            if p_var == var {
+                Step::Split(var, (p_then, var + 1), (p_else, var + 1))
+            } else {
+                // None of the sets contain the variable.
+                Step::Split(var, (cache.false_node(), var + 1), (p, var + 1))
+            }
+        },
+        |var, new_then, new_else| cache.var_node(var, &new_then, &new_else),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Manager, Zdd};
+
+    /// The family `{{0, 1}, {2}}`.
+    fn example(dd: &Manager) -> Zdd {
+        dd.zdd_singleton(0).change(1).union(&dd.zdd_singleton(2))
+    }
+
+    #[test]
+    fn test_family_operations() {
+        let dd = Manager::new();
+        let family = example(&dd);
+        let pair = dd.zdd_singleton(1).change(0);
+
+        assert_eq!(family.count(), 2);
+        assert_eq!(family.union(&family), family);
+        assert_eq!(family.intersect(&dd.zdd_singleton(2)), dd.zdd_singleton(2));
+        assert_eq!(family.diff(&dd.zdd_singleton(2)), pair);
+        assert!(family.diff(&family).is_empty());
+        assert!(family.intersect(&dd.zdd_base()).is_empty());
+        assert_eq!(family.union(&dd.zdd_base()).count(), 3);
+
+        assert_eq!(family.subset1(2), dd.zdd_base());
+        assert_eq!(family.subset0(2), pair);
+        assert_eq!(family.subset1(0), dd.zdd_singleton(1));
+        assert!(family.subset1(3).is_empty());
+        assert_eq!(family.subset0(3), family);
+
+        // `{{1}, {0, 2}}`
+        let changed = family.change(0);
+        assert_eq!(
+            changed,
+            dd.zdd_singleton(1).union(&dd.zdd_singleton(0).change(2))
+        );
+        assert_eq!(changed.change(0), family);
+        assert!(dd.zdd_empty().change(0).is_empty());
+    }
+
+    #[test]
+    fn test_conversions() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & dd.bit(1) & !dd.bit(2)) | (!dd.bit(0) & !dd.bit(1) & dd.bit(2));
+
+        assert_eq!(node.to_zdd(3).unwrap(), example(&dd));
+        assert_eq!(example(&dd).to_node(3).unwrap(), node);
+        assert_eq!(dd.true_node().to_zdd(10).unwrap().count(), 1 << 10);
+        assert!(dd.false_node().to_zdd(10).unwrap().is_empty());
+        assert_eq!(dd.zdd_base().to_node(2).unwrap(), !dd.bit(0) & !dd.bit(1));
+
+        let node = (dd.bit(0) ^ dd.bit(3)) | (dd.bit(1) & dd.bit(2));
+        assert_eq!(node.to_zdd(4).unwrap().to_node(4).unwrap(), node);
+    }
+
+    #[test]
+    fn test_sparse() {
+        let dd = Manager::new();
+        let node = dd.minterm(5, 64) | dd.minterm(40, 64);
+
+        let family = node.to_zdd(64).unwrap();
+
+        assert_eq!(node.size(), 64 + 35);
+        assert_eq!(family.size(), 2);
+        assert_eq!(family, dd.zdd_singleton(5).union(&dd.zdd_singleton(40)));
+        assert_eq!(family.to_node(64).unwrap(), node);
+    }
+
+    #[test]
+    fn test_reordered() {
+        let dd = Manager::new();
+        let node = (dd.bit(0) & dd.bit(3)) | (dd.bit(1) & dd.bit(4)) | (dd.bit(2) & dd.bit(5));
+        let family = node.to_zdd(6).unwrap();
+        dd.reorder();
+
+        assert_eq!(node.to_zdd(6).unwrap(), family);
+        assert_eq!(family.to_node(6).unwrap(), node);
+        assert_eq!(family.count(), 64 - 27);
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let dd = Manager::new();
+        let node = dd.bit(0) ^ dd.bit(1) ^ dd.bit(2);
+        let family = node.to_zdd(3).unwrap();
+        let changed = family.change(3);
+        dd.set_node_limit(Some(dd.node_count() + dd.cache.zdd_table().len()));
+
+        assert!(matches!(node.to_zdd(4), Err(Error::NodeLimit { .. })));
+        assert!(matches!(changed.to_node(4), Err(Error::NodeLimit { .. })));
+        assert_eq!(dd.take_error(), None);
+        assert_eq!(family.to_node(3), Ok(node));
+    }
+}
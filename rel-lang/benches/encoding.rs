@@ -0,0 +1,48 @@
+//! Compares the number of decision diagram nodes of the identity `I` and the strict order `trans`
+//! (the transitive closure of the successor relation) for both encodings.
+//!
+//! Run with `cargo bench --bench encoding`.
+
+use std::time::Instant;
+
+use rel_lang::{Domain, Encoding, relation::Relation, set_default_encoding};
+
+type Bench = (&'static str, fn(u32) -> Relation, &'static [u32]);
+
+fn identity(n: u32) -> Relation {
+    Relation::identity(Domain::new(n))
+}
+
+fn trans(n: u32) -> Relation {
+    Relation::sparse(
+        (..n, ..n),
+        (0..n).flat_map(|x| (x + 1..n).map(move |y| (x, y))),
+    )
+}
+
+fn main() {
+    println!(
+        "{:>6} {:>10} {:>14} {:>14} {:>10}",
+        "n", "relation", "concatenated", "interleaved", "time"
+    );
+    // `trans` has quadratically many pairs, which are inserted one by one.
+    let benches: [Bench; 2] = [
+        ("I", identity, &[16, 64, 256, 1024, 4096]),
+        ("trans", trans, &[16, 64, 256]),
+    ];
+    for (name, relation, sizes) in benches {
+        for &n in sizes {
+            let start = Instant::now();
+            let counts = [Encoding::Concatenated, Encoding::Interleaved].map(|encoding| {
+                set_default_encoding(encoding);
+                relation(n).node_count()
+            });
+            println!(
+                "{n:>6} {name:>10} {:>14} {:>14} {:>10.2?}",
+                counts[0],
+                counts[1],
+                start.elapsed()
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Times procedures from `examples/Examples.prog` on random acyclic graphs of increasing size, to
+//! track the performance of the evaluator, `Relation` and the decision diagrams.
+//!
+//! Every benchmark is run once to warm up, and then repeatedly until it has enough samples or has
+//! taken long enough. Run with `cargo bench --bench programs`.
+
+use std::time::{Duration, Instant};
+
+use rel_lang::{
+    eval::{EvalOptions, Globals, Locals, eval},
+    parser::{parse_expr, parse_program},
+    relation::Relation,
+};
+
+const PROCEDURES: &[&str] = &["RTC1", "RTC2", "RTC3", "TopSort"];
+const SIZES: &[u32] = &[8, 16, 32];
+
+const MAX_SAMPLES: usize = 20;
+const MAX_TIME: Duration = Duration::from_secs(2);
+
+/// A random acyclic graph on `n` nodes with about two edges per node, which is the same for every
+/// run.
+fn graph(n: u32) -> Relation {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64 ^ u64::from(n);
+    let mut random = move || {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let edges = (0..2 * n)
+        .map(|_| {
+            let x = (random() % u64::from(n)) as u32;
+            let y = (random() % u64::from(n)) as u32;
+            (x.min(y), x.max(y))
+        })
+        .filter(|(x, y)| x != y)
+        .collect::<Vec<_>>();
+    Relation::sparse((..n, ..n), edges)
+}
+
+fn main() {
+    let src = include_str!("../examples/Examples.prog");
+    let mut globals = Globals::default();
+    globals.extend(parse_program("Examples.prog", src).unwrap().items);
+
+    println!(
+        "{:>10} {:>6} {:>8} {:>12} {:>12} {:>8}",
+        "procedure", "n", "samples", "median", "min", "nodes"
+    );
+    for procedure in PROCEDURES {
+        let expr = parse_expr(&format!("{procedure}(R)")).unwrap();
+        for &n in SIZES {
+            let mut locals = Locals::default();
+            locals.assign("R", graph(n));
+            let result = eval(&globals, &locals, &expr, &EvalOptions::default()).unwrap();
+
+            let mut samples = Vec::new();
+            let start = Instant::now();
+            while samples.len() < MAX_SAMPLES && start.elapsed() < MAX_TIME {
+                let sample = Instant::now();
+                eval(&globals, &locals, &expr, &EvalOptions::default()).unwrap();
+                samples.push(sample.elapsed());
+            }
+            samples.sort();
+
+            println!(
+                "{procedure:>10} {n:>6} {:>8} {:>12} {:>12} {:>8}",
+                samples.len(),
+                format!("{:.2?}", samples[samples.len() / 2]),
+                format!("{:.2?}", samples[0]),
+                result.node_count()
+            );
+        }
+    }
+}
@@ -1,25 +1,40 @@
-#[derive(Debug)]
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
 pub struct Program {
     pub items: Vec<Item>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Item {
     Procedure {
         name: String,
         params: Vec<String>,
         decls: Vec<String>,
         body: Vec<Stmt>,
+        /// The text of the item in its source, which is saved instead of the printed item.
+        source: String,
     },
     Function {
         name: String,
         params: Vec<String>,
         value: Expr,
+        /// The text of the item in its source.
+        source: String,
     },
 }
 
-#[derive(Debug)]
-pub enum Stmt {
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    /// The name of the file that the statement is in, as it was loaded.
+    pub file: Arc<str>,
+    /// The line of the statement in its source, starting at 1.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Assign {
         lhs: String,
         rhs: Expr,
@@ -38,7 +53,7 @@ pub enum Stmt {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Expr {
     Ident {
         ident: String,
@@ -60,7 +75,7 @@ pub enum Expr {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BinOp {
     Union,
     Intersect,
@@ -75,4 +90,12 @@ impl Item {
             Item::Function { name, .. } => name,
         }
     }
+
+    /// Returns the text of the item in its source, from its name to the final `.`.
+    pub fn source(&self) -> &str {
+        match self {
+            Item::Procedure { source, .. } => source,
+            Item::Function { source, .. } => source,
+        }
+    }
 }
@@ -0,0 +1,83 @@
+//! The standard base64 alphabet with padding, for binary data in XML files.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes the text, ignoring whitespace. Returns `None` if it is not valid base64.
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    if digits.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() / 4 * 3);
+    for (n, chunk) in digits.chunks(4).enumerate() {
+        let is_last = n + 1 == digits.len() / 4;
+        let padding = chunk
+            .iter()
+            .rev()
+            .take_while(|&&digit| digit == b'=')
+            .count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for (i, &digit) in chunk[..4 - padding].iter().enumerate() {
+            let value = ALPHABET.iter().position(|&c| c == digit)?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        bytes.extend(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::{decode, encode};
+
+    #[test]
+    fn test_roundtrip() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\x00\xfe", "/wD+"),
+        ] {
+            assert_eq!(encode(bytes), encoded);
+            assert_eq!(decode(encoded).as_deref(), Some(bytes));
+        }
+        assert_eq!(decode(" Zm9v\n Yg== ").as_deref(), Some(&b"foob"[..]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(decode("Zm9"), None);
+        assert_eq!(decode("Zm=v"), None);
+        assert_eq!(decode("Zg==Zg=="), None);
+        assert_eq!(decode("Z==="), None);
+        assert_eq!(decode("Zm9*"), None);
+    }
+}
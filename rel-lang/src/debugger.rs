@@ -0,0 +1,272 @@
+//! Tracing and step debugging of procedures in the REPL.
+//!
+//! A debugged evaluation runs on its own thread, which waits in the observer of each statement
+//! until the REPL resumes it. The relations are shared by all threads, but the builtins are not,
+//! so the thread evaluates with its own globals.
+
+use std::{
+    collections::BTreeSet,
+    fmt, io,
+    ops::ControlFlow,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    ast,
+    eval::{Error, Globals, Locals, Observer, Outcome, eval_observed},
+    relation::Relation,
+};
+
+/// Writes every statement that procedures execute, indented by the depth of the call.
+pub struct Tracer<'a> {
+    out: &'a mut dyn io::Write,
+    /// The first error while writing, after which the trace is cut short.
+    error: Option<io::Error>,
+}
+
+/// An evaluation that is being debugged, see the module documentation.
+pub struct Debugger {
+    /// Dropping the sender stops the evaluation at its next statement.
+    commands: Option<Sender<Command>>,
+    events: Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+    stop: Option<Stop>,
+}
+
+/// A line of a program file at which the debugger stops.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Breakpoint {
+    /// The name of the file, as it was loaded.
+    pub file: String,
+    pub line: usize,
+}
+
+/// The statement that an evaluation stopped at, before executing it.
+pub struct Stop {
+    /// The names of the functions and procedures that were being called, innermost last.
+    pub call_stack: Vec<String>,
+    pub line: usize,
+    /// The first line of the statement.
+    pub header: String,
+    pub locals: Locals,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Stop at the next statement.
+    Step,
+    /// Stop at the next statement on a line with a breakpoint.
+    Continue,
+}
+
+pub enum Progress {
+    Stopped,
+    Finished(Result<Relation, Error>),
+}
+
+/// Tells the thread of the evaluation how far to run.
+struct Command {
+    resume: Resume,
+    /// The breakpoints when the evaluation was resumed, since they may change at every stop.
+    breakpoints: BTreeSet<Breakpoint>,
+}
+
+/// Tells the REPL where the evaluation stopped, or how it ended.
+enum Event {
+    Stopped(Stop),
+    Finished(Result<Relation, Error>),
+}
+
+/// Suspends the evaluation at the statements that the commands stop at.
+struct Suspend {
+    commands: Receiver<Command>,
+    events: Sender<Event>,
+    command: Command,
+}
+
+/// Formats the domain and the number of pairs of a relation.
+struct Summary<'a>(&'a Relation);
+
+impl<'a> Tracer<'a> {
+    pub fn new(out: &'a mut dyn io::Write) -> Self {
+        Self { out, error: None }
+    }
+
+    /// Returns the first error while writing the trace.
+    pub fn finish(self) -> io::Result<()> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+impl Observer for Tracer<'_> {
+    fn after_stmt(&mut self, call_stack: &[String], stmt: &ast::Stmt, outcome: Outcome) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let indent = "  ".repeat(call_stack.len().saturating_sub(1));
+        let name = call_stack.last().map_or("", String::as_str);
+        let line = stmt.line;
+        let result = match outcome {
+            Outcome::Assigned { lhs, value } => {
+                writeln!(self.out, "{indent}{name}:{line} {lhs} = {}", Summary(value))
+            }
+            Outcome::Condition { holds } => {
+                writeln!(self.out, "{indent}{name}:{line} {} {holds}", stmt.header())
+            }
+            Outcome::Returned { value } => {
+                writeln!(self.out, "{indent}{name}:{line} RETURN {}", Summary(value))
+            }
+        };
+        self.error = result.err();
+    }
+}
+
+impl Debugger {
+    /// Starts to debug the expression, which waits for [`Debugger::resume`] before it evaluates
+    /// anything. The evaluation uses the functions, procedures and locals as they are now, and
+    /// the settings of this thread, except for the timeout, since it waits for the user.
+    pub fn new(globals: &Globals, locals: &Locals, expr: ast::Expr) -> io::Result<Self> {
+        let items: Vec<ast::Item> = globals.items().cloned().collect();
+        let locals = locals.clone();
+        let encoding = crate::default_encoding();
+        let max_iterations = crate::max_iterations();
+        let max_call_depth = crate::max_call_depth();
+        let (commands, command_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+
+        let thread = thread::Builder::new()
+            .name("debugger".to_owned())
+            // Like the main thread, see `crate::max_call_depth`.
+            .stack_size(8 << 20)
+            .spawn(move || {
+                crate::set_default_encoding(encoding);
+                crate::set_max_iterations(max_iterations);
+                crate::set_max_call_depth(max_call_depth);
+                let mut globals = Globals::default();
+                globals.extend(items);
+
+                let Ok(command) = command_receiver.recv() else {
+                    return;
+                };
+                let mut suspend = Suspend {
+                    commands: command_receiver,
+                    events: event_sender,
+                    command,
+                };
+                let result = eval_observed(&globals, &locals, &expr, &mut suspend);
+                // The REPL may have stopped debugging in the meantime.
+                let _ = suspend.events.send(Event::Finished(result));
+            })?;
+
+        Ok(Self {
+            commands: Some(commands),
+            events,
+            thread: Some(thread),
+            stop: None,
+        })
+    }
+
+    /// Returns the statement that the evaluation stopped at.
+    pub fn stop(&self) -> Option<&Stop> {
+        self.stop.as_ref()
+    }
+
+    /// Continues the evaluation until it reaches the next statement, or a statement on a line
+    /// with a breakpoint.
+    pub fn resume(&mut self, breakpoints: &BTreeSet<Breakpoint>, resume: Resume) -> Progress {
+        let command = Command {
+            resume,
+            breakpoints: breakpoints.clone(),
+        };
+        let sent = self
+            .commands
+            .as_ref()
+            .is_some_and(|commands| commands.send(command).is_ok());
+        // The thread only ends early if it panicked.
+        let event = if sent { self.events.recv().ok() } else { None };
+
+        match event {
+            Some(Event::Stopped(stop)) => {
+                self.stop = Some(stop);
+                Progress::Stopped
+            }
+            Some(Event::Finished(result)) => Progress::Finished(result),
+            None => Progress::Finished(Err(Error::Stopped)),
+        }
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        // Without commands the evaluation stops with `Error::Stopped`.
+        self.commands = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Observer for Suspend {
+    fn before_stmt(
+        &mut self,
+        call_stack: &[String],
+        locals: &Locals,
+        stmt: &ast::Stmt,
+    ) -> ControlFlow<()> {
+        let stops =
+            match self.command.resume {
+                Resume::Step => true,
+                Resume::Continue => self.command.breakpoints.iter().any(|breakpoint| {
+                    *breakpoint.file == *stmt.file && breakpoint.line == stmt.line
+                }),
+            };
+        if !stops {
+            return ControlFlow::Continue(());
+        }
+
+        let stop = Stop {
+            call_stack: call_stack.to_vec(),
+            line: stmt.line,
+            header: stmt.header().to_string(),
+            locals: locals.clone(),
+        };
+        if self.events.send(Event::Stopped(stop)).is_err() {
+            return ControlFlow::Break(());
+        }
+        match self.commands.recv() {
+            Ok(command) => {
+                self.command = command;
+                ControlFlow::Continue(())
+            }
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.call_stack.last().map_or("", String::as_str);
+        write!(f, "{name}:{} {}", self.line, self.header)
+    }
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x_domain, y_domain) = self.0.domain();
+        write!(
+            f,
+            "({}, {}) with {} pairs",
+            x_domain.end(),
+            y_domain.end(),
+            self.0.cardinality()
+        )
+    }
+}
@@ -1,14 +1,82 @@
 use std::fmt;
 
-use crate::relation::Relation;
+use crate::{
+    Element, ast, base64,
+    eval::{Globals, Locals},
+    labels::Labels,
+    relation::Relation,
+};
 
 pub struct DisplayRelation<'a> {
     name: &'a str,
     relation: &'a Relation,
+    labels: bool,
 }
 
 pub struct DisplayMatrix<'a> {
     relation: &'a Relation,
+    labels: bool,
+}
+
+/// Formats a homogeneous relation as an edge list: a record for each element of the domain in
+/// order, so that the elements keep their numbers when the list is read back, followed by one
+/// `source<separator>target` record per pair.
+pub struct DisplayEdgeList<'a> {
+    relation: &'a Relation,
+    separator: char,
+}
+
+/// Formats the first line of a statement, without the statements that it contains.
+pub struct DisplayStmtHeader<'a> {
+    stmt: &'a ast::Stmt,
+}
+
+/// Formats the relations and user-defined functions of a workspace in rel-lang's XML format, see
+/// [`crate::parser::xml`]. The relations keep their encodings, and the functions their source
+/// text.
+pub struct DisplayWorkspace<'a> {
+    globals: &'a Globals,
+    locals: &'a Locals,
+    /// Whether to format a REPL session, see [`DisplayWorkspace::as_session`].
+    session: bool,
+    last_result: Option<&'a Relation>,
+}
+
+pub fn display_workspace<'a>(globals: &'a Globals, locals: &'a Locals) -> DisplayWorkspace<'a> {
+    DisplayWorkspace {
+        globals,
+        locals,
+        session: false,
+        last_result: None,
+    }
+}
+
+impl<'a> DisplayWorkspace<'a> {
+    /// Formats a REPL session instead, which also has the last result. The relations are stored
+    /// as their decision diagrams in base64, see [`Relation::to_bytes`], since one element per
+    /// pair takes far too much space for large relations.
+    pub fn as_session(self, last_result: Option<&'a Relation>) -> Self {
+        Self {
+            session: true,
+            last_result,
+            ..self
+        }
+    }
+
+    fn fmt_relation(
+        &self,
+        f: &mut fmt::Formatter,
+        tag: &str,
+        attrs: &str,
+        relation: &Relation,
+    ) -> fmt::Result {
+        if self.session {
+            let bytes = base64::encode(&relation.to_bytes());
+            writeln!(f, "  <{tag}{attrs}><bdd>{bytes}</bdd></{tag}>")
+        } else {
+            fmt_xml_relation(f, tag, attrs, relation)
+        }
+    }
 }
 
 impl Relation {
@@ -16,11 +84,48 @@ impl Relation {
         DisplayRelation {
             name,
             relation: self,
+            labels: true,
         }
     }
 
     pub fn display_matrix<'a>(&'a self) -> DisplayMatrix<'a> {
-        DisplayMatrix { relation: self }
+        DisplayMatrix {
+            relation: self,
+            labels: true,
+        }
+    }
+
+    pub fn display_edge_list<'a>(&'a self, separator: char) -> DisplayEdgeList<'a> {
+        DisplayEdgeList {
+            relation: self,
+            separator,
+        }
+    }
+}
+
+impl ast::Stmt {
+    pub fn header<'a>(&'a self) -> DisplayStmtHeader<'a> {
+        DisplayStmtHeader { stmt: self }
+    }
+}
+
+impl DisplayRelation<'_> {
+    /// Numbers the elements even if the relation has labels, as in RelView's ASCII format.
+    pub fn without_labels(self) -> Self {
+        Self {
+            labels: false,
+            ..self
+        }
+    }
+}
+
+impl DisplayMatrix<'_> {
+    /// Leaves out the labels of the rows and columns, as in the matrix file format.
+    pub fn without_labels(self) -> Self {
+        Self {
+            labels: false,
+            ..self
+        }
     }
 }
 
@@ -30,20 +135,26 @@ impl fmt::Display for DisplayRelation<'_> {
             f,
             "{} ({}, {})",
             self.name,
-            self.relation.domain().0.end,
-            self.relation.domain().1.end
+            self.relation.domain().0.end(),
+            self.relation.domain().1.end()
         )?;
 
+        let (x_labels, y_labels) = if self.labels {
+            self.relation.labels()
+        } else {
+            (None, None)
+        };
+
         let mut last_x = None;
         // Entries are guaranteed to be in ascending order
         for (x, y) in self.relation.iter() {
             if Some(x) == last_x {
                 write!(f, ", ")?;
             } else {
-                write!(f, "\n{} : ", x + 1)?;
+                write!(f, "\n{} : ", DisplayElement(x_labels, x))?;
                 last_x = Some(x);
             }
-            write!(f, "{}", y + 1)?;
+            write!(f, "{}", DisplayElement(y_labels, y))?;
         }
         writeln!(f)?;
 
@@ -51,18 +162,58 @@ impl fmt::Display for DisplayRelation<'_> {
     }
 }
 
+/// Formats an element by its label, falling back to its 1-based number.
+struct DisplayElement<'a>(Option<&'a Labels>, Element);
+
+impl fmt::Display for DisplayElement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.and_then(|labels| labels.name(self.1)) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.1 + 1),
+        }
+    }
+}
+
 impl fmt::Display for DisplayMatrix<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "+")?;
-        for _ in 0..self.relation.domain().1.end {
+        let (x_domain, y_domain) = self.relation.domain();
+        let (x_labels, y_labels) = if self.labels {
+            self.relation.labels()
+        } else {
+            (None, None)
+        };
+
+        // Row labels are written in a left margin, column labels vertically above the matrix.
+        let margin = x_labels.map_or(0, |labels| {
+            labels
+                .iter()
+                .map(|name| name.chars().count())
+                .max()
+                .unwrap_or(0)
+                + 1
+        });
+        if let Some(y_labels) = y_labels {
+            let height = y_labels.iter().map(|name| name.chars().count()).max();
+            for i in 0..height.unwrap_or(0) {
+                let line = y_labels
+                    .iter()
+                    .map(|name| name.chars().nth(i).unwrap_or(' '))
+                    .collect::<String>();
+                writeln!(f, "{:margin$} {}", "", line.trim_end())?;
+            }
+        }
+
+        write!(f, "{:margin$}+", "")?;
+        for _ in 0..y_domain.end() {
             write!(f, "-")?;
         }
         writeln!(f, "+")?;
 
-        for x in 0..self.relation.domain().0.end {
-            write!(f, "|")?;
-            for y in 0..self.relation.domain().1.end {
-                if self.relation.contains((x, y)) {
+        for x in 0..x_domain.end() {
+            let name = x_labels.and_then(|labels| labels.name(x)).unwrap_or("");
+            write!(f, "{name:margin$}|")?;
+            for y in 0..y_domain.end() {
+                if self.relation.contains((x, y)) == Ok(true) {
                     write!(f, "X")?;
                 } else {
                     write!(f, " ")?;
@@ -71,8 +222,8 @@ impl fmt::Display for DisplayMatrix<'_> {
             writeln!(f, "|")?;
         }
 
-        write!(f, "+")?;
-        for _ in 0..self.relation.domain().1.end {
+        write!(f, "{:margin$}+", "")?;
+        for _ in 0..y_domain.end() {
             write!(f, "-")?;
         }
         writeln!(f, "+")?;
@@ -81,15 +232,310 @@ impl fmt::Display for DisplayMatrix<'_> {
     }
 }
 
+impl fmt::Display for DisplayEdgeList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x_labels, y_labels) = self.relation.labels();
+        let field = |labels, element| EdgeListField {
+            element: DisplayElement(labels, element).to_string(),
+            separator: self.separator,
+        };
+
+        // Elements are numbered by their first appearance when read back.
+        let (x_domain, _) = self.relation.domain();
+        for element in 0..x_domain.end() {
+            writeln!(f, "{}", field(x_labels.or(y_labels), element))?;
+        }
+
+        for (x, y) in self.relation.iter() {
+            writeln!(
+                f,
+                "{}{}{}",
+                field(x_labels, x),
+                self.separator,
+                field(y_labels, y)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+struct EdgeListField {
+    element: String,
+    separator: char,
+}
+
+impl fmt::Display for EdgeListField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let needs_quotes = self.element.trim() != self.element
+            || self.element.contains([self.separator, '"', '\r', '\n']);
+        if needs_quotes {
+            write!(f, "\"{}\"", self.element.replace('"', "\"\""))
+        } else {
+            write!(f, "{}", self.element)
+        }
+    }
+}
+
+impl fmt::Display for DisplayWorkspace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(f, "<relview>")?;
+
+        for (name, relation) in self.locals.relations() {
+            let attrs = format!(r#" name="{}""#, XmlEscaped(name));
+            self.fmt_relation(f, "relation", &attrs, relation)?;
+        }
+
+        if let Some(relation) = self.last_result {
+            self.fmt_relation(f, "result", "", relation)?;
+        }
+
+        for item in self.globals.items() {
+            let tag = match item {
+                ast::Item::Procedure { .. } => "program",
+                ast::Item::Function { .. } => "function",
+            };
+            writeln!(
+                f,
+                r#"  <{tag} name="{}">{}</{tag}>"#,
+                XmlEscaped(item.name()),
+                XmlEscaped(item.source()),
+            )?;
+        }
+
+        writeln!(f, "</relview>")?;
+
+        Ok(())
+    }
+}
+
+fn fmt_xml_relation(
+    f: &mut fmt::Formatter,
+    tag: &str,
+    attrs: &str,
+    relation: &Relation,
+) -> fmt::Result {
+    let (x_domain, y_domain) = relation.domain();
+    writeln!(
+        f,
+        r#"  <{tag}{attrs} rows="{}" cols="{}" encoding="{}">"#,
+        x_domain.end(),
+        y_domain.end(),
+        relation.encoding().name()
+    )?;
+
+    for (labels_tag, labels) in [
+        ("rowlabels", x_domain.labels()),
+        ("collabels", y_domain.labels()),
+    ] {
+        if let Some(labels) = labels {
+            write!(f, "    <{labels_tag}>")?;
+            for name in labels.iter() {
+                write!(f, "<label>{}</label>", XmlEscaped(name))?;
+            }
+            writeln!(f, "</{labels_tag}>")?;
+        }
+    }
+
+    let mut last_x = None;
+    // Entries are guaranteed to be in ascending order
+    for (x, y) in relation.iter() {
+        if Some(x) != last_x {
+            if last_x.is_some() {
+                writeln!(f, "</row>")?;
+            }
+            write!(f, r#"    <row nr="{}">"#, x + 1)?;
+            last_x = Some(x);
+        }
+        write!(f, r#"<col nr="{}"/>"#, y + 1)?;
+    }
+    if last_x.is_some() {
+        writeln!(f, "</row>")?;
+    }
+
+    writeln!(f, "  </{tag}>")
+}
+
+struct XmlEscaped<'a>(&'a str);
+
+impl fmt::Display for XmlEscaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => write!(f, "&lt;")?,
+                '>' => write!(f, "&gt;")?,
+                '&' => write!(f, "&amp;")?,
+                '"' => write!(f, "&quot;")?,
+                _ => write!(f, "{c}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Debug for Relation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.display("Relation"), f)
     }
 }
 
+/// Formats items in the RelView program syntax, such that they can be parsed again.
+impl fmt::Display for ast::Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ast::Item::Procedure {
+                name,
+                params,
+                decls,
+                body,
+                ..
+            } => {
+                writeln!(f, "{name}({})", params.join(","))?;
+                writeln!(f, "  DECL {}", decls.join(","))?;
+                writeln!(f, "  BEG")?;
+                fmt_stmts(f, body, 2)?;
+                write!(f, "  END.")
+            }
+            ast::Item::Function {
+                name,
+                params,
+                value,
+                ..
+            } => write!(f, "{name}({}) = {value}.", params.join(",")),
+        }
+    }
+}
+
+fn fmt_stmts(f: &mut fmt::Formatter, stmts: &[ast::Stmt], depth: usize) -> fmt::Result {
+    for stmt in stmts {
+        fmt_stmt(f, stmt, depth)?;
+    }
+    Ok(())
+}
+
+fn fmt_stmt(f: &mut fmt::Formatter, stmt: &ast::Stmt, depth: usize) -> fmt::Result {
+    let indent = "  ".repeat(depth);
+    writeln!(f, "{indent}{}", stmt.header())?;
+    match &stmt.kind {
+        ast::StmtKind::Assign { .. } | ast::StmtKind::Return { .. } => Ok(()),
+        ast::StmtKind::While { body, .. } => {
+            fmt_stmts(f, body, depth + 1)?;
+            writeln!(f, "{indent}OD")
+        }
+        ast::StmtKind::If {
+            then_body,
+            else_body,
+            ..
+        } => {
+            fmt_stmts(f, then_body, depth + 1)?;
+            if let Some(else_body) = else_body {
+                writeln!(f, "{indent}ELSE")?;
+                fmt_stmts(f, else_body, depth + 1)?;
+            }
+            writeln!(f, "{indent}FI")
+        }
+    }
+}
+
+impl fmt::Display for DisplayStmtHeader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.stmt.kind {
+            ast::StmtKind::Assign { lhs, rhs } => write!(f, "{lhs} = {rhs}"),
+            ast::StmtKind::While { cond, .. } => write!(f, "WHILE {cond} DO"),
+            ast::StmtKind::Return { value } => write!(f, "RETURN {value}"),
+            ast::StmtKind::If { cond, .. } => write!(f, "IF {cond} THEN"),
+        }
+    }
+}
+
+/// Formats expressions with the minimal parentheses required by the grammar in `parser`.
+impl fmt::Display for ast::Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ast::Expr::Ident { ident } => write!(f, "{ident}"),
+            ast::Expr::Call { func, args } => {
+                write!(f, "{func}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+            ast::Expr::Negate { value } => {
+                write!(f, "-")?;
+                fmt_inner_expr(f, value)
+            }
+            ast::Expr::BinExpr {
+                left,
+                op: ast::BinOp::Compose,
+                right,
+            } => {
+                fmt_product(f, left)?;
+                write!(f, " * ")?;
+                fmt_term(f, right)
+            }
+            ast::Expr::BinExpr { left, op, right } => {
+                fmt_product(f, left)?;
+                write!(f, " {op} ")?;
+                fmt_product(f, right)
+            }
+            ast::Expr::Transpose { value } => {
+                fmt_inner_expr(f, value)?;
+                write!(f, "^")
+            }
+        }
+    }
+}
+
+fn fmt_product(f: &mut fmt::Formatter, expr: &ast::Expr) -> fmt::Result {
+    match expr {
+        ast::Expr::BinExpr {
+            op: ast::BinOp::Compose,
+            ..
+        } => write!(f, "{expr}"),
+        _ => fmt_term(f, expr),
+    }
+}
+
+fn fmt_term(f: &mut fmt::Formatter, expr: &ast::Expr) -> fmt::Result {
+    match expr {
+        ast::Expr::Negate { .. } => write!(f, "{expr}"),
+        _ => fmt_inner_expr(f, expr),
+    }
+}
+
+fn fmt_inner_expr(f: &mut fmt::Formatter, expr: &ast::Expr) -> fmt::Result {
+    match expr {
+        ast::Expr::Ident { .. } | ast::Expr::Call { .. } => write!(f, "{expr}"),
+        _ => write!(f, "({expr})"),
+    }
+}
+
+impl fmt::Display for ast::BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ast::BinOp::Union => write!(f, "|"),
+            ast::BinOp::Intersect => write!(f, "&"),
+            ast::BinOp::Compose => write!(f, "*"),
+            ast::BinOp::Sum => write!(f, "+"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::parser::{matrix::parse_matrix, relation::parse_relation};
+    use crate::{
+        display::display_workspace,
+        eval::{Globals, Locals},
+        parser::{
+            csv::parse_edge_list, matrix::parse_matrix, parse_program, relation::parse_relation,
+            xml::parse_workspace,
+        },
+        relation::Relation,
+    };
 
     #[test]
     fn test_display_r1() {
@@ -132,4 +578,131 @@ mod tests {
         let relation = parse_matrix("R3.matrix", src).unwrap();
         assert_eq!(relation.display_matrix().to_string(), src);
     }
+
+    #[test]
+    fn test_display_items_roundtrip() {
+        let src = include_str!("../examples/Examples.prog");
+        let program = parse_program("Examples.prog", src).unwrap();
+        let printed = program
+            .items
+            .iter()
+            .map(|item| format!("{item}\n"))
+            .collect::<String>();
+
+        let reparsed = parse_program("Examples.prog", &printed).unwrap();
+        assert_eq!(
+            without_lines(&format!("{reparsed:?}")),
+            without_lines(&format!("{program:?}"))
+        );
+    }
+
+    /// Drops the lines of the statements and the source text of the items, which change when the
+    /// items are printed.
+    fn without_lines(debug: &str) -> String {
+        let debug = debug
+            .split("line: ")
+            .map(|part| part.trim_start_matches(|c: char| c.is_ascii_digit()))
+            .collect::<String>();
+        let mut parts = debug.split("source: \"");
+        let mut result = parts.next().unwrap_or_default().to_owned();
+        for part in parts {
+            // The source ends at the first quote that is not escaped.
+            let mut escaped = false;
+            let end = part
+                .find(|c| {
+                    let is_end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    is_end
+                })
+                .expect("the source is quoted");
+            result.push_str(&part[end + 1..]);
+        }
+        result
+    }
+
+    #[test]
+    fn test_display_workspace_roundtrip() {
+        let src = include_str!("../examples/Examples.xml");
+        let workspace = parse_workspace("Examples.xml", src).unwrap();
+        let mut globals = Globals::default();
+        let mut locals = Locals::default();
+        for (name, relation) in workspace.relations {
+            locals.assign(&name, relation);
+        }
+        globals.extend(workspace.items);
+
+        let printed = display_workspace(&globals, &locals).to_string();
+        let reparsed = parse_workspace("<printed>", &printed).unwrap();
+        let mut reparsed_globals = Globals::default();
+        let mut reparsed_locals = Locals::default();
+        for (name, relation) in reparsed.relations {
+            reparsed_locals.assign(&name, relation);
+        }
+        reparsed_globals.extend(reparsed.items);
+
+        assert_eq!(
+            display_workspace(&reparsed_globals, &reparsed_locals).to_string(),
+            printed
+        );
+        assert_eq!(
+            reparsed_locals.get("R1").unwrap(),
+            locals.get("R1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_display_edge_list_roundtrip() {
+        let src = include_str!("../examples/Edges.csv");
+        let relation = parse_edge_list("Edges.csv", src, ',').unwrap();
+        let printed = relation.display_edge_list(',').to_string();
+        assert_eq!(
+            printed,
+            "Auckland\nWellington\nChristchurch\nDunedin\n\"Queenstown, NZ\"\n\
+             Auckland,Wellington\nWellington,Christchurch\nChristchurch,Dunedin\n\
+             Dunedin,Wellington\nDunedin,\"Queenstown, NZ\"\n"
+        );
+        let reparsed = parse_edge_list("<printed>", &printed, ',').unwrap();
+        assert_eq!(reparsed, relation);
+        assert_eq!(reparsed.labels(), relation.labels());
+    }
+
+    #[test]
+    fn test_display_edge_list_unlabelled_roundtrip() {
+        let relation = Relation::sparse((..4, ..4), [(1, 0), (2, 2)]);
+        let printed = relation.display_edge_list('\t').to_string();
+        assert_eq!(printed, "1\n2\n3\n4\n2\t1\n3\t3\n");
+        let reparsed = parse_edge_list("<printed>", &printed, '\t').unwrap();
+        assert_eq!(reparsed.try_eq(&relation), Ok(true));
+    }
+
+    #[test]
+    fn test_display_labels() {
+        let src = include_str!("../examples/Edges.csv");
+        let relation = parse_edge_list("Edges.csv", src, ',').unwrap();
+        assert_eq!(
+            relation.display("Edges").to_string(),
+            "Edges (5, 5)\n\
+             Auckland : Wellington\n\
+             Wellington : Christchurch\n\
+             Christchurch : Dunedin\n\
+             Dunedin : Wellington, Queenstown, NZ\n"
+        );
+        assert_eq!(
+            relation.display("Edges").without_labels().to_string(),
+            "Edges (5, 5)\n1 : 2\n2 : 3\n3 : 4\n4 : 2, 5\n"
+        );
+    }
+
+    #[test]
+    fn test_display_matrix_labels() {
+        let relation = parse_edge_list("<test>", "ab,c\nc,ab\n", ',').unwrap();
+        assert_eq!(
+            relation.display_matrix().to_string(),
+            "    ac\n    b\n   +--+\nab | X|\nc  |X |\n   +--+\n"
+        );
+        assert_eq!(
+            relation.display_matrix().without_labels().to_string(),
+            "+--+\n| X|\n|X |\n+--+\n"
+        );
+    }
 }
@@ -1,22 +1,29 @@
 use std::{
+    cell::RefCell,
     collections::BTreeMap,
     fmt,
     ops::{self, ControlFlow},
+    time::{Duration, Instant},
 };
 
+use itertools::Itertools;
 use snafu::Snafu;
 
-use crate::{Domain, ast, relation::Relation};
+use crate::{
+    Domain, ast, dd,
+    memo::Memo,
+    profile::{self, Measurement, Profile},
+    relation::{self, Relation},
+};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Arity mismatch: expected {expected} args, got {actual} args"))]
     ArityMismatch { expected: usize, actual: usize },
-    #[snafu(display("Domain mismatch: expected {expected:?}, got {actual:?}",))]
-    DomainMismatch {
-        expected: (Domain, Domain),
-        actual: (Domain, Domain),
-    },
+    #[snafu(display("Arity mismatch: expected at least {minimum} args, got {actual} args"))]
+    TooFewArgs { minimum: usize, actual: usize },
+    #[snafu(context(false), display("{source}"))]
+    Relation { source: relation::Error },
     #[snafu(display("Empty relation"))]
     EmptyRelation,
     #[snafu(display("Unknown local variable: {name}"))]
@@ -27,14 +34,38 @@ pub enum Error {
     UnknownFunction { name: String },
     #[snafu(display("Procedure did not return a value"))]
     ProcedureDidNotReturn,
+    #[snafu(display("Node limit of {limit} exceeded"))]
+    NodeLimitExceeded { limit: usize },
+    #[snafu(display("Iteration limit of {limit} exceeded"))]
+    IterationLimitExceeded { limit: usize },
+    #[snafu(display("{func} expects the name of a function as its first argument"))]
+    ExpectedFunctionName { func: String },
+    #[snafu(display(
+        "Stack overflow: maximum call depth of {limit} exceeded{}",
+        display_trace(trace),
+    ))]
+    StackOverflow {
+        limit: usize,
+        /// The names of the functions and procedures that were called, innermost last.
+        trace: Vec<String>,
+    },
+    #[snafu(display("Evaluation stopped"))]
+    Stopped,
+    #[snafu(display("Evaluation interrupted"))]
+    Interrupted,
+    #[snafu(display("Timeout of {timeout:?} exceeded"))]
+    Timeout { timeout: Duration },
 }
 
 #[derive(Debug)]
 pub struct Globals {
     functions: BTreeMap<String, Function>,
+    /// The results of earlier calls, if memoization is enabled. Evaluation only borrows the
+    /// globals, so the memo is updated through a `RefCell`.
+    memo: RefCell<Memo>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Locals {
     relations: BTreeMap<String, Option<Relation>>,
 }
@@ -45,12 +76,84 @@ pub enum Function {
         Box<dyn Fn(Vec<Relation>) -> Result<Relation, Error>>,
     ),
     Custom(ast::Item),
+    /// A higher-order builtin that iterates a function until its result no longer changes.
+    Fixpoint(Fixpoint),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fixpoint {
+    /// `lfp(F, start, args...)` iterates `X = X | F(X, args...)` from the start.
+    Least,
+    /// `gfp(F, start, args...)` iterates `X = X & F(X, args...)` from the start.
+    Greatest,
+}
+
+/// The budget of an evaluation. Limits that are `None` fall back to the settings of the thread,
+/// like [`crate::max_iterations`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// The time after which the evaluation fails with [`Error::Timeout`].
+    pub timeout: Option<Duration>,
+    /// The number of decision diagram nodes, including the ones of existing relations, after which
+    /// the evaluation fails with [`Error::NodeLimitExceeded`]. The nodes are shared by all threads,
+    /// so this replaces the limit of [`crate::set_node_limit`] while the evaluation runs.
+    pub max_nodes: Option<usize>,
+    /// The number of iterations after which loops and fixpoints fail with
+    /// [`Error::IterationLimitExceeded`].
+    pub max_iterations: Option<usize>,
+}
+
+/// Observes the statements that procedures execute, see [`eval_observed`].
+pub trait Observer {
+    /// Called before a statement is executed, and before every evaluation of the condition of a
+    /// loop. Breaking stops the evaluation with [`Error::Stopped`].
+    fn before_stmt(
+        &mut self,
+        _call_stack: &[String],
+        _locals: &Locals,
+        _stmt: &ast::Stmt,
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after a statement was executed, and after every evaluation of the condition of a
+    /// loop.
+    fn after_stmt(&mut self, _call_stack: &[String], _stmt: &ast::Stmt, _outcome: Outcome) {}
+}
+
+/// The effect of a statement that was executed.
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome<'a> {
+    Assigned {
+        lhs: &'a str,
+        value: &'a Relation,
+    },
+    /// Whether the condition of a loop or branch was non-empty.
+    Condition {
+        holds: bool,
+    },
+    Returned {
+        value: &'a Relation,
+    },
+}
+
+/// The state of an evaluation that is shared by all nested calls.
+struct Context<'a> {
+    globals: &'a Globals,
+    /// The names of the functions and procedures that are being called, innermost last.
+    call_stack: Vec<String>,
+    observer: Option<&'a mut dyn Observer>,
+    profile: Option<&'a mut Profile>,
+    timeout: Option<Duration>,
+    max_nodes: Option<usize>,
+    max_iterations: usize,
 }
 
 impl Globals {
     fn builtins() -> Self {
         let mut globals = Self {
             functions: BTreeMap::new(),
+            memo: RefCell::default(),
         };
         globals.register_builtins();
         globals
@@ -63,56 +166,57 @@ impl Globals {
         self.register_builtin("true", |[]| Ok(Relation::true_relation()));
         self.register_builtin("FALSE", |[]| Ok(Relation::false_relation()));
         self.register_builtin("false", |[]| Ok(Relation::false_relation()));
-        self.register_builtin("L", |[r]| Ok(Relation::universal(r.domain())));
+        self.register_builtin("L", |[r]| Ok(Relation::try_universal(r.domain())?));
         self.register_builtin("O", |[r]| Ok(Relation::empty(r.domain())));
         self.register_builtin("I", |[r]| {
             let (x_domain, y_domain) = r.domain();
-            if x_domain != y_domain {
-                return Err(Error::DomainMismatch {
-                    expected: (x_domain, x_domain),
-                    actual: (x_domain, y_domain),
-                });
+            if !x_domain.is_compatible(&y_domain) {
+                return Err(relation::Error::DomainMismatch {
+                    expected: (x_domain.clone(), x_domain),
+                    actual: r.domain(),
+                }
+                .into());
             }
-            Ok(Relation::identity(x_domain))
+            Ok(Relation::try_identity(x_domain.merge(&y_domain))?)
+        });
+        self.register_builtin("Ln1", |[r]| {
+            Ok(Relation::try_universal((r.domain().0, Domain::new(1)))?)
         });
-        self.register_builtin("Ln1", |[r]| Ok(Relation::universal((r.domain().0, ..1))));
-        self.register_builtin("On1", |[r]| Ok(Relation::empty((r.domain().0, ..1))));
-        self.register_builtin("L1n", |[r]| Ok(Relation::universal((..1, r.domain().1))));
-        self.register_builtin("O1n", |[r]| Ok(Relation::empty((..1, r.domain().1))));
-        self.register_builtin("dom", |[r]| Ok(r.collapse_left()));
+        self.register_builtin("On1", |[r]| {
+            Ok(Relation::empty((r.domain().0, Domain::new(1))))
+        });
+        self.register_builtin("L1n", |[r]| {
+            Ok(Relation::try_universal((Domain::new(1), r.domain().1))?)
+        });
+        self.register_builtin("O1n", |[r]| {
+            Ok(Relation::empty((Domain::new(1), r.domain().1)))
+        });
+        self.register_builtin("dom", |[r]| Ok(r.collapse_left()?));
 
         self.register_builtin("point", |[v]| {
             if v.is_empty() {
                 return Err(Error::EmptyRelation);
             }
-            Ok(v.choose_one() * Relation::universal((v.domain().1, v.domain().1)))
+            let universal = Relation::try_universal((v.domain().1, v.domain().1))?;
+            Ok(v.choose_one()?.try_compose(universal)?)
         });
         self.register_builtin("atom", |[v]| {
             if v.is_empty() {
                 return Err(Error::EmptyRelation);
             }
-            Ok(v.choose_one())
+            Ok(v.choose_one()?)
         });
 
         self.register_builtin("empty", |[r]| Ok(Relation::from(r.is_empty())));
-        self.register_builtin("eq", |[lhs, rhs]| {
-            if lhs.domain() != rhs.domain() {
-                return Err(Error::DomainMismatch {
-                    expected: lhs.domain(),
-                    actual: rhs.domain(),
-                });
-            }
-            Ok(Relation::from(lhs == rhs))
-        });
+        self.register_builtin("eq", |[lhs, rhs]| Ok(Relation::from(lhs.try_eq(&rhs)?)));
         self.register_builtin("incl", |[lhs, rhs]| {
-            if lhs.domain() != rhs.domain() {
-                return Err(Error::DomainMismatch {
-                    expected: lhs.domain(),
-                    actual: rhs.domain(),
-                });
-            }
-            Ok(Relation::from(lhs.is_subset_of(&rhs)))
+            Ok(Relation::from(lhs.try_is_subset_of(&rhs)?))
         });
+
+        for fixpoint in [Fixpoint::Least, Fixpoint::Greatest] {
+            self.functions
+                .insert(fixpoint.name().to_owned(), Function::Fixpoint(fixpoint));
+        }
     }
 
     fn register_builtin<const N: usize>(
@@ -151,6 +255,29 @@ impl Extend<ast::Item> for Globals {
             iter.into_iter()
                 .map(|item| (item.name().to_owned(), Function::Custom(item))),
         );
+        // The functions may have been redefined.
+        self.memo.get_mut().clear();
+    }
+}
+
+impl Globals {
+    /// Returns the memo of the results of calls to user-defined functions and procedures.
+    pub fn memo(&self) -> &RefCell<Memo> {
+        &self.memo
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Returns the user-defined functions and procedures, ordered by name.
+    pub fn items(&self) -> impl Iterator<Item = &ast::Item> {
+        self.functions
+            .values()
+            .filter_map(|function| match function {
+                Function::BuiltIn(..) | Function::Fixpoint(_) => None,
+                Function::Custom(item) => Some(item),
+            })
     }
 }
 
@@ -173,6 +300,13 @@ impl Locals {
             })
     }
 
+    /// Returns the initialized variables, ordered by name.
+    pub fn relations(&self) -> impl Iterator<Item = (&str, &Relation)> {
+        self.relations
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)))
+    }
+
     pub fn variables_by_prefix(&self, prefix: &str) -> impl Iterator<Item = &str> {
         self.relations
             .range(prefix.to_owned()..)
@@ -182,127 +316,228 @@ impl Locals {
 }
 
 impl Function {
-    fn call(&self, globals: &Globals, args: Vec<Relation>) -> Result<Relation, Error> {
+    /// Calls the function with the budget of the options, like [`eval`] evaluates a call.
+    pub fn call(
+        &self,
+        globals: &Globals,
+        args: Vec<Relation>,
+        options: &EvalOptions,
+    ) -> Result<Relation, Error> {
+        if let Function::Fixpoint(fixpoint) = self {
+            // The function to iterate can only be named in an expression.
+            return Err(Error::ExpectedFunctionName {
+                func: fixpoint.name().to_owned(),
+            });
+        }
+        Context::new(globals, options).run(|ctx| self.call_in(ctx, args))
+    }
+
+    fn call_in(&self, ctx: &mut Context, args: Vec<Relation>) -> Result<Relation, Error> {
+        let measurement = ctx.start_measurement(|_| self.profile_key());
+        let result = self.call_unmeasured(ctx, args);
+        ctx.stop_measurement(measurement);
+        result
+    }
+
+    fn call_unmeasured(&self, ctx: &mut Context, args: Vec<Relation>) -> Result<Relation, Error> {
         match self {
             Function::BuiltIn(_name, f) => f(args),
-            Function::Custom(item) => match item {
-                ast::Item::Procedure {
-                    name: _,
-                    params,
-                    decls,
-                    body,
-                } => {
-                    if params.len() != args.len() {
-                        return Err(Error::ArityMismatch {
-                            expected: params.len(),
-                            actual: args.len(),
-                        });
-                    }
-                    let mut locals = Locals::default();
-                    for (param, arg) in params.iter().zip(args) {
-                        locals.relations.insert((*param).to_owned(), Some(arg));
-                    }
-                    for decl in decls {
-                        locals.relations.insert((*decl).to_owned(), None);
-                    }
-
-                    let res = eval_stmts(globals, &mut locals, body);
-                    match res {
-                        ControlFlow::Break(Ok(r)) => Ok(r),
-                        ControlFlow::Break(Err(err)) => Err(err),
-                        ControlFlow::Continue(()) => Err(Error::ProcedureDidNotReturn),
-                    }
+            Function::Fixpoint(_) => unreachable!("fixpoints are evaluated by eval_fixpoint"),
+            Function::Custom(item) => {
+                // Memoized calls would not report their statements to the observer.
+                let memoize = ctx.observer.is_none() && ctx.globals.memo.borrow().is_enabled();
+                if memoize
+                    && let Some(result) = ctx.globals.memo.borrow_mut().get(item.name(), &args)
+                {
+                    return Ok(result);
                 }
-                ast::Item::Function {
-                    name: _,
-                    params,
-                    value,
-                } => {
-                    if params.len() != args.len() {
-                        return Err(Error::ArityMismatch {
-                            expected: params.len(),
-                            actual: args.len(),
-                        });
-                    }
-                    let mut locals = Locals::default();
-                    for (param, arg) in params.iter().zip(args) {
-                        locals.relations.insert((*param).to_owned(), Some(arg));
-                    }
-
-                    eval(globals, &locals, value)
+                let memo_args = memoize.then(|| args.clone());
+
+                let limit = crate::max_call_depth();
+                if ctx.call_stack.len() >= limit {
+                    let mut trace = ctx.call_stack.clone();
+                    trace.push(item.name().to_owned());
+                    return Err(Error::StackOverflow { limit, trace });
                 }
-            },
+
+                ctx.call_stack.push(item.name().to_owned());
+                let result = call_item(ctx, item, args);
+                ctx.call_stack.pop();
+
+                // Failed decision diagram operations leave placeholders that must not be reused.
+                if let (Some(args), Ok(result)) = (memo_args, &result)
+                    && !crate::dd_manager().has_error()
+                {
+                    let mut memo = ctx.globals.memo.borrow_mut();
+                    memo.insert(item.name(), args, result.clone());
+                }
+                result
+            }
+        }
+    }
+}
+
+fn call_item(ctx: &mut Context, item: &ast::Item, args: Vec<Relation>) -> Result<Relation, Error> {
+    match item {
+        ast::Item::Procedure {
+            params,
+            decls,
+            body,
+            ..
+        } => {
+            if params.len() != args.len() {
+                return Err(Error::ArityMismatch {
+                    expected: params.len(),
+                    actual: args.len(),
+                });
+            }
+            let mut locals = Locals::default();
+            for (param, arg) in params.iter().zip(args) {
+                locals.relations.insert((*param).to_owned(), Some(arg));
+            }
+            for decl in decls {
+                locals.relations.insert((*decl).to_owned(), None);
+            }
+
+            let res = eval_stmts(ctx, &mut locals, body);
+            match res {
+                ControlFlow::Break(Ok(r)) => Ok(r),
+                ControlFlow::Break(Err(err)) => Err(err),
+                ControlFlow::Continue(()) => Err(Error::ProcedureDidNotReturn),
+            }
+        }
+        ast::Item::Function { params, value, .. } => {
+            if params.len() != args.len() {
+                return Err(Error::ArityMismatch {
+                    expected: params.len(),
+                    actual: args.len(),
+                });
+            }
+            let mut locals = Locals::default();
+            for (param, arg) in params.iter().zip(args) {
+                locals.relations.insert((*param).to_owned(), Some(arg));
+            }
+
+            eval_expr(ctx, &locals, value)
         }
     }
 }
 
 fn eval_stmts(
-    globals: &Globals,
+    ctx: &mut Context,
     locals: &mut Locals,
     body: &[ast::Stmt],
 ) -> ops::ControlFlow<Result<Relation, Error>> {
     for stmt in body {
-        eval_stmt(globals, locals, stmt)?;
+        eval_stmt(ctx, locals, stmt)?;
     }
     ControlFlow::Continue(())
 }
 
 fn eval_stmt(
-    globals: &Globals,
+    ctx: &mut Context,
     locals: &mut Locals,
     stmt: &ast::Stmt,
 ) -> ops::ControlFlow<Result<Relation, Error>> {
-    match stmt {
-        ast::Stmt::Assign { lhs, rhs } => {
+    let measurement = ctx.start_measurement(|ctx| profile::Key {
+        kind: profile::Kind::Statement,
+        name: format!(
+            "{}:{} {}",
+            ctx.call_stack.last().map_or("", String::as_str),
+            stmt.line,
+            stmt.header()
+        ),
+    });
+    let result = eval_stmt_unmeasured(ctx, locals, stmt);
+    ctx.stop_measurement(measurement);
+    result
+}
+
+fn eval_stmt_unmeasured(
+    ctx: &mut Context,
+    locals: &mut Locals,
+    stmt: &ast::Stmt,
+) -> ops::ControlFlow<Result<Relation, Error>> {
+    if let Err(e) = ctx.check_cancelled() {
+        return ops::ControlFlow::Break(Err(e));
+    }
+
+    match &stmt.kind {
+        ast::StmtKind::Assign { lhs, rhs } => {
+            ctx.before_stmt(locals, stmt)?;
             if !locals.relations.contains_key(lhs) {
                 return ops::ControlFlow::Break(Err(Error::UnknownLocal { name: lhs.clone() }));
             }
 
-            let value = match eval(globals, locals, rhs) {
+            let value = match eval_expr(ctx, locals, rhs) {
                 Ok(v) => v,
                 Err(e) => return ops::ControlFlow::Break(Err(e)),
             };
+            ctx.after_stmt(stmt, Outcome::Assigned { lhs, value: &value });
 
             // We checked that lhs exists earlier, and variables never become undeclared
             let var = locals.relations.get_mut(lhs).unwrap();
             *var = Some(value);
             ops::ControlFlow::Continue(())
         }
-        ast::Stmt::While { cond, body } => {
-            const MAX_LOOP: usize = 1_000; // Prevent infinite loops
-            for _ in 0..MAX_LOOP {
-                let cond_value = match eval(globals, locals, cond) {
+        ast::StmtKind::While { cond, body } => {
+            let limit = ctx.max_iterations;
+            for _ in 0..limit {
+                if let Err(e) = ctx.check_cancelled() {
+                    return ops::ControlFlow::Break(Err(e));
+                }
+                ctx.before_stmt(locals, stmt)?;
+                let cond_value = match eval_expr(ctx, locals, cond) {
                     Ok(v) => v,
                     Err(e) => return ops::ControlFlow::Break(Err(e)),
                 };
+                // The condition is meaningless if an operation failed.
+                if let Err(e) = ctx.check_dd_error() {
+                    return ops::ControlFlow::Break(Err(e));
+                }
+                ctx.after_stmt(
+                    stmt,
+                    Outcome::Condition {
+                        holds: !cond_value.is_empty(),
+                    },
+                );
                 if cond_value.is_empty() {
                     return ControlFlow::Continue(());
                 }
 
-                eval_stmts(globals, locals, body)?;
+                eval_stmts(ctx, locals, body)?;
             }
-            panic!("While loop exceeded {MAX_LOOP} iterations");
+            ops::ControlFlow::Break(Err(Error::IterationLimitExceeded { limit }))
         }
-        ast::Stmt::Return { value } => {
-            let value = match eval(globals, locals, value) {
+        ast::StmtKind::Return { value } => {
+            ctx.before_stmt(locals, stmt)?;
+            let value = match eval_expr(ctx, locals, value) {
                 Ok(v) => v,
                 Err(e) => return ops::ControlFlow::Break(Err(e)),
             };
+            ctx.after_stmt(stmt, Outcome::Returned { value: &value });
             ops::ControlFlow::Break(Ok(value))
         }
-        ast::Stmt::If {
+        ast::StmtKind::If {
             cond,
             then_body,
             else_body,
         } => {
-            let cond_value = match eval(globals, locals, cond) {
+            ctx.before_stmt(locals, stmt)?;
+            let cond_value = match eval_expr(ctx, locals, cond) {
                 Ok(v) => v,
                 Err(e) => return ops::ControlFlow::Break(Err(e)),
             };
-            if cond_value.is_empty() {
-                eval_stmts(globals, locals, then_body)
+            ctx.after_stmt(
+                stmt,
+                Outcome::Condition {
+                    holds: !cond_value.is_empty(),
+                },
+            );
+            if !cond_value.is_empty() {
+                eval_stmts(ctx, locals, then_body)
             } else if let Some(else_body) = else_body {
-                eval_stmts(globals, locals, else_body)
+                eval_stmts(ctx, locals, else_body)
             } else {
                 ControlFlow::Continue(())
             }
@@ -310,58 +545,359 @@ fn eval_stmt(
     }
 }
 
-pub fn eval(globals: &Globals, locals: &Locals, expr: &ast::Expr) -> Result<Relation, Error> {
+pub fn eval(
+    globals: &Globals,
+    locals: &Locals,
+    expr: &ast::Expr,
+    options: &EvalOptions,
+) -> Result<Relation, Error> {
+    Context::new(globals, options).run(|ctx| eval_expr(ctx, locals, expr))
+}
+
+/// Like [`eval`], but reports the statements that procedures execute to the observer. Calls are
+/// not memoized, so that the statements of every call are reported.
+pub fn eval_observed(
+    globals: &Globals,
+    locals: &Locals,
+    expr: &ast::Expr,
+    observer: &mut dyn Observer,
+) -> Result<Relation, Error> {
+    let ctx = Context {
+        observer: Some(observer),
+        ..Context::new(globals, &EvalOptions::default())
+    };
+    ctx.run(|ctx| eval_expr(ctx, locals, expr))
+}
+
+/// Like [`eval`], but measures the calls of functions, procedures and builtins, and the statements
+/// of procedures.
+pub fn eval_profiled(
+    globals: &Globals,
+    locals: &Locals,
+    expr: &ast::Expr,
+    profile: &mut Profile,
+) -> Result<Relation, Error> {
+    let ctx = Context {
+        profile: Some(profile),
+        ..Context::new(globals, &EvalOptions::default())
+    };
+    ctx.run(|ctx| eval_expr(ctx, locals, expr))
+}
+
+impl<'a> Context<'a> {
+    fn new(globals: &'a Globals, options: &EvalOptions) -> Self {
+        Self {
+            globals,
+            call_stack: Vec::new(),
+            observer: None,
+            profile: None,
+            timeout: options.timeout.or_else(crate::timeout),
+            max_nodes: options.max_nodes,
+            max_iterations: options.max_iterations.unwrap_or_else(crate::max_iterations),
+        }
+    }
+
+    /// Runs an evaluation within the budget of the context.
+    fn run(
+        mut self,
+        f: impl FnOnce(&mut Self) -> Result<Relation, Error>,
+    ) -> Result<Relation, Error> {
+        let manager = crate::dd_manager();
+        let node_limit = manager.node_limit();
+        if let Some(max_nodes) = self.max_nodes {
+            manager.set_node_limit(Some(max_nodes));
+        }
+        let new_deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let deadline = crate::replace_deadline(new_deadline);
+        // The manager checks the deadline itself, so that single long operations stop in time.
+        let manager_deadline = manager.deadline();
+        manager.set_deadline(new_deadline);
+
+        // Relation constructors return the errors of the manager themselves.
+        let result = f(&mut self).map_err(|error| match error {
+            Error::Relation {
+                source: relation::Error::Aborted { source },
+            } => self.dd_error(source),
+            Error::Relation {
+                source: relation::Error::Stopped,
+            } => self.check_cancelled().err().unwrap_or(Error::Interrupted),
+            error => error,
+        });
+        // Interrupted relation operations may return partial results.
+        let result = self
+            .check_dd_error()
+            .and_then(|()| self.check_cancelled())
+            .and(result);
+        // The memoized results keep their nodes alive, so they are dropped to make room for the next
+        // evaluation.
+        if let Err(Error::NodeLimitExceeded { .. }) = result {
+            self.globals.memo.borrow_mut().clear();
+        }
+
+        manager.set_deadline(manager_deadline);
+        crate::replace_deadline(deadline);
+        manager.set_node_limit(node_limit);
+        result
+    }
+
+    /// Returns the error of a decision diagram operation since the last check. Failed operations
+    /// only leave a placeholder result behind, which must not be used.
+    fn check_dd_error(&self) -> Result<(), Error> {
+        match crate::dd_manager().take_error() {
+            Some(error) => Err(self.dd_error(error)),
+            None => Ok(()),
+        }
+    }
+
+    fn dd_error(&self, error: dd::Error) -> Error {
+        match error {
+            dd::Error::NodeLimit { limit } => Error::NodeLimitExceeded { limit },
+            dd::Error::Interrupted => Error::Interrupted,
+            dd::Error::Timeout => Error::Timeout {
+                timeout: self
+                    .timeout
+                    .expect("the deadline is only set with a timeout"),
+            },
+        }
+    }
+
+    /// Fails if the evaluation was interrupted, see [`crate::interrupt_flag`], or ran out of time.
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if crate::is_interrupted() {
+            Err(Error::Interrupted)
+        } else if let Some(timeout) = self.timeout
+            && crate::is_past_deadline()
+        {
+            Err(Error::Timeout { timeout })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn before_stmt(
+        &mut self,
+        locals: &Locals,
+        stmt: &ast::Stmt,
+    ) -> ControlFlow<Result<Relation, Error>> {
+        let Some(observer) = self.observer.as_deref_mut() else {
+            return ControlFlow::Continue(());
+        };
+        match observer.before_stmt(&self.call_stack, locals, stmt) {
+            ControlFlow::Continue(()) => ControlFlow::Continue(()),
+            ControlFlow::Break(()) => ControlFlow::Break(Err(Error::Stopped)),
+        }
+    }
+
+    fn after_stmt(&mut self, stmt: &ast::Stmt, outcome: Outcome) {
+        if let Some(observer) = self.observer.as_deref_mut() {
+            observer.after_stmt(&self.call_stack, stmt, outcome);
+        }
+    }
+
+    /// Starts measuring a call or statement if the evaluation is profiled. The key is only built
+    /// then.
+    fn start_measurement(
+        &mut self,
+        key: impl FnOnce(&Self) -> profile::Key,
+    ) -> Option<Measurement> {
+        let key = self.profile.is_some().then(|| key(self))?;
+        self.profile
+            .as_deref_mut()
+            .map(|profile| profile.start(key))
+    }
+
+    fn stop_measurement(&mut self, measurement: Option<Measurement>) {
+        if let (Some(profile), Some(measurement)) = (self.profile.as_deref_mut(), measurement) {
+            profile.stop(measurement);
+        }
+    }
+}
+
+fn eval_expr(ctx: &mut Context, locals: &Locals, expr: &ast::Expr) -> Result<Relation, Error> {
     match expr {
         ast::Expr::Ident { ident } => locals.get(ident),
         ast::Expr::Call { func, args } => {
-            let func = globals
+            let func = ctx
+                .globals
                 .functions
                 .get(func)
                 .ok_or_else(|| Error::UnknownFunction {
                     name: func.to_owned(),
                 })?;
+            if let Function::Fixpoint(fixpoint) = func {
+                let measurement = ctx.start_measurement(|_| func.profile_key());
+                let result = eval_fixpoint(ctx, locals, *fixpoint, args);
+                ctx.stop_measurement(measurement);
+                return result;
+            }
             let args = args
                 .iter()
-                .map(|arg| eval(globals, locals, arg))
+                .map(|arg| eval_expr(ctx, locals, arg))
                 .collect::<Result<_, _>>()?;
-            func.call(globals, args)
+            func.call_in(ctx, args)
+        }
+        ast::Expr::Negate { value } => {
+            let relation = eval_expr(ctx, locals, value)?;
+            eval_op(ctx, "-", || Ok(relation.try_complement()?))
+        }
+        ast::Expr::BinExpr {
+            left,
+            op: ast::BinOp::Compose,
+            right,
+        } if matches!(**left, ast::Expr::Transpose { .. }) => {
+            let ast::Expr::Transpose { value } = &**left else {
+                unreachable!("lhs is a transpose");
+            };
+            let relation = eval_expr(ctx, locals, value)?;
+            let rhs = eval_expr(ctx, locals, right)?;
+            // `R^ * v` is the image of the vector, which does not need the converse of `R`.
+            if is_vector(&rhs) && relation.domain().0.is_compatible(&rhs.domain().0) {
+                return eval_op(ctx, "image", || Ok(relation.try_image(&rhs)?));
+            }
+            let converse = eval_op(ctx, "^", || Ok(relation.try_converse()?))?;
+            compose(ctx, converse, rhs)
         }
-        ast::Expr::Negate { value } => eval(globals, locals, value).map(|r| -r),
         ast::Expr::BinExpr { left, op, right } => {
-            let lhs = eval(globals, locals, left)?;
-            let rhs = eval(globals, locals, right)?;
+            let lhs = eval_expr(ctx, locals, left)?;
+            let rhs = eval_expr(ctx, locals, right)?;
             match op {
-                ast::BinOp::Union => {
-                    if lhs.domain() != rhs.domain() {
-                        return Err(Error::DomainMismatch {
-                            expected: lhs.domain(),
-                            actual: rhs.domain(),
-                        });
-                    }
-                    Ok(lhs | rhs)
-                }
-                ast::BinOp::Intersect => {
-                    if lhs.domain() != rhs.domain() {
-                        return Err(Error::DomainMismatch {
-                            expected: lhs.domain(),
-                            actual: rhs.domain(),
-                        });
-                    }
-                    Ok(lhs & rhs)
-                }
-                ast::BinOp::Compose => {
-                    if lhs.domain().1 != rhs.domain().0 {
-                        return Err(Error::DomainMismatch {
-                            expected: (lhs.domain().1, rhs.domain().1),
-                            actual: rhs.domain(),
-                        });
-                    }
-                    Ok(lhs * rhs)
-                }
+                ast::BinOp::Union => eval_op(ctx, "|", || Ok(lhs.try_union(rhs)?)),
+                ast::BinOp::Intersect => eval_op(ctx, "&", || Ok(lhs.try_intersection(rhs)?)),
+                ast::BinOp::Compose => compose(ctx, lhs, rhs),
                 ast::BinOp::Sum => todo!(),
             }
         }
-        ast::Expr::Transpose { value } => eval(globals, locals, value).map(|r| r.converse()),
+        ast::Expr::Transpose { value } => {
+            let relation = eval_expr(ctx, locals, value)?;
+            eval_op(ctx, "^", || Ok(relation.try_converse()?))
+        }
+    }
+}
+
+/// Applies an operator to evaluated operands, measuring it on its own if the evaluation is
+/// profiled.
+fn eval_op(
+    ctx: &mut Context,
+    name: &str,
+    op: impl FnOnce() -> Result<Relation, Error>,
+) -> Result<Relation, Error> {
+    let measurement = ctx.start_measurement(|_| profile::Key {
+        kind: profile::Kind::Operator,
+        name: name.to_owned(),
+    });
+    let result = op();
+    ctx.stop_measurement(measurement);
+    result
+}
+
+/// Iterates the function that is named by the first argument, starting from the second argument,
+/// until the relation no longer changes. The remaining arguments are passed on to the function.
+fn eval_fixpoint(
+    ctx: &mut Context,
+    locals: &Locals,
+    fixpoint: Fixpoint,
+    args: &[ast::Expr],
+) -> Result<Relation, Error> {
+    let [func, start, extra_args @ ..] = args else {
+        return Err(Error::TooFewArgs {
+            minimum: 2,
+            actual: args.len(),
+        });
+    };
+    let ast::Expr::Ident { ident } = func else {
+        return Err(Error::ExpectedFunctionName {
+            func: fixpoint.name().to_owned(),
+        });
+    };
+    let func = ctx
+        .globals
+        .functions
+        .get(ident)
+        .ok_or_else(|| Error::UnknownFunction {
+            name: ident.to_owned(),
+        })?;
+    if let Function::Fixpoint(_) = func {
+        return Err(Error::ExpectedFunctionName {
+            func: fixpoint.name().to_owned(),
+        });
+    }
+
+    let mut current = eval_expr(ctx, locals, start)?;
+    let extra_args = extra_args
+        .iter()
+        .map(|arg| eval_expr(ctx, locals, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let limit = ctx.max_iterations;
+    for _ in 0..limit {
+        let mut args = Vec::with_capacity(1 + extra_args.len());
+        args.push(current.clone());
+        args.extend(extra_args.iter().cloned());
+        let value = func.call_in(ctx, args)?;
+        ctx.check_dd_error()?;
+        ctx.check_cancelled()?;
+        let next = match fixpoint {
+            Fixpoint::Least => current.clone().try_union(value)?,
+            Fixpoint::Greatest => current.clone().try_intersection(value)?,
+        };
+        if next.try_eq(&current)? {
+            return Ok(current);
+        }
+        current = next;
+    }
+    Err(Error::IterationLimitExceeded { limit })
+}
+
+fn compose(ctx: &mut Context, lhs: Relation, rhs: Relation) -> Result<Relation, Error> {
+    // `R * v` is the preimage of the vector.
+    if is_vector(&rhs) && lhs.domain().1.is_compatible(&rhs.domain().0) {
+        return eval_op(ctx, "preimage", || Ok(lhs.try_preimage(&rhs)?));
+    }
+    eval_op(ctx, "*", || Ok(lhs.try_compose(rhs)?))
+}
+
+/// Returns whether the relation is a vector, which has a single column.
+fn is_vector(relation: &Relation) -> bool {
+    relation.domain().1.end() == 1
+}
+
+/// Formats the call stack with the innermost call first, and repeated calls collapsed.
+fn display_trace(trace: &[String]) -> String {
+    let mut lines = String::new();
+    for (name, calls) in &trace.iter().rev().chunk_by(|name| *name) {
+        match calls.count() {
+            1 => lines += &format!("\n  in {name}"),
+            count => lines += &format!("\n  in {name} ({count} calls)"),
+        }
+    }
+    lines
+}
+
+impl Fixpoint {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Least => "lfp",
+            Self::Greatest => "gfp",
+        }
+    }
+}
+
+impl Function {
+    fn profile_key(&self) -> profile::Key {
+        let (kind, name) = match self {
+            Function::BuiltIn(name, _) => (profile::Kind::Builtin, *name),
+            Function::Fixpoint(fixpoint) => (profile::Kind::Builtin, fixpoint.name()),
+            Function::Custom(item @ ast::Item::Procedure { .. }) => {
+                (profile::Kind::Procedure, item.name())
+            }
+            Function::Custom(item @ ast::Item::Function { .. }) => {
+                (profile::Kind::Function, item.name())
+            }
+        };
+        profile::Key {
+            kind,
+            name: name.to_owned(),
+        }
     }
 }
 
@@ -369,6 +905,7 @@ impl fmt::Debug for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Function::BuiltIn(name, _) => write!(f, "<builtin-function {name}>"),
+            Function::Fixpoint(fixpoint) => write!(f, "<builtin-function {}>", fixpoint.name()),
             Function::Custom(item) => write!(f, "<function {item:?}>"),
         }
     }
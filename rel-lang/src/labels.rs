@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use crate::Element;
+
+/// Names for the elements of a domain, in element order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels {
+    names: Vec<String>,
+    elements: BTreeMap<String, Element>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> Element {
+        self.names.len() as Element
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, element: Element) -> Option<&str> {
+        self.names.get(element as usize).map(String::as_str)
+    }
+
+    pub fn element(&self, name: &str) -> Option<Element> {
+        self.elements.get(name).copied()
+    }
+
+    /// Returns the element with the given name, allocating the next free element if there is none.
+    pub fn get_or_insert(&mut self, name: &str) -> Element {
+        if let Some(element) = self.element(name) {
+            return element;
+        }
+
+        let element = self.len();
+        self.names.push(name.to_owned());
+        self.elements.insert(name.to_owned(), element);
+        element
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+}
@@ -1,18 +1,38 @@
-use std::{fs, ops, sync::OnceLock};
+use std::{
+    cell::Cell,
+    fmt, fs, ops,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use itertools::Itertools;
 use rel_lang_dd as dd;
-use snafu::Snafu;
+use snafu::{Snafu, ensure};
 
 use crate::{
+    display::display_workspace,
     eval::{Globals, Locals},
-    parser::{parse_program, relation::parse_relation},
+    labels::Labels,
+    parser::{
+        csv::parse_edge_list, labels::parse_labels, parse_program, relation::parse_relation,
+        xml::parse_workspace,
+    },
+    relation::Relation,
+    repl::State,
 };
 
 pub mod ast;
+mod base64;
+pub mod debugger;
 pub mod display;
 pub mod eval;
+pub mod labels;
+pub mod memo;
 pub mod parser;
+pub mod profile;
 pub mod relation;
 pub mod repl;
 pub mod repl_commands;
@@ -26,22 +46,103 @@ pub enum Error {
     Parse { source: parser::Error },
     #[snafu(transparent)]
     Eval { source: eval::Error },
+    #[snafu(transparent)]
+    Decode { source: relation::DecodeError },
+    #[snafu(display("Edge lists need the same domain on both sides, got {domain:?}"))]
+    Heterogeneous { domain: (Domain, Domain) },
+    #[snafu(display("{count} labels do not fit the domains {domain:?}"))]
+    LabelCount {
+        count: Element,
+        domain: (Domain, Domain),
+    },
 }
 
 pub type Element = u32;
-pub type Domain = ops::RangeTo<Element>;
 
-fn iter_domain(domain: Domain) -> impl Iterator<Item = Element> + Clone {
+/// The elements `0..end`, optionally with a name for each element.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Domain {
+    end: Element,
+    labels: Option<Arc<Labels>>,
+}
+
+impl Domain {
+    pub fn new(end: Element) -> Self {
+        Self { end, labels: None }
+    }
+
+    pub fn labelled(labels: Arc<Labels>) -> Self {
+        Self {
+            end: labels.len(),
+            labels: Some(labels),
+        }
+    }
+
+    pub fn end(&self) -> Element {
+        self.end
+    }
+
+    pub fn labels(&self) -> Option<&Labels> {
+        self.labels.as_deref()
+    }
+
+    pub fn contains(&self, element: &Element) -> bool {
+        *element < self.end
+    }
+
+    /// Domains are compatible if they have the same size, and their labels are equal when both
+    /// domains are labelled.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.end == other.end
+            && match (&self.labels, &other.labels) {
+                (Some(lhs), Some(rhs)) => Arc::ptr_eq(lhs, rhs) || lhs == rhs,
+                _ => true,
+            }
+    }
+
+    /// Combines two compatible domains, keeping the labels of either.
+    pub fn merge(&self, other: &Self) -> Self {
+        debug_assert!(
+            self.is_compatible(other),
+            "domains {self:?} and {other:?} are not compatible",
+        );
+        if self.labels.is_some() {
+            self.clone()
+        } else {
+            other.clone()
+        }
+    }
+}
+
+impl From<ops::RangeTo<Element>> for Domain {
+    fn from(range: ops::RangeTo<Element>) -> Self {
+        Self::new(range.end)
+    }
+}
+
+impl fmt::Debug for Domain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "..{}", self.end)?;
+        if self.labels.is_some() {
+            write!(f, " (labelled)")?;
+        }
+        Ok(())
+    }
+}
+
+fn iter_domain(domain: &Domain) -> impl Iterator<Item = Element> + Clone + use<> {
     0..domain.end
 }
 
-fn iter_domain_product(domain: (Domain, Domain)) -> impl Iterator<Item = (Element, Element)> {
-    let x_iter = iter_domain(domain.0);
-    let y_iter = iter_domain(domain.1);
+fn iter_domain_product(
+    domain: &(Domain, Domain),
+) -> impl Iterator<Item = (Element, Element)> + use<> {
+    let x_iter = iter_domain(&domain.0);
+    let y_iter = iter_domain(&domain.1);
     x_iter.cartesian_product(y_iter)
 }
 
-fn num_vars(domain: Domain) -> u32 {
+fn num_vars(domain: &Domain) -> u32 {
     if domain.end == 0 {
         0
     } else {
@@ -52,85 +153,204 @@ fn num_vars(domain: Domain) -> u32 {
 
 /// Big-endian bit representation of an element in the given domain.
 fn bits(
-    domain: Domain,
+    domain: &Domain,
     n: Element,
-) -> impl DoubleEndedIterator<Item = bool> + ExactSizeIterator {
+) -> impl DoubleEndedIterator<Item = bool> + ExactSizeIterator + use<> {
     (0..num_vars(domain))
         .rev()
         .map(move |i| (n & (1u32 << i)) != 0)
 }
 
-fn bits2(
-    domain: (Domain, Domain),
-    pair: (Element, Element),
-) -> impl DoubleEndedIterator<Item = bool> + ExactSizeIterator {
-    chain_exact(bits(domain.0, pair.0), bits(domain.1, pair.1))
+/// How the bits of the two elements of a pair are assigned to the variables of a decision diagram.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// All bits of the source element, followed by all bits of the target element.
+    #[default]
+    Concatenated,
+    /// The bits of the source and target elements alternate, which keeps the decision diagrams of
+    /// identity-like relations linear in the number of bits.
+    Interleaved,
 }
 
-fn chain_exact<T, I, J>(
-    i: I,
-    j: J,
-) -> impl DoubleEndedIterator<Item = T> + ExactSizeIterator
-where
-    I: Iterator<Item = T> + DoubleEndedIterator + ExactSizeIterator,
-    J: Iterator<Item = T> + DoubleEndedIterator + ExactSizeIterator,
-{
-    struct ChainExact<I, J> {
-        i: I,
-        j: J,
-    }
-
-    impl<T, I, J> Iterator for ChainExact<I, J>
-    where
-        I: Iterator<Item = T>,
-        J: Iterator<Item = T>,
-    {
-        type Item = T;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Source,
+    Target,
+}
 
-        fn next(&mut self) -> Option<Self::Item> {
-            self.i.next().or_else(|| self.j.next())
+impl Encoding {
+    /// The name of the encoding in the REPL and in saved sessions.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Concatenated => "concatenated",
+            Self::Interleaved => "interleaved",
         }
+    }
 
-        fn size_hint(&self) -> (usize, Option<usize>) {
-            let (i_lower, i_upper) = self.i.size_hint();
-            let (j_lower, j_upper) = self.j.size_hint();
-            (
-                i_lower + j_lower,
-                i_upper.and_then(|i| j_upper.map(|j| i + j)),
-            )
-        }
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Concatenated, Self::Interleaved]
+            .into_iter()
+            .find(|encoding| encoding.name() == name)
     }
 
-    impl<T, I, J> DoubleEndedIterator for ChainExact<I, J>
-    where
-        I: DoubleEndedIterator<Item = T>,
-        J: DoubleEndedIterator<Item = T>,
-    {
-        fn next_back(&mut self) -> Option<Self::Item> {
-            self.j.next_back().or_else(|| self.i.next_back())
+    /// Returns the variable of the `i`-th most significant bit of an element on one side of a pair,
+    /// where the elements have `num_vars` bits.
+    fn var(self, num_vars: (u32, u32), side: Side, i: u32) -> u64 {
+        let i = u64::from(i);
+        match (self, side) {
+            (Self::Concatenated, Side::Source) => i,
+            (Self::Concatenated, Side::Target) => u64::from(num_vars.0) + i,
+            (Self::Interleaved, side) => {
+                let common = u64::from(num_vars.0.min(num_vars.1));
+                let offset = if side == Side::Source { 0 } else { 1 };
+                if i < common {
+                    2 * i + offset
+                } else {
+                    common + i
+                }
+            }
         }
     }
 
-    impl<T, I, J> ExactSizeIterator for ChainExact<I, J>
-    where
-        I: ExactSizeIterator<Item = T>,
-        J: ExactSizeIterator<Item = T>,
-    {
-        fn len(&self) -> usize {
-            self.i.len() + self.j.len()
+    /// The inverse of [`Encoding::var`].
+    fn side(self, num_vars: (u32, u32), var: u64) -> (Side, u32) {
+        let num_x_vars = u64::from(num_vars.0);
+        match self {
+            Self::Concatenated if var < num_x_vars => (Side::Source, var as u32),
+            Self::Concatenated => (Side::Target, (var - num_x_vars) as u32),
+            Self::Interleaved => {
+                let common = u64::from(num_vars.0.min(num_vars.1));
+                if var < 2 * common {
+                    let side = if var.is_multiple_of(2) { Side::Source } else { Side::Target };
+                    (side, (var / 2) as u32)
+                } else if num_vars.0 > num_vars.1 {
+                    (Side::Source, (var - common) as u32)
+                } else {
+                    (Side::Target, (var - common) as u32)
+                }
+            }
         }
     }
+}
+
+thread_local! {
+    static DEFAULT_ENCODING: Cell<Encoding> = const { Cell::new(Encoding::Concatenated) };
+}
+
+/// The encoding of relations that are created on this thread.
+pub fn default_encoding() -> Encoding {
+    DEFAULT_ENCODING.get()
+}
+
+pub fn set_default_encoding(encoding: Encoding) {
+    DEFAULT_ENCODING.set(encoding);
+}
+
+thread_local! {
+    static MAX_ITERATIONS: Cell<usize> = const { Cell::new(1_000) };
+}
+
+/// The number of iterations after which loops and fixpoints on this thread fail with
+/// [`eval::Error::IterationLimitExceeded`].
+pub fn max_iterations() -> usize {
+    MAX_ITERATIONS.get()
+}
+
+pub fn set_max_iterations(limit: usize) {
+    MAX_ITERATIONS.set(limit);
+}
+
+thread_local! {
+    static MAX_CALL_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_CALL_DEPTH) };
+}
+
+/// Each call takes several kilobytes of stack in debug builds, which leaves plenty of room in the
+/// 8 MiB stack of the main thread.
+const DEFAULT_MAX_CALL_DEPTH: usize = 100;
+
+/// The number of nested calls of functions and procedures after which evaluation on this thread
+/// fails with [`eval::Error::StackOverflow`].
+pub fn max_call_depth() -> usize {
+    MAX_CALL_DEPTH.get()
+}
+
+pub fn set_max_call_depth(limit: usize) {
+    MAX_CALL_DEPTH.set(limit);
+}
+
+thread_local! {
+    static TIMEOUT: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// When the running evaluation on this thread times out.
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// The time after which evaluations on this thread fail with [`eval::Error::Timeout`].
+pub fn timeout() -> Option<Duration> {
+    TIMEOUT.get()
+}
+
+pub fn set_timeout(timeout: Option<Duration>) {
+    TIMEOUT.set(timeout);
+}
 
-    ChainExact {
-        i: i.into_iter(),
-        j: j.into_iter(),
+/// Sets when the running evaluation times out, and returns the previous deadline.
+fn replace_deadline(deadline: Option<Instant>) -> Option<Instant> {
+    DEADLINE.replace(deadline)
+}
+
+fn is_past_deadline() -> bool {
+    DEADLINE.get().is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// The values of the variables for a pair of elements.
+fn bits2(domain: &(Domain, Domain), encoding: Encoding, pair: (Element, Element)) -> Vec<bool> {
+    let num_vars = (num_vars(&domain.0), num_vars(&domain.1));
+    let mut vars = vec![false; (num_vars.0 + num_vars.1) as usize];
+    for (i, bit) in (0..).zip(bits(&domain.0, pair.0)) {
+        vars[encoding.var(num_vars, Side::Source, i) as usize] = bit;
     }
+    for (i, bit) in (0..).zip(bits(&domain.1, pair.1)) {
+        vars[encoding.var(num_vars, Side::Target, i) as usize] = bit;
+    }
+    vars
 }
 
 fn dd_manager() -> &'static dd::Manager {
     static MANAGER: OnceLock<dd::Manager> = OnceLock::new();
 
-    MANAGER.get_or_init(dd::Manager::new)
+    MANAGER.get_or_init(|| {
+        let manager = dd::Manager::new();
+        manager.set_interrupt_flag(Some(Arc::clone(interrupt_flag())));
+        manager
+    })
+}
+
+/// Returns the flag that aborts the running evaluation with [`eval::Error::Interrupted`], which
+/// the REPL sets on Ctrl-C. It stays set until it is cleared with [`clear_interrupt`].
+pub fn interrupt_flag() -> &'static Arc<AtomicBool> {
+    static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    FLAG.get_or_init(Arc::default)
+}
+
+pub fn is_interrupted() -> bool {
+    interrupt_flag().load(Ordering::Relaxed)
+}
+
+pub fn clear_interrupt() {
+    interrupt_flag().store(false, Ordering::Relaxed);
+}
+
+/// Returns whether the running evaluation was interrupted or timed out, so that long operations
+/// can stop early.
+fn should_stop() -> bool {
+    is_interrupted() || is_past_deadline()
+}
+
+/// Limits the number of decision diagram nodes, so that evaluating an expression that needs more
+/// fails with [`eval::Error::NodeLimitExceeded`] instead of running out of memory.
+pub fn set_node_limit(limit: Option<usize>) {
+    dd_manager().set_node_limit(limit);
 }
 
 pub fn load_file(filename: &str, globals: &mut Globals) -> Result<(), Error> {
@@ -154,14 +374,114 @@ pub fn load_matrix(variable: &str, filename: &str, locals: &mut Locals) -> Resul
     Ok(())
 }
 
+pub fn load_edge_list(variable: &str, filename: &str, locals: &mut Locals) -> Result<(), Error> {
+    let src = fs::read_to_string(filename)?;
+    let relation = parse_edge_list(filename, &src, edge_list_separator(filename))?;
+    locals.assign(variable, relation);
+    Ok(())
+}
+
+/// Attaches labels read from a file, one per line, to the domains of a relation that they fit.
+pub fn load_labels(variable: &str, filename: &str, locals: &mut Locals) -> Result<(), Error> {
+    let src = fs::read_to_string(filename)?;
+    let labels = Arc::new(parse_labels(filename, &src)?);
+    let relation = locals.get(variable)?;
+
+    let (x_domain, y_domain) = relation.domain();
+    let fit = |domain: &Domain| (domain.end == labels.len()).then(|| Arc::clone(&labels));
+    let (x_labels, y_labels) = (fit(&x_domain), fit(&y_domain));
+    if x_labels.is_none() && y_labels.is_none() {
+        return Err(Error::LabelCount {
+            count: labels.len(),
+            domain: (x_domain, y_domain),
+        });
+    }
+
+    let x_labels = x_labels.or_else(|| x_domain.labels.clone());
+    let y_labels = y_labels.or_else(|| y_domain.labels.clone());
+    locals.assign(variable, relation.with_labels((x_labels, y_labels)));
+    Ok(())
+}
+
 pub fn save_relation(locals: &Locals, name: &str, filename: &str) -> Result<(), Error> {
     let relation = locals.get(name)?;
-    fs::write(filename, relation.display(name).to_string())?;
+    fs::write(filename, relation.display(name).without_labels().to_string())?;
     Ok(())
 }
 
 pub fn save_matrix(locals: &Locals, name: &str, filename: &str) -> Result<(), Error> {
     let relation = locals.get(name)?;
-    fs::write(filename, relation.display_matrix().to_string())?;
+    fs::write(filename, relation.display_matrix().without_labels().to_string())?;
+    Ok(())
+}
+
+pub fn save_edge_list(locals: &Locals, name: &str, filename: &str) -> Result<(), Error> {
+    let relation = locals.get(name)?;
+    let domain = relation.domain();
+    ensure!(
+        domain.0.is_compatible(&domain.1),
+        HeterogeneousSnafu { domain }
+    );
+    let separator = edge_list_separator(filename);
+    fs::write(filename, relation.display_edge_list(separator).to_string())?;
+    Ok(())
+}
+
+pub fn load_bdd(variable: &str, filename: &str, locals: &mut Locals) -> Result<(), Error> {
+    let bytes = fs::read(filename)?;
+    locals.assign(variable, Relation::from_bytes(&bytes)?);
+    Ok(())
+}
+
+pub fn save_bdd(locals: &Locals, name: &str, filename: &str) -> Result<(), Error> {
+    let relation = locals.get(name)?;
+    fs::write(filename, relation.to_bytes())?;
+    Ok(())
+}
+
+/// Files ending in `.tsv` are tab-separated, everything else is comma-separated.
+fn edge_list_separator(filename: &str) -> char {
+    if filename.ends_with(".tsv") { '\t' } else { ',' }
+}
+
+pub fn load_workspace(
+    filename: &str,
+    globals: &mut Globals,
+    locals: &mut Locals,
+) -> Result<(), Error> {
+    let src = fs::read_to_string(filename)?;
+    let workspace = parse_workspace(filename, &src)?;
+    for (name, relation) in workspace.relations {
+        locals.assign(&name, relation);
+    }
+    globals.extend(workspace.items);
+    Ok(())
+}
+
+pub fn save_workspace(globals: &Globals, locals: &Locals, filename: &str) -> Result<(), Error> {
+    fs::write(filename, display_workspace(globals, locals).to_string())?;
+    Ok(())
+}
+
+/// Replaces the REPL state with a session saved by [`save_session`].
+pub fn load_session(filename: &str, state: &mut State) -> Result<(), Error> {
+    let src = fs::read_to_string(filename)?;
+    let workspace = parse_workspace(filename, &src)?;
+
+    let mut session = State::default();
+    for (name, relation) in workspace.relations {
+        session.locals.assign(&name, relation);
+    }
+    session.globals.extend(workspace.items);
+    session.last_result = workspace.last_result;
+    *state = session;
+    Ok(())
+}
+
+/// Saves the variables, user-defined functions and last result of the REPL.
+pub fn save_session(state: &State, filename: &str) -> Result<(), Error> {
+    let workspace =
+        display_workspace(&state.globals, &state.locals).as_session(state.last_result.as_ref());
+    fs::write(filename, workspace.to_string())?;
     Ok(())
 }
@@ -1,9 +1,15 @@
-use std::{cell::RefCell, io, ops, rc::Rc};
+use std::{cell::RefCell, io, ops, rc::Rc, sync::Arc};
 
-use rel_lang::{repl::Repl, repl_helper::ReplHelper};
+use rel_lang::{clear_interrupt, interrupt_flag, repl::Repl, repl_helper::ReplHelper};
 use rustyline::{Editor, Result, error::ReadlineError};
+use signal_hook::{consts::SIGINT, flag};
 
 fn main() -> Result<()> {
+    // Ctrl-C at the prompt is handled by rustyline. During an evaluation it aborts the evaluation
+    // instead of the process, unless it is pressed again before the evaluation notices.
+    flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(interrupt_flag()))?;
+    flag::register(SIGINT, Arc::clone(interrupt_flag()))?;
+
     let mut rl = Editor::new()?;
     let mut stdout = io::stdout();
 
@@ -23,6 +29,7 @@ fn main() -> Result<()> {
             Err(err) => return Err(err),
         };
 
+        clear_interrupt();
         let res = repl.borrow_mut().process_input(&line, &mut stdout)?;
 
         match res {
@@ -0,0 +1,201 @@
+//! Memoization of calls to user-defined functions and procedures.
+//!
+//! Functions and procedures have no side effects, since their locals are fresh for every call, so
+//! their results only depend on their arguments. Arguments are identified by their decision diagram
+//! nodes, which are canonical, so equal relations share an entry.
+//!
+//! The entries keep their relations alive, so their nodes count toward the node limit. The memo
+//! holds at most [`DEFAULT_CAPACITY`] entries by default and evicts the least recently used ones.
+
+use std::collections::BTreeMap;
+
+use crate::{dd, relation::Relation};
+
+/// The number of entries that are kept by default.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+type Key = (String, Vec<dd::NodeId>);
+
+#[derive(Debug)]
+pub struct Memo {
+    enabled: bool,
+    capacity: usize,
+    /// The calls by the name of the function and the nodes of the arguments. Relations with the
+    /// same node can still differ in their domains or encodings, so each entry keeps its arguments,
+    /// which also keeps the nodes alive.
+    entries: BTreeMap<Key, Vec<Entry>>,
+    /// The keys of the entries by the time they were last used, oldest first.
+    recent: BTreeMap<u64, Key>,
+    clock: u64,
+    stats: MemoStats,
+}
+
+#[derive(Debug)]
+struct Entry {
+    args: Vec<Relation>,
+    result: Relation,
+    last_used: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub entries: usize,
+}
+
+impl Default for Memo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: DEFAULT_CAPACITY,
+            entries: BTreeMap::new(),
+            recent: BTreeMap::new(),
+            clock: 0,
+            stats: MemoStats::default(),
+        }
+    }
+}
+
+impl Memo {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables memoization. Disabling it also drops all entries.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// Drops all entries, which is needed whenever a function is redefined. Evaluations also drop
+    /// them when they exceed the node limit.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recent.clear();
+        self.stats.entries = 0;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the maximum number of entries, evicting the least recently used ones beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        self.stats
+    }
+
+    /// Returns the result of an earlier call with the same arguments.
+    pub(crate) fn get(&mut self, name: &str, args: &[Relation]) -> Option<Relation> {
+        if !self.enabled {
+            return None;
+        }
+
+        let key = key(name, args);
+        let clock = self.tick();
+        let entry = self.entries.get_mut(&key).and_then(|entries| {
+            entries
+                .iter_mut()
+                .find(|entry| same_args(&entry.args, args))
+        });
+        let Some(entry) = entry else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.recent.remove(&entry.last_used);
+        entry.last_used = clock;
+        self.recent.insert(clock, key);
+        Some(entry.result.clone())
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, args: Vec<Relation>, result: Relation) {
+        if !self.enabled || self.capacity == 0 {
+            return;
+        }
+
+        let key = key(name, &args);
+        let clock = self.tick();
+        let entries = self.entries.entry(key.clone()).or_default();
+        if !entries.iter().any(|entry| same_args(&entry.args, &args)) {
+            entries.push(Entry {
+                args,
+                result,
+                last_used: clock,
+            });
+            self.recent.insert(clock, key);
+            self.stats.entries += 1;
+            self.evict();
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Drops the least recently used entries until there are at most `capacity` of them.
+    fn evict(&mut self) {
+        while self.stats.entries > self.capacity {
+            let (last_used, key) = self.recent.pop_first().expect("every entry is recent");
+            let entries = self.entries.get_mut(&key).expect("recent entries exist");
+            entries.retain(|entry| entry.last_used != last_used);
+            if entries.is_empty() {
+                self.entries.remove(&key);
+            }
+            self.stats.entries -= 1;
+        }
+    }
+}
+
+fn key(name: &str, args: &[Relation]) -> Key {
+    (
+        name.to_owned(),
+        args.iter().map(Relation::node_id).collect(),
+    )
+}
+
+/// Returns whether the arguments have the same domains and encodings, given that they have the
+/// same nodes.
+fn same_args(lhs: &[Relation], rhs: &[Relation]) -> bool {
+    lhs.iter()
+        .zip(rhs)
+        .all(|(lhs, rhs)| lhs.domain() == rhs.domain() && lhs.encoding() == rhs.encoding())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{memo::Memo, relation::Relation};
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let relations = (1..=3)
+            .map(|n| Relation::sparse((..n, ..n), [(0, 0)]))
+            .collect::<Vec<_>>();
+        let mut memo = Memo::default();
+        memo.set_enabled(true);
+        memo.set_capacity(2);
+
+        memo.insert("F", vec![relations[0].clone()], relations[0].clone());
+        memo.insert("F", vec![relations[1].clone()], relations[1].clone());
+        // Using the first entry makes the second one the least recently used.
+        assert!(memo.get("F", &relations[..1]).is_some());
+        memo.insert("F", vec![relations[2].clone()], relations[2].clone());
+        assert_eq!(memo.stats().entries, 2);
+        assert!(memo.get("F", &relations[1..2]).is_none());
+        assert!(memo.get("F", &relations[..1]).is_some());
+        assert!(memo.get("F", &relations[2..]).is_some());
+
+        memo.set_capacity(1);
+        assert_eq!(memo.stats().entries, 1);
+        assert!(memo.get("F", &relations[2..]).is_some());
+        assert!(memo.get("F", &relations[..1]).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use chumsky::prelude::*;
+
+use crate::{
+    Domain,
+    labels::Labels,
+    parser::{Error, Span, handle_errors},
+    relation::Relation,
+};
+
+/// Parses an edge list with one `source<separator>target` record per line.
+///
+/// Elements are numbered in order of their first appearance, and the resulting homogeneous
+/// relation carries the same label table on both sides. A record with a single field declares an
+/// element without any edges.
+pub fn parse_edge_list(filename: &str, src: &str, separator: char) -> Result<Relation, Error> {
+    match edge_list(separator).parse(src).into_result() {
+        Ok(r) => Ok(r),
+        Err(errs) => {
+            handle_errors(
+                filename,
+                src,
+                errs.into_iter().map(|e| e.map_token(|c| c.to_string())),
+            );
+            Err(Error)
+        }
+    }
+}
+
+fn edge_list<'src>(
+    separator: char,
+) -> impl Parser<'src, &'src str, Relation, extra::Err<Rich<'src, char, Span>>> {
+    let quoted = none_of('"')
+        .or(just("\"\"").to('"'))
+        .repeated()
+        .collect::<String>()
+        .delimited_by(just('"'), just('"'));
+    let unquoted = any()
+        .filter(move |&c| c != separator && !matches!(c, '"' | '\r' | '\n'))
+        .repeated()
+        .to_slice()
+        .map(|s: &str| s.trim().to_owned());
+    let field = quoted.or(unquoted);
+
+    let newline = just('\r').or_not().then(just('\n'));
+    let record = field
+        .separated_by(just(separator))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .try_map(|fields, span| match fields.len() {
+            1 | 2 => Ok(fields),
+            n => Err(Rich::custom(
+                span,
+                format!("expected a source and a target, got {n} fields"),
+            )),
+        });
+
+    record
+        .separated_by(newline)
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .map(|records| {
+            let mut labels = Labels::new();
+            let mut pairs = Vec::new();
+            for fields in records {
+                match fields.as_slice() {
+                    [name] if name.is_empty() => {
+                        // Blank line
+                    }
+                    [name] => {
+                        labels.get_or_insert(name);
+                    }
+                    [source, target] => {
+                        let x = labels.get_or_insert(source);
+                        let y = labels.get_or_insert(target);
+                        pairs.push((x, y));
+                    }
+                    _ => unreachable!("records have been validated"),
+                }
+            }
+
+            let domain = Domain::labelled(Arc::new(labels));
+            Relation::sparse((domain.clone(), domain), pairs)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::csv::parse_edge_list, relation::Relation};
+
+    #[test]
+    fn test_parse_edges_csv() {
+        let relation =
+            parse_edge_list("Edges.csv", include_str!("../../examples/Edges.csv"), ',').unwrap();
+
+        let expected = Relation::sparse((..5, ..5), [(0, 1), (1, 2), (2, 3), (3, 1), (3, 4)]);
+        assert_eq!(relation, expected);
+
+        let labels = relation.labels().0.unwrap();
+        let names = labels.iter().collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Auckland",
+                "Wellington",
+                "Christchurch",
+                "Dunedin",
+                "Queenstown, NZ"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_tsv() {
+        let relation = parse_edge_list("<test>", "a\tb\r\nb\ta\r\nc\n", '\t').unwrap();
+
+        let expected = Relation::sparse((..3, ..3), [(0, 1), (1, 0)]);
+        assert_eq!(relation, expected);
+    }
+
+    #[test]
+    fn test_parse_too_many_fields() {
+        assert!(parse_edge_list("<test>", "a,b,c\n", ',').is_err());
+    }
+}
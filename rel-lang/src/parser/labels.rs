@@ -0,0 +1,64 @@
+use chumsky::prelude::*;
+
+use crate::{
+    labels::Labels,
+    parser::{Error, Span, handle_errors},
+};
+
+/// Parses a list of element names, one per line.
+pub fn parse_labels(filename: &str, src: &str) -> Result<Labels, Error> {
+    match labels().parse(src).into_result() {
+        Ok(labels) => Ok(labels),
+        Err(errs) => {
+            handle_errors(
+                filename,
+                src,
+                errs.into_iter().map(|e| e.map_token(|c| c.to_string())),
+            );
+            Err(Error)
+        }
+    }
+}
+
+fn labels<'src>() -> impl Parser<'src, &'src str, Labels, extra::Err<Rich<'src, char, Span>>> {
+    let newline = just('\r').or_not().then(just('\n'));
+    let name = none_of("\r\n")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .map(str::trim)
+        .map_with(|name, e| (name, e.span()));
+
+    name.separated_by(newline.repeated().at_least(1))
+        .allow_leading()
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .validate(|names, _, emitter| {
+            let mut labels = Labels::new();
+            for (name, span) in names {
+                if labels.element(name).is_some() {
+                    emitter.emit(Rich::custom(span, format!("duplicate label '{name}'")));
+                }
+                labels.get_or_insert(name);
+            }
+            labels
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::labels::parse_labels;
+
+    #[test]
+    fn test_parse_labels() {
+        let labels = parse_labels("<test>", "a\nb c\r\n\nd\n").unwrap();
+
+        let names = labels.iter().collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b c", "d"]);
+    }
+
+    #[test]
+    fn test_parse_duplicate_labels() {
+        assert!(parse_labels("<test>", "a\nb\na\n").is_err());
+    }
+}
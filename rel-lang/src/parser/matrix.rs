@@ -35,10 +35,10 @@ fn matrix<'src>() -> impl Parser<'src, &'src str, Relation, extra::Err<Rich<'src
                 .collect::<Vec<_>>(),
         )
         .then_ignore(header)
-        .map(|(width, lines)| {
+        .try_map(|(width, lines), span| {
             
-            Relation::sparse(
-                (..width as u32, ..lines.len() as u32),
+            Relation::try_sparse(
+                (..lines.len() as u32, ..width as u32),
                 lines.into_iter().enumerate().flat_map(|(i, cells)| {
                     cells
                         .into_iter()
@@ -47,6 +47,7 @@ fn matrix<'src>() -> impl Parser<'src, &'src str, Relation, extra::Err<Rich<'src
                         .map(move |(j, _)| (i as u32, j as u32))
                 }),
             )
+            .map_err(|e| Rich::custom(span, e))
         })
 }
 
@@ -54,6 +55,14 @@ fn matrix<'src>() -> impl Parser<'src, &'src str, Relation, extra::Err<Rich<'src
 mod tests {
     use crate::{parser::matrix::parse_matrix, relation::Relation};
 
+    #[test]
+    fn test_parse_non_square_matrix() {
+        let relation = parse_matrix("R.matrix", "+---+\n|  X|\n|X  |\n+---+\n").unwrap();
+        assert_eq!(relation, Relation::sparse((..2, ..3), [(0, 2), (1, 0)]));
+
+        assert!(parse_matrix("R.matrix", "+--+\n|  X|\n|X |\n+--+\n").is_err());
+    }
+
     #[test]
     fn test_parse_r1_matrix() {
         let relation = parse_matrix("R1.matrix", include_str!("../../examples/R1.matrix")).unwrap();
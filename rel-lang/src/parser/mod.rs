@@ -1,3 +1,5 @@
+use std::{rc::Rc, sync::Arc};
+
 use ariadne::{Color, Label, Report, ReportKind, sources};
 use chumsky::{input::ValueInput, prelude::*};
 use snafu::Snafu;
@@ -7,9 +9,12 @@ use crate::{
     parser::lexer::{Token, lexer},
 };
 
+pub mod csv;
+pub mod labels;
 mod lexer;
 pub mod matrix;
 pub mod relation;
+pub mod xml;
 
 #[derive(Debug, Clone, Snafu)]
 #[snafu(display("Error while parsing"))]
@@ -18,14 +23,45 @@ pub struct Error;
 type Span = SimpleSpan;
 type Spanned<T> = (T, Span);
 
+/// The byte offsets at which the lines of a source start.
+type LineStarts = Rc<[usize]>;
+
 pub fn parse_program(filename: &str, src: &str) -> Result<ast::Program, Error> {
-    parse_inner(filename, src, |tokens| {
-        program().parse(tokens.map((src.len()..src.len()).into(), |(t, s)| (t, s)))
+    parse_program_in(filename, src, src)
+}
+
+/// Parses a program that is embedded in a larger file, such as the text of an XML element, which
+/// starts at byte `offset` of `file`. Errors and the lines of statements refer to the whole file.
+pub(crate) fn parse_embedded_program(
+    filename: &str,
+    file: &str,
+    offset: usize,
+    src: &str,
+) -> Result<ast::Program, Error> {
+    // Blanking out the file before the program keeps its byte offsets and lines.
+    let mut padded = file.as_bytes()[..offset]
+        .iter()
+        .map(|&b| if b == b'\n' { '\n' } else { ' ' })
+        .collect::<String>();
+    padded.push_str(src);
+    parse_program_in(filename, &padded, file)
+}
+
+/// Parses `src`, reporting errors against `report_src`, which has the same byte offsets.
+fn parse_program_in(filename: &str, src: &str, report_src: &str) -> Result<ast::Program, Error> {
+    let line_starts: LineStarts = std::iter::once(0)
+        .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let shared_src: Rc<str> = src.into();
+    let file: Arc<str> = filename.into();
+    parse_inner(filename, src, report_src, |tokens| {
+        program(shared_src.clone(), file.clone(), line_starts.clone())
+            .parse(tokens.map((src.len()..src.len()).into(), |(t, s)| (t, s)))
     })
 }
 
 pub fn parse_expr(src: &str) -> Result<ast::Expr, Error> {
-    parse_inner("<expr>", src, |tokens| {
+    parse_inner("<expr>", src, src, |tokens| {
         expr().parse(tokens.map((src.len()..src.len()).into(), |(t, s)| (t, s)))
     })
 }
@@ -33,6 +69,7 @@ pub fn parse_expr(src: &str) -> Result<ast::Expr, Error> {
 fn parse_inner<T>(
     filename: &str,
     src: &str,
+    report_src: &str,
     parser: impl for<'tokens, 'src> Fn(
         &'tokens [Spanned<Token<'src>>],
     ) -> ParseResult<T, Rich<'tokens, Token<'src>>>,
@@ -53,7 +90,7 @@ fn parse_inner<T>(
 
     handle_errors(
         filename,
-        src,
+        report_src,
         errs.into_iter()
             .map(|e| e.map_token(|c| c.to_string()))
             .chain(
@@ -93,8 +130,13 @@ fn handle_errors<'src>(
     }
 }
 
-fn program<'tokens, 'src: 'tokens, I>()
--> impl Parser<'tokens, I, ast::Program, extra::Err<Rich<'tokens, Token<'src>, Span>>> + Clone
+/// Parses the items of a program. The source is shared with the parser, which keeps the text of
+/// each item.
+fn program<'tokens, 'src: 'tokens, I>(
+    src: Rc<str>,
+    file: Arc<str>,
+    line_starts: LineStarts,
+) -> impl Parser<'tokens, I, ast::Program, extra::Err<Rich<'tokens, Token<'src>, Span>>> + Clone
 where
     I: ValueInput<'tokens, Token = Token<'src>, Span = Span>,
 {
@@ -108,7 +150,7 @@ where
     let decls = just(Token::Decl).ignore_then(ident.separated_by(just(Token::Ctrl(','))).collect());
 
     let body = just(Token::Beg)
-        .ignore_then(stmt().repeated().collect())
+        .ignore_then(stmt(file, line_starts).repeated().collect())
         .then_ignore(just(Token::End));
 
     let procedure = ident
@@ -116,11 +158,18 @@ where
         .then(decls)
         .then(body)
         .then_ignore(just(Token::Ctrl('.')))
-        .map(|(((name, params), decls), body)| ast::Item::Procedure {
-            name,
-            params,
-            decls,
-            body,
+        .map_with({
+            let src = src.clone();
+            move |(((name, params), decls), body), e| {
+                let span: Span = e.span();
+                ast::Item::Procedure {
+                    name,
+                    params,
+                    decls,
+                    body,
+                    source: src[span.into_range()].to_owned(),
+                }
+            }
         });
 
     let function = ident
@@ -128,10 +177,14 @@ where
         .then_ignore(just(Token::Op('=')))
         .then(expr())
         .then_ignore(just(Token::Ctrl('.')))
-        .map(|((name, params), value)| ast::Item::Function {
-            name,
-            params,
-            value,
+        .map_with(move |((name, params), value), e| {
+            let span: Span = e.span();
+            ast::Item::Function {
+                name,
+                params,
+                value,
+                source: src[span.into_range()].to_owned(),
+            }
         });
 
     let item = procedure.or(function);
@@ -141,8 +194,10 @@ where
         .map(|items| ast::Program { items })
 }
 
-fn stmt<'tokens, 'src: 'tokens, I>()
--> impl Parser<'tokens, I, ast::Stmt, extra::Err<Rich<'tokens, Token<'src>, Span>>> + Clone
+fn stmt<'tokens, 'src: 'tokens, I>(
+    file: Arc<str>,
+    line_starts: LineStarts,
+) -> impl Parser<'tokens, I, ast::Stmt, extra::Err<Rich<'tokens, Token<'src>, Span>>> + Clone
 where
     I: ValueInput<'tokens, Token = Token<'src>, Span = Span>,
 {
@@ -152,18 +207,18 @@ where
         let assign = ident
             .then_ignore(just(Token::Op('=')))
             .then(expr())
-            .map(|(lhs, rhs)| ast::Stmt::Assign { lhs, rhs });
+            .map(|(lhs, rhs)| ast::StmtKind::Assign { lhs, rhs });
 
         let while_stmt = just::<_, I, _>(Token::While)
             .ignore_then(expr())
             .then_ignore(just(Token::Do))
             .then(stmt.clone().repeated().collect())
             .then_ignore(just(Token::Od))
-            .map(|(cond, body)| ast::Stmt::While { cond, body });
+            .map(|(cond, body)| ast::StmtKind::While { cond, body });
 
         let return_stmt = just(Token::Return)
             .ignore_then(expr())
-            .map(|value| ast::Stmt::Return { value });
+            .map(|value| ast::StmtKind::Return { value });
 
         let if_stmt = just(Token::If)
             .ignore_then(expr())
@@ -175,13 +230,26 @@ where
                     .or_not(),
             )
             .then_ignore(just(Token::Fi))
-            .map(|((cond, then_body), else_body)| ast::Stmt::If {
+            .map(|((cond, then_body), else_body)| ast::StmtKind::If {
                 cond,
                 then_body,
                 else_body,
             });
 
-        assign.or(while_stmt).or(return_stmt).or(if_stmt)
+        assign
+            .or(while_stmt)
+            .or(return_stmt)
+            .or(if_stmt)
+            .map_with(move |kind, e| {
+                let start = e.span().start;
+                // The number of lines that start at or before the statement.
+                let line = line_starts.partition_point(|&line_start| line_start <= start);
+                ast::Stmt {
+                    kind,
+                    file: file.clone(),
+                    line,
+                }
+            })
     })
 }
 
@@ -261,7 +329,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::parser::parse_program;
+    use crate::{ast, parser::parse_program};
 
     #[test]
     fn test_parse_examples() {
@@ -273,4 +341,25 @@ mod tests {
 
         assert_eq!(ast.items.len(), 19);
     }
+
+    #[test]
+    fn test_parse_lines() {
+        let ast = parse_program(
+            "Recursion.prog",
+            include_str!("../../examples/Recursion.prog"),
+        )
+        .unwrap();
+
+        let ast::Item::Procedure { body, .. } = &ast.items[0] else {
+            panic!("Reach is a procedure");
+        };
+        assert_eq!(
+            body.iter().map(|stmt| stmt.line).collect::<Vec<_>>(),
+            [5, 6]
+        );
+        let ast::StmtKind::If { else_body, .. } = &body[1].kind else {
+            panic!("the second statement is a branch");
+        };
+        assert_eq!(else_body.as_ref().unwrap()[0].line, 9);
+    }
 }
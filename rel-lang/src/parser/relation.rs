@@ -3,7 +3,7 @@ use chumsky::prelude::*;
 use crate::{
     Domain,
     parser::{Error, Span, handle_errors},
-    relation::Relation,
+    relation::{self, Relation},
 };
 
 pub fn parse_relation(filename: &str, src: &str) -> Result<(String, Relation), Error> {
@@ -28,29 +28,57 @@ fn header<'src>()
         .then_ignore(just(", "))
         .then(u32_digits())
         .then_ignore(just(")\n"))
-        .map(|((name, x), y): ((&str, _), _)| (name.to_owned(), (..x, ..y)))
+        .map(|((name, x), y): ((&str, _), _)| (name.to_owned(), (Domain::new(x), Domain::new(y))))
 }
 
 fn relation<'src>()
 -> impl Parser<'src, &'src str, (String, Relation), extra::Err<Rich<'src, char, Span>>> {
-    let line = u32_digits()
+    let line = element()
         .then_ignore(just(" : "))
-        .then(u32_digits().separated_by(just(", ")).collect::<Vec<_>>())
-        .then_ignore(just('\n'));
+        .then(element().separated_by(just(", ")).collect::<Vec<_>>())
+        .then_ignore(just('\n'))
+        .map_with(|line, e| (line, e.span()));
 
     header()
         .then(line.repeated().collect::<Vec<_>>())
-        .map(|((name, domain), lines)| {
-            let relation = Relation::sparse(
-                domain,
-                lines
-                    .into_iter()
-                    .flat_map(|(x, ys)| ys.into_iter().map(move |y| (x - 1, y - 1))),
-            );
-            (name, relation)
+        .try_map(|((name, domain), lines), span| {
+            let pairs = lines
+                .iter()
+                .flat_map(|((x, ys), _)| ys.iter().map(move |&y| (*x, y)));
+            match Relation::try_sparse(domain, pairs) {
+                Ok(relation) => Ok((name, relation)),
+                Err(relation::Error::NotInDomain {
+                    pair: (x, y),
+                    domain,
+                }) => {
+                    let (_, span) = lines
+                        .iter()
+                        .find(|((line_x, ys), _)| *line_x == x && ys.contains(&y))
+                        .expect("the pair is on a line");
+                    Err(Rich::custom(
+                        *span,
+                        format!(
+                            "entry {} : {} is outside of the relation ({}, {})",
+                            x + 1,
+                            y + 1,
+                            domain.0.end(),
+                            domain.1.end()
+                        ),
+                    ))
+                }
+                Err(e) => Err(Rich::custom(span, e)),
+            }
         })
 }
 
+/// Reads a 1-based element and converts it to a 0-based one.
+fn element<'src>() -> impl Parser<'src, &'src str, u32, extra::Err<Rich<'src, char, Span>>> {
+    u32_digits().try_map(|x, span| {
+        x.checked_sub(1)
+            .ok_or_else(|| Rich::custom(span, "elements are numbered from 1"))
+    })
+}
+
 fn u32_digits<'src>() -> impl Parser<'src, &'src str, u32, extra::Err<Rich<'src, char, Span>>> {
     text::digits(10)
         .to_slice()
@@ -61,6 +89,14 @@ fn u32_digits<'src>() -> impl Parser<'src, &'src str, u32, extra::Err<Rich<'src,
 mod tests {
     use crate::{parser::relation::parse_relation, relation::Relation};
 
+    #[test]
+    fn test_parse_out_of_domain() {
+        assert!(parse_relation("R.ascii", "R (2, 2)\n1 : 1, 3\n").is_err());
+        assert!(parse_relation("R.ascii", "R (2, 2)\n3 : 1\n").is_err());
+        assert!(parse_relation("R.ascii", "R (2, 2)\n0 : 1\n").is_err());
+        assert!(parse_relation("R.ascii", "R (2, 2)\n2 : 1, 2\n").is_ok());
+    }
+
     #[test]
     fn test_parse_r1() {
         let (name, relation) =
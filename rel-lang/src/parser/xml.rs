@@ -0,0 +1,475 @@
+//! Reader for rel-lang's own XML workspace files, which hold relations (with their dimensions,
+//! element labels and encodings), functions and programs, and the last result of a REPL session.
+//! The format is not RelView's, whose files cannot be read. Unknown elements are skipped.
+//!
+//! Sessions store relations as the base64 of their decision diagrams in a `<bdd>` element, see
+//! [`Relation::to_bytes`], instead of one element per pair.
+//!
+//! ```xml
+//! <relview>
+//!   <relation name="R" rows="2" cols="3" encoding="interleaved">
+//!     <rowlabels><label>a</label><label>b</label></rowlabels>
+//!     <row nr="1"><col nr="2"/><col nr="3"/></row>
+//!   </relation>
+//!   <result rows="1" cols="1"/>
+//!   <function name="Sym">Sym(R) = R | R^.</function>
+//!   <program name="Id">Id(R) DECL BEG RETURN R END.</program>
+//! </relview>
+//! ```
+
+use std::sync::Arc;
+
+use chumsky::prelude::*;
+
+use crate::{
+    Domain, Element, Encoding, ast, base64,
+    labels::Labels,
+    parser::{Error, Span, handle_errors, parse_embedded_program},
+    relation::Relation,
+};
+
+/// The contents of a workspace file.
+#[derive(Debug, Default)]
+pub struct Workspace {
+    pub relations: Vec<(String, Relation)>,
+    pub items: Vec<ast::Item>,
+    pub last_result: Option<Relation>,
+}
+
+#[derive(Debug)]
+struct XmlElement {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<XmlContent>,
+    span: Span,
+}
+
+#[derive(Debug)]
+enum XmlContent {
+    Element(XmlElement),
+    /// Unescaped text or the contents of a CDATA section, with the span of the raw text.
+    Text(String, Span),
+}
+
+pub fn parse_workspace(filename: &str, src: &str) -> Result<Workspace, Error> {
+    let root = match document().parse(src).into_result() {
+        Ok(root) => root,
+        Err(errs) => {
+            handle_errors(
+                filename,
+                src,
+                errs.into_iter().map(|e| e.map_token(|c| c.to_string())),
+            );
+            return Err(Error);
+        }
+    };
+
+    match workspace(filename, src, &root) {
+        Ok(workspace) => Ok(workspace),
+        Err(WorkspaceError::Invalid(e)) => {
+            handle_errors(filename, src, [e]);
+            Err(Error)
+        }
+        Err(WorkspaceError::Program(e)) => Err(e),
+    }
+}
+
+enum WorkspaceError<'src> {
+    Invalid(Rich<'src, String>),
+    Program(Error),
+}
+
+impl<'src> From<Rich<'src, String>> for WorkspaceError<'src> {
+    fn from(e: Rich<'src, String>) -> Self {
+        WorkspaceError::Invalid(e)
+    }
+}
+
+fn workspace<'src>(
+    filename: &str,
+    src: &str,
+    root: &XmlElement,
+) -> Result<Workspace, WorkspaceError<'src>> {
+    if root.name != "relview" {
+        return Err(Rich::custom(root.span, "expected a <relview> root element").into());
+    }
+
+    let mut workspace = Workspace::default();
+    for element in root.elements() {
+        match element.name.as_str() {
+            "relation" => {
+                let name = element.attr("name")?.to_owned();
+                let relation = relation(element)?;
+                workspace.relations.push((name, relation));
+            }
+            "result" => {
+                workspace.last_result = Some(relation(element)?);
+            }
+            "function" | "program" => {
+                // Parsed at its position in the file, so that errors and statements have the
+                // line numbers of the file.
+                let offset = element.text_start().unwrap_or(element.span.end);
+                let program = parse_embedded_program(filename, src, offset, &element.text())
+                    .map_err(WorkspaceError::Program)?;
+                workspace.items.extend(program.items);
+            }
+            _ => {
+                // Not part of the workspace state that rel-lang knows about.
+            }
+        }
+    }
+    Ok(workspace)
+}
+
+fn relation<'src>(element: &XmlElement) -> Result<Relation, Rich<'src, String>> {
+    // Sessions store the decision diagram instead of the pairs.
+    if let Some(bdd) = element.elements().find(|e| e.name == "bdd") {
+        let bytes = base64::decode(&bdd.text())
+            .ok_or_else(|| Rich::custom(bdd.span, "<bdd> is not valid base64".to_owned()))?;
+        return Relation::from_bytes(&bytes).map_err(|e| Rich::custom(bdd.span, e.to_string()));
+    }
+
+    let rows = element.number_attr("rows")?;
+    let cols = element.number_attr("cols")?;
+
+    let row_labels = labels(element, "rowlabels", rows)?.map(Arc::new);
+    let col_labels = match labels(element, "collabels", cols)? {
+        // Share the table of homogeneous relations, like the CSV reader does.
+        Some(labels) if row_labels.as_deref() == Some(&labels) => row_labels.clone(),
+        labels => labels.map(Arc::new),
+    };
+    let x_domain = row_labels.map_or(Domain::new(rows), Domain::labelled);
+    let y_domain = col_labels.map_or(Domain::new(cols), Domain::labelled);
+
+    let mut pairs = Vec::new();
+    for row in element.elements().filter(|e| e.name == "row") {
+        let x = row.index_attr("nr", rows)?;
+        for col in row.elements().filter(|e| e.name == "col") {
+            let y = col.index_attr("nr", cols)?;
+            pairs.push((x, y));
+        }
+    }
+    let relation = Relation::try_sparse((x_domain, y_domain), pairs)
+        .map_err(|e| Rich::custom(element.span, e))?;
+    // Relations without an encoding use the default one.
+    match element.attrs.iter().find(|(key, _)| key == "encoding") {
+        Some((_, name)) => match Encoding::from_name(name) {
+            Some(encoding) => Ok(relation.with_encoding(encoding)),
+            None => Err(Rich::custom(
+                element.span,
+                format!("unknown encoding '{name}' of <{}>", element.name),
+            )),
+        },
+        None => Ok(relation),
+    }
+}
+
+fn labels<'src>(
+    element: &XmlElement,
+    name: &str,
+    end: Element,
+) -> Result<Option<Labels>, Rich<'src, String>> {
+    let Some(list) = element.elements().find(|e| e.name == name) else {
+        return Ok(None);
+    };
+
+    let mut labels = Labels::new();
+    for label in list.elements().filter(|e| e.name == "label") {
+        let text = label.text();
+        if labels.element(&text).is_some() {
+            return Err(Rich::custom(
+                label.span,
+                format!("duplicate label '{text}'"),
+            ));
+        }
+        labels.get_or_insert(&text);
+    }
+    if labels.len() != end {
+        return Err(Rich::custom(
+            list.span,
+            format!("<{name}> has {} labels, expected {end}", labels.len()),
+        ));
+    }
+    Ok(Some(labels))
+}
+
+impl XmlElement {
+    fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|child| match child {
+            XmlContent::Element(element) => Some(element),
+            XmlContent::Text(..) => None,
+        })
+    }
+
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|child| match child {
+                XmlContent::Element(_) => None,
+                XmlContent::Text(text, _) => Some(text.as_str()),
+            })
+            .collect()
+    }
+
+    /// Returns the byte offset of the text in the file. Entities are longer than the characters
+    /// they stand for, so offsets within the text are only exact if it has none.
+    fn text_start(&self) -> Option<usize> {
+        self.children.iter().find_map(|child| match child {
+            XmlContent::Element(_) => None,
+            XmlContent::Text(_, span) => Some(span.start),
+        })
+    }
+
+    fn attr<'src>(&self, name: &str) -> Result<&str, Rich<'src, String>> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| {
+                Rich::custom(
+                    self.span,
+                    format!("<{}> is missing the attribute '{name}'", self.name),
+                )
+            })
+    }
+
+    fn number_attr<'src>(&self, name: &str) -> Result<Element, Rich<'src, String>> {
+        let value = self.attr(name)?;
+        value.trim().parse().map_err(|e| {
+            Rich::custom(
+                self.span,
+                format!("attribute '{name}' of <{}> is not a number: {e}", self.name),
+            )
+        })
+    }
+
+    /// Reads a 1-based index attribute and converts it to a 0-based element of `..end`.
+    fn index_attr<'src>(&self, name: &str, end: Element) -> Result<Element, Rich<'src, String>> {
+        let value = self.number_attr(name)?;
+        if value == 0 || value > end {
+            return Err(Rich::custom(
+                self.span,
+                format!(
+                    "attribute '{name}' of <{}> is out of range: {value} is not in 1..={end}",
+                    self.name
+                ),
+            ));
+        }
+        Ok(value - 1)
+    }
+}
+
+fn document<'src>() -> impl Parser<'src, &'src str, XmlElement, extra::Err<Rich<'src, char, Span>>>
+{
+    let comment = just("<!--")
+        .then(any().and_is(just("-->").not()).repeated())
+        .then(just("-->"))
+        .ignored();
+    let misc = comment.or(text::whitespace().at_least(1)).repeated();
+
+    let declaration = just("<?")
+        .then(any().and_is(just("?>").not()).repeated())
+        .then(just("?>"))
+        .ignored();
+    let doctype = just("<!DOCTYPE")
+        .then(none_of(">").repeated())
+        .then(just(">"))
+        .ignored();
+
+    declaration
+        .or_not()
+        .then(misc)
+        .then(doctype.or_not())
+        .then(misc)
+        .ignore_then(element())
+        .then_ignore(misc)
+        .then_ignore(end())
+}
+
+fn element<'src>() -> impl Parser<'src, &'src str, XmlElement, extra::Err<Rich<'src, char, Span>>> {
+    let name = any()
+        .filter(|c: &char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .map(str::to_owned);
+
+    let attr_value = none_of("\"<")
+        .repeated()
+        .to_slice()
+        .delimited_by(just('"'), just('"'))
+        .or(none_of("'<")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('\''), just('\'')))
+        .try_map(|s, span| unescape(s).map_err(|e| Rich::custom(span, e)));
+    let attr = name.then_ignore(just('=').padded()).then(attr_value);
+    let attrs = text::whitespace()
+        .at_least(1)
+        .ignore_then(attr)
+        .repeated()
+        .collect::<Vec<_>>()
+        .then_ignore(text::whitespace());
+
+    recursive(|element| {
+        let comment = just("<!--")
+            .then(any().and_is(just("-->").not()).repeated())
+            .then(just("-->"))
+            .ignored();
+        let cdata = just("<![CDATA[")
+            .ignore_then(
+                any()
+                    .and_is(just("]]>").not())
+                    .repeated()
+                    .to_slice()
+                    .map_with(|s: &str, e| Some(XmlContent::Text(s.to_owned(), e.span()))),
+            )
+            .then_ignore(just("]]>"));
+        let text = none_of("<")
+            .repeated()
+            .at_least(1)
+            .to_slice()
+            .try_map(|s, span| {
+                let text = unescape(s).map_err(|e| Rich::custom(span, e))?;
+                Ok(Some(XmlContent::Text(text, span)))
+            });
+        let content = comment
+            .map(|()| None)
+            .or(cdata)
+            .or(element.map(|e| Some(XmlContent::Element(e))))
+            .or(text)
+            .repeated()
+            .collect::<Vec<_>>()
+            .map(|children| children.into_iter().flatten().collect::<Vec<_>>());
+
+        let empty_tag = just("/>").map(|_| (Vec::new(), None));
+        let close_tag = just("</")
+            .ignore_then(name)
+            .then_ignore(text::whitespace())
+            .then_ignore(just('>'));
+        let full_tag = just('>')
+            .ignore_then(content)
+            .then(close_tag)
+            .map(|(children, close_name)| (children, Some(close_name)));
+
+        just('<')
+            .ignore_then(name)
+            .then(attrs)
+            .then(empty_tag.or(full_tag))
+            .try_map_with(|((name, attrs), (children, close_name)), e| {
+                if let Some(close_name) = close_name
+                    && close_name != name
+                {
+                    return Err(Rich::custom(
+                        e.span(),
+                        format!("<{name}> is closed by </{close_name}>"),
+                    ));
+                }
+                Ok(XmlElement {
+                    name,
+                    attrs,
+                    children,
+                    span: e.span(),
+                })
+            })
+    })
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find(';') else {
+            return Err(format!("unterminated entity in {s:?}"));
+        };
+        let entity = &rest[start + 1..start + len];
+        let c = match entity {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(char::from_u32)
+                    .ok_or_else(|| format!("unknown entity &{entity};"))?
+            }
+        };
+        result.push(c);
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast, parser::xml::parse_workspace, relation::Relation};
+
+    #[test]
+    fn test_parse_examples_xml() {
+        let workspace =
+            parse_workspace("Examples.xml", include_str!("../../examples/Examples.xml")).unwrap();
+
+        let names = workspace
+            .relations
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["R1", "V"]);
+
+        let expected = Relation::sparse((..5, ..5), [(0, 1), (1, 2), (2, 3), (3, 4), (3, 1)]);
+        assert_eq!(workspace.relations[0].1, expected);
+        let expected = Relation::sparse((..5, ..1), [(0, 0), (2, 0)]);
+        assert_eq!(workspace.relations[1].1, expected);
+
+        let items = workspace
+            .items
+            .iter()
+            .map(|item| item.name())
+            .collect::<Vec<_>>();
+        assert_eq!(items, ["Sym", "RTC2"]);
+
+        // The statements have the lines of the file rather than of the <program> element.
+        let ast::Item::Procedure { body, .. } = &workspace.items[1] else {
+            panic!("RTC2 is a procedure");
+        };
+        let lines = body.iter().map(|stmt| stmt.line).collect::<Vec<_>>();
+        assert_eq!(lines, [19, 20, 21, 25]);
+    }
+
+    #[test]
+    fn test_parse_labels_and_result() {
+        let src = r#"<relview>
+  <relation name="R" rows="2" cols="2">
+    <rowlabels><label>a</label><label>b &amp; c</label></rowlabels>
+    <collabels><label>a</label><label>b &amp; c</label></collabels>
+    <row nr="2"><col nr="1"/></row>
+  </relation>
+  <result rows="1" cols="1"><row nr="1"><col nr="1"/></row></result>
+</relview>"#;
+        let workspace = parse_workspace("<test>", src).unwrap();
+
+        let relation = &workspace.relations[0].1;
+        assert_eq!(relation, &Relation::sparse((..2, ..2), [(1, 0)]));
+        let (x_labels, y_labels) = relation.labels();
+        assert_eq!(x_labels.unwrap().name(1), Some("b & c"));
+        assert_eq!(x_labels, y_labels);
+
+        assert_eq!(workspace.last_result, Some(Relation::true_relation()));
+    }
+
+    #[test]
+    fn test_parse_out_of_range() {
+        let src =
+            r#"<relview><relation name="R" rows="2" cols="2"><row nr="3"/></relation></relview>"#;
+        assert!(parse_workspace("<test>", src).is_err());
+    }
+}
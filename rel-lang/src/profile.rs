@@ -0,0 +1,194 @@
+//! Profiling of the time and decision diagram nodes spent in functions, procedures, statements and
+//! operators.
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// The measurements of an evaluation, see [`crate::eval::eval_profiled`].
+#[derive(Debug, Default)]
+pub struct Profile {
+    entries: BTreeMap<Key, Entry>,
+    /// The number of calls of each key that have not returned yet.
+    running: BTreeMap<Key, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Key {
+    pub kind: Kind,
+    /// The name of the function or operator, or the location and first line of the statement.
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Procedure,
+    Function,
+    Builtin,
+    Statement,
+    /// The relation operators, with composition with a vector profiled as `image` or `preimage`.
+    Operator,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub calls: usize,
+    /// The wall time, including nested calls. Recursive calls only count once, as part of the
+    /// outermost call.
+    pub time: Duration,
+    /// The number of decision diagram nodes that were created, counted like the time.
+    pub nodes: usize,
+}
+
+/// A call or statement that is being measured.
+pub(crate) struct Measurement {
+    key: Key,
+    start: Instant,
+    created_nodes: usize,
+}
+
+impl Profile {
+    /// Returns the measurements, with the most time first.
+    pub fn entries(&self) -> Vec<(&Key, &Entry)> {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, entry)| Reverse(entry.time));
+        entries
+    }
+
+    pub(crate) fn start(&mut self, key: Key) -> Measurement {
+        *self.running.entry(key.clone()).or_default() += 1;
+        Measurement {
+            key,
+            start: Instant::now(),
+            created_nodes: crate::dd_manager().created_node_count(),
+        }
+    }
+
+    pub(crate) fn stop(&mut self, measurement: Measurement) {
+        let time = measurement.start.elapsed();
+        let nodes = crate::dd_manager().created_node_count() - measurement.created_nodes;
+
+        let running = self
+            .running
+            .get_mut(&measurement.key)
+            .expect("measurements are started before they are stopped");
+        *running -= 1;
+        let is_outermost = *running == 0;
+
+        let entry = self.entries.entry(measurement.key).or_default();
+        entry.calls += 1;
+        if is_outermost {
+            entry.time += time;
+            entry.nodes += nodes;
+        }
+    }
+}
+
+/// Formats a table with a row per function, procedure, statement and operator, with the most time
+/// first.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>10} {:>8}  {:<10} name",
+            "time", "nodes", "calls", "kind"
+        )?;
+        for (key, entry) in self.entries() {
+            writeln!(
+                f,
+                "{:>12} {:>10} {:>8}  {:<10} {}",
+                format!("{:.3?}", entry.time),
+                entry.nodes,
+                entry.calls,
+                key.kind,
+                key.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            Kind::Procedure => "procedure",
+            Kind::Function => "function",
+            Kind::Builtin => "builtin",
+            Kind::Statement => "statement",
+            Kind::Operator => "operator",
+        };
+        // Pads the kind in the table.
+        f.pad(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::{Globals, Locals, eval_profiled},
+        parser::{parse_expr, parse_program},
+        profile::{Kind, Profile},
+        relation::Relation,
+    };
+
+    #[test]
+    fn test_profile_calls() {
+        let src = include_str!("../examples/Recursion.prog");
+        let mut globals = Globals::default();
+        globals.extend(parse_program("Recursion.prog", src).unwrap().items);
+        let mut locals = Locals::default();
+        locals.assign("R", Relation::sparse((..5, ..5), [(0, 1), (1, 2), (3, 4)]));
+        locals.assign("Q", Relation::sparse((..5, ..1), [(0, 0)]));
+
+        let mut profile = Profile::default();
+        let expr = parse_expr("Reach(Q, R)").unwrap();
+        let result = eval_profiled(&globals, &locals, &expr, &mut profile).unwrap();
+
+        assert_eq!(
+            result,
+            Relation::sparse((..5, ..1), [(0, 0), (1, 0), (2, 0)])
+        );
+        let calls = |kind, name: &str| {
+            profile
+                .entries()
+                .into_iter()
+                .find(|(key, _)| key.kind == kind && key.name == name)
+                .map_or(0, |(_, entry)| entry.calls)
+        };
+        assert_eq!(calls(Kind::Procedure, "Reach"), 3);
+        assert_eq!(calls(Kind::Builtin, "eq"), 3);
+        assert_eq!(calls(Kind::Statement, "Reach:9 RETURN Reach(N,R)"), 2);
+        assert_eq!(calls(Kind::Statement, "Reach:7 RETURN Q"), 1);
+        assert_eq!(calls(Kind::Operator, "image"), 3);
+    }
+
+    #[test]
+    fn test_profile_operators() {
+        let mut locals = Locals::default();
+        locals.assign(
+            "R",
+            Relation::sparse((..64, ..64), (1..64).map(|x| (x - 1, x))),
+        );
+
+        let mut profile = Profile::default();
+        let expr = parse_expr("-(R * R) & (R | R^)").unwrap();
+        eval_profiled(&Globals::default(), &locals, &expr, &mut profile).unwrap();
+
+        let entry = |name: &str| {
+            profile
+                .entries()
+                .into_iter()
+                .find(|(key, _)| key.kind == Kind::Operator && key.name == name)
+                .map(|(_, entry)| *entry)
+                .unwrap()
+        };
+        for name in ["-", "*", "&", "|", "^"] {
+            assert_eq!(entry(name).calls, 1);
+        }
+        // The composition creates the nodes of the two-step relation.
+        assert!(entry("*").nodes > 0);
+    }
+}
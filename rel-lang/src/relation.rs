@@ -1,12 +1,40 @@
-use std::ops;
+use std::{ops, sync::Arc};
 
 use rel_lang_dd as dd;
-
-use crate::{Domain, Element, bits, bits2, dd_manager, iter_domain_product, num_vars};
+use snafu::{ResultExt, Snafu, ensure};
+
+use crate::{
+    Domain, Element, Encoding, Side, bits, bits2, dd_manager, default_encoding,
+    iter_domain_product, labels::Labels, num_vars, should_stop,
+};
+
+/// An operation on relations with domains that do not fit, or a constructor whose decision diagram
+/// could not be built.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+pub enum Error {
+    #[snafu(display("Pair {pair:?} is not in the domain {domain:?}"))]
+    NotInDomain {
+        pair: (Element, Element),
+        domain: (Domain, Domain),
+    },
+    #[snafu(display("Domain mismatch: expected {expected:?}, got {actual:?}"))]
+    DomainMismatch {
+        expected: (Domain, Domain),
+        actual: (Domain, Domain),
+    },
+    /// The decision diagram operations were aborted, for example by the node limit, and only left
+    /// a placeholder behind.
+    #[snafu(display("Decision diagram operation aborted: {source}"))]
+    Aborted { source: dd::Error },
+    /// The operation was stopped early because the evaluation was interrupted or timed out.
+    #[snafu(display("Operation stopped before completion"))]
+    Stopped,
+}
 
 #[derive(Clone)]
 pub struct Relation {
     domain: (Domain, Domain),
+    encoding: Encoding,
     node: dd::Node,
 }
 
@@ -14,199 +42,619 @@ impl Relation {
     pub fn empty(domain: (Domain, Domain)) -> Self {
         Self {
             domain,
+            encoding: default_encoding(),
             node: dd_manager().false_node(),
         }
     }
 
+    /// Panics if the decision diagram operations are aborted, see [`Relation::try_identity`].
     pub fn identity(domain: Domain) -> Self {
-        Self::sparse((domain, domain), (0..domain.end).map(|x| (x, x)))
+        Self::try_identity(domain).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_identity(domain: Domain) -> Result<Self, Error> {
+        let end = domain.end();
+        let relation = Self::sparse_encoded(
+            (domain.clone(), domain),
+            default_encoding(),
+            (0..end).map(|x| (x, x)),
+        )
+        .expect("the diagonal is in the domain");
+        check_manager()?;
+        Ok(relation)
     }
 
+    /// Panics if the decision diagram operations are aborted, see [`Relation::try_universal`].
     pub fn universal(domain: (Domain, Domain)) -> Self {
-        if domain.0.end == 0 || domain.1.end == 0 {
-            return Self::empty(domain);
+        Self::try_universal(domain).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_universal(domain: (Domain, Domain)) -> Result<Self, Error> {
+        let relation = Self::universal_encoded(domain, default_encoding());
+        check_manager()?;
+        Ok(relation)
+    }
+
+    fn universal_encoded(domain: (Domain, Domain), encoding: Encoding) -> Self {
+        if domain.0.end() == 0 || domain.1.end() == 0 {
+            return Self {
+                encoding,
+                ..Self::empty(domain)
+            };
         }
 
         let dd = dd_manager();
+        let node = dd.less_than_eq_vec(bits(&domain.0, domain.0.end() - 1))
+            & dd.less_than_eq_vec(bits(&domain.1, domain.1.end() - 1))
+                .shift(num_vars(&domain.0).into());
         Self {
+            node: reencode(&node, &domain, Encoding::Concatenated, encoding),
             domain,
-            node: dd.less_than_eq_vec(bits(domain.0, domain.0.end - 1))
-                & dd.less_than_eq_vec(bits(domain.1, domain.1.end - 1))
-                    .shift(num_vars(domain.0).into()),
+            encoding,
         }
     }
 
+    /// Panics if a pair is not in the domain or the decision diagram operations are aborted, see
+    /// [`Relation::try_sparse`].
     pub fn sparse(
-        domain: (Domain, Domain),
+        domain: (impl Into<Domain>, impl Into<Domain>),
         pairs: impl IntoIterator<Item = (Element, Element)>,
     ) -> Self {
+        Self::try_sparse(domain, pairs).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_sparse(
+        domain: (impl Into<Domain>, impl Into<Domain>),
+        pairs: impl IntoIterator<Item = (Element, Element)>,
+    ) -> Result<Self, Error> {
+        let relation = Self::sparse_encoded(domain, default_encoding(), pairs)?;
+        check_manager()?;
+        Ok(relation)
+    }
+
+    fn sparse_encoded(
+        domain: (impl Into<Domain>, impl Into<Domain>),
+        encoding: Encoding,
+        pairs: impl IntoIterator<Item = (Element, Element)>,
+    ) -> Result<Self, Error> {
+        let domain = (domain.0.into(), domain.1.into());
         let (x_domain, y_domain) = &domain;
         let dd = dd_manager();
 
         let mut node = dd.false_node();
         for (x, y) in pairs {
-            assert!(
-                x_domain.contains(&x),
-                "lhs of element {:?} is not in domain",
-                (x, y),
-            );
-            assert!(
-                y_domain.contains(&y),
-                "rhs of element {:?} is not in domain",
-                (x, y),
+            ensure!(
+                x_domain.contains(&x) && y_domain.contains(&y),
+                NotInDomainSnafu {
+                    pair: (x, y),
+                    domain: domain.clone(),
+                }
             );
-
-            node |= dd.minterm_vec(bits2(domain, (x, y)));
+            node |= dd.minterm_vec(bits2(&domain, encoding, (x, y)));
+            if dd.has_error() {
+                break;
+            }
         }
-        Self { domain, node }
+        Ok(Self {
+            domain,
+            encoding,
+            node,
+        })
     }
 
     pub fn true_relation() -> Self {
-        Self::universal((..1, ..1))
+        Self::universal((Domain::new(1), Domain::new(1)))
     }
 
     pub fn false_relation() -> Self {
-        Self::empty((..1, ..1))
+        Self::empty((Domain::new(1), Domain::new(1)))
     }
 
     pub fn domain(&self) -> (Domain, Domain) {
-        self.domain
+        self.domain.clone()
     }
 
-    pub fn converse(self) -> Self {
+    /// Attaches names to the elements of the domains, which are used when displaying the relation.
+    pub fn with_labels(self, labels: (Option<Arc<Labels>>, Option<Arc<Labels>>)) -> Self {
         let (x_domain, y_domain) = self.domain;
+        let label = |domain: Domain, labels: Option<Arc<Labels>>| match labels {
+            Some(labels) => {
+                assert_eq!(
+                    labels.len(),
+                    domain.end(),
+                    "{} labels do not fit domain {domain:?}",
+                    labels.len(),
+                );
+                Domain::labelled(labels)
+            }
+            None => Domain::new(domain.end()),
+        };
+        Self {
+            domain: (label(x_domain, labels.0), label(y_domain, labels.1)),
+            ..self
+        }
+    }
+
+    pub fn labels(&self) -> (Option<&Labels>, Option<&Labels>) {
+        (self.domain.0.labels(), self.domain.1.labels())
+    }
 
-        let num_vars_x = num_vars(x_domain);
-        let num_vars_y = num_vars(y_domain);
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
 
+    /// Converts the relation to another assignment of bits to variables.
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        if encoding == self.encoding {
+            return self;
+        }
         Self {
-            domain: (y_domain, x_domain),
-            node: self.node.split_shift(
-                num_vars_x.into(),
-                num_vars_y.into(),
-                -i64::from(num_vars_x),
-            ),
+            node: reencode(&self.node, &self.domain, self.encoding, encoding),
+            encoding,
+            ..self
         }
     }
 
+    /// Identifies the decision diagram node of the relation while the relation is alive.
+    pub(crate) fn node_id(&self) -> dd::NodeId {
+        self.node.id()
+    }
+
+    /// Returns the number of decision diagram nodes used by the relation.
+    pub fn node_count(&self) -> usize {
+        self.node.size()
+    }
+
+    /// Returns the number of pairs in the relation.
+    pub fn cardinality(&self) -> u128 {
+        let relation_num_vars = num_vars(&self.domain.0) + num_vars(&self.domain.1);
+        self.node.sat_count(relation_num_vars.into())
+    }
+
+    /// Panics if the decision diagram operations are aborted, see [`Relation::try_converse`].
+    pub fn converse(self) -> Self {
+        self.try_converse().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_converse(self) -> Result<Self, Error> {
+        let (x_domain, y_domain) = self.domain;
+
+        let num_vars_x = num_vars(&x_domain);
+        let num_vars_y = num_vars(&y_domain);
+
+        let node = match self.encoding {
+            Encoding::Concatenated => {
+                self.node
+                    .split_shift(num_vars_x.into(), num_vars_y.into(), -i64::from(num_vars_x))
+            }
+            Encoding::Interleaved => self.node.rename(|var| {
+                let (side, i) = self.encoding.side((num_vars_x, num_vars_y), var);
+                let side = match side {
+                    Side::Source => Side::Target,
+                    Side::Target => Side::Source,
+                };
+                self.encoding.var((num_vars_y, num_vars_x), side, i)
+            }),
+        };
+        check_manager()?;
+        Ok(Self {
+            domain: (y_domain, x_domain),
+            encoding: self.encoding,
+            node,
+        })
+    }
+
+    /// Panics if the decision diagram operations are aborted, see [`Relation::try_complement`].
+    pub fn complement(self) -> Self {
+        self.try_complement().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the pairs of the domain that are not in the relation, like `-`.
+    pub fn try_complement(self) -> Result<Self, Error> {
+        // Negating the node is free, restricting it to the domain keeps the encoding canonical.
+        let node = !self.node & Self::universal_encoded(self.domain.clone(), self.encoding).node;
+        check_manager()?;
+        Ok(Self { node, ..self })
+    }
+
+    /// Panics if the argument is not a vector on the source domain or the decision diagram
+    /// operations are aborted, see [`Relation::try_image`].
+    pub fn image(&self, vector: &Self) -> Self {
+        self.try_image(vector).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the vector of the elements that are related to an element of the vector, which is
+    /// `R^ * v` for the relation `R` and a vector `v` on its source domain.
+    pub fn try_image(&self, vector: &Self) -> Result<Self, Error> {
+        let image = self.relational_product(vector, Side::Source)?;
+        check_manager()?;
+        Ok(image)
+    }
+
+    /// Panics if the argument is not a vector on the target domain or the decision diagram
+    /// operations are aborted, see [`Relation::try_preimage`].
+    pub fn preimage(&self, vector: &Self) -> Self {
+        self.try_preimage(vector).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Returns the vector of the elements that are related to an element of the vector, which is
+    /// `R * v` for the relation `R` and a vector `v` on its target domain.
+    pub fn try_preimage(&self, vector: &Self) -> Result<Self, Error> {
+        let preimage = self.relational_product(vector, Side::Target)?;
+        check_manager()?;
+        Ok(preimage)
+    }
+
+    /// Returns the vector of `exists x. R(x, y) & v(x)` where `x` is on the given side of the
+    /// relation, without building the conjunction first. Fails if the argument is not a vector on
+    /// that side.
+    fn relational_product(&self, vector: &Self, side: Side) -> Result<Self, Error> {
+        let (domain, other_domain) = match side {
+            Side::Source => (&self.domain.0, &self.domain.1),
+            Side::Target => (&self.domain.1, &self.domain.0),
+        };
+        ensure!(
+            domain.is_compatible(&vector.domain.0) && vector.domain.1.end() == 1,
+            DomainMismatchSnafu {
+                expected: (domain.clone(), Domain::new(1)),
+                actual: vector.domain.clone(),
+            }
+        );
+
+        let relation_num_vars = (num_vars(&self.domain.0), num_vars(&self.domain.1));
+        let vector_num_vars = (num_vars(&vector.domain.0), num_vars(&vector.domain.1));
+        let new_domain = (other_domain.clone(), vector.domain.1.clone());
+        let new_num_vars = (num_vars(&new_domain.0), num_vars(&new_domain.1));
+
+        // Moves the bits of the vector to the quantified side of the relation, and the remaining
+        // bits of the relation to the source of the new vector.
+        let vector_node = vector.node.rename(|var| {
+            let (_, i) = vector.encoding.side(vector_num_vars, var);
+            self.encoding.var(relation_num_vars, side, i)
+        });
+        let node = self
+            .node
+            .and_exists(&vector_node, |var| {
+                self.encoding.side(relation_num_vars, var).0 == side
+            })
+            .rename(|var| {
+                let (_, i) = self.encoding.side(relation_num_vars, var);
+                self.encoding.var(new_num_vars, Side::Source, i)
+            });
+        Ok(Self {
+            domain: new_domain,
+            encoding: self.encoding,
+            node,
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.node.is_false()
     }
 
+    /// Panics if the domains do not match, see [`Relation::try_is_subset_of`].
     pub fn is_subset_of(&self, other: &Self) -> bool {
-        debug_assert_eq!(
-            self.domain, other.domain,
-            "domains {:?} and {:?} do not match",
-            self.domain, other.domain,
-        );
-        self.node.implies(&other.node).is_true()
+        self.try_is_subset_of(other)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_is_subset_of(&self, other: &Self) -> Result<bool, Error> {
+        check_compatible(&self.domain, &other.domain)?;
+        let other = other.clone().with_encoding(self.encoding);
+        let is_subset = self.node.implies(&other.node).is_true();
+        check_manager()?;
+        Ok(is_subset)
+    }
+
+    /// Returns whether the relations contain the same pairs, like `==`, but fails instead of
+    /// comparing relations with domains that do not match.
+    pub fn try_eq(&self, other: &Self) -> Result<bool, Error> {
+        check_compatible(&self.domain, &other.domain)?;
+        let eq = self.node == other.clone().with_encoding(self.encoding).node;
+        check_manager()?;
+        Ok(eq)
+    }
+
+    pub fn try_union(self, rhs: Self) -> Result<Self, Error> {
+        check_compatible(&self.domain, &rhs.domain)?;
+        let rhs = rhs.with_encoding(self.encoding);
+        let node = self.node | rhs.node;
+        check_manager()?;
+        Ok(Self {
+            domain: merge_domains(&self.domain, &rhs.domain),
+            node,
+            ..self
+        })
+    }
+
+    pub fn try_intersection(self, rhs: Self) -> Result<Self, Error> {
+        check_compatible(&self.domain, &rhs.domain)?;
+        let rhs = rhs.with_encoding(self.encoding);
+        let node = self.node & rhs.node;
+        check_manager()?;
+        Ok(Self {
+            domain: merge_domains(&self.domain, &rhs.domain),
+            node,
+            ..self
+        })
     }
 
-    pub fn contains(&self, pair: (Element, Element)) -> bool {
-        debug_assert!(
-            self.domain.0.contains(&pair.0),
-            "lhs of element {:?} is not in domain {:?}",
-            pair,
-            self.domain.0
+    /// Returns the composition `self * rhs`, which needs the target domain of `self` to match the
+    /// source domain of `rhs`.
+    pub fn try_compose(self, rhs: Self) -> Result<Self, Error> {
+        ensure!(
+            self.domain.1.is_compatible(&rhs.domain.0),
+            DomainMismatchSnafu {
+                expected: (self.domain.1.clone(), rhs.domain.1.clone()),
+                actual: rhs.domain.clone(),
+            }
         );
-        debug_assert!(
-            self.domain.1.contains(&pair.1),
-            "rhs of element {:?} is not in domain {:?}",
-            pair,
-            self.domain.1
+
+        // TODO: faster algorithm
+        let new_domain = (self.domain.0.clone(), rhs.domain.1.clone());
+        let inner_dim = self.domain.1.end();
+        let product = Self::sparse_encoded(
+            new_domain.clone(),
+            self.encoding,
+            // The product takes cubic time, so it stops early when interrupted or out of time.
+            iter_domain_product(&new_domain)
+                .take_while(|_| !should_stop())
+                .filter(|&(i, k)| {
+                    (0..inner_dim).any(|j| {
+                        self.contains((i, j)) == Ok(true) && rhs.contains((j, k)) == Ok(true)
+                    })
+                }),
         );
+        // The partial product is not returned.
+        ensure!(!should_stop(), StoppedSnafu);
+        let product = product.expect("the product is in the domain");
+        check_manager()?;
+        Ok(product)
+    }
 
-        self.node.eval(bits2(self.domain, pair)).unwrap_or(false)
+    pub fn contains(&self, pair: (Element, Element)) -> Result<bool, Error> {
+        ensure!(
+            self.domain.0.contains(&pair.0) && self.domain.1.contains(&pair.1),
+            NotInDomainSnafu {
+                pair,
+                domain: self.domain.clone(),
+            }
+        );
+        Ok(self
+            .node
+            .eval(bits2(&self.domain, self.encoding, pair))
+            .unwrap_or(false))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Element, Element)> {
-        iter_domain_product(self.domain).filter(move |&pair| self.contains(pair))
+        iter_domain_product(&self.domain).filter(move |&pair| self.contains(pair) == Ok(true))
+    }
+
+    pub fn collapse_left(&self) -> Result<Relation, Error> {
+        let vector = Relation::sparse_encoded(
+            (self.domain.0.clone(), Domain::new(1)),
+            self.encoding,
+            self.iter().map(|(x, _)| (x, 0)),
+        )
+        .expect("the pairs of the relation are in its source domain");
+        check_manager()?;
+        Ok(vector)
+    }
+
+    pub fn choose_one(&self) -> Result<Relation, Error> {
+        let relation = Relation::sparse_encoded(self.domain(), self.encoding, self.iter().take(1))
+            .expect("the pairs of the relation are in its domain");
+        check_manager()?;
+        Ok(relation)
+    }
+}
+
+const MAGIC: &[u8; 5] = b"RLREL";
+/// Version 2 added the encoding, version 1 files are always concatenated.
+const FORMAT_VERSION: u8 = 2;
+
+#[derive(Debug, Snafu)]
+pub enum DecodeError {
+    #[snafu(display("not a relation file"))]
+    InvalidMagic,
+    #[snafu(display("unsupported relation format version {version}"))]
+    UnsupportedVersion { version: u8 },
+    #[snafu(display("unexpected end of the domain header"))]
+    UnexpectedEnd,
+    #[snafu(display("invalid labels in the domain header"))]
+    InvalidLabels,
+    #[snafu(display("invalid encoding {encoding} in the domain header"))]
+    InvalidEncoding { encoding: u8 },
+    #[snafu(display("invalid decision diagram: {source}"))]
+    Diagram { source: dd::DecodeError },
+    #[snafu(display("the relation contains pairs outside of its domains"))]
+    OutOfDomain,
+}
+
+impl Relation {
+    /// Serializes the relation as its domains followed by the shared nodes of its decision
+    /// diagram, see [`dd::Node::to_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(&MAGIC[..]);
+        bytes.push(FORMAT_VERSION);
+        bytes.push(match self.encoding {
+            Encoding::Concatenated => 0,
+            Encoding::Interleaved => 1,
+        });
+        for domain in [&self.domain.0, &self.domain.1] {
+            let labels = domain.labels().into_iter().flat_map(Labels::iter);
+            let labels = labels.collect::<Vec<_>>();
+            bytes.extend(domain.end().to_le_bytes());
+            bytes.extend((labels.len() as u32).to_le_bytes());
+            for label in labels {
+                bytes.extend((label.len() as u32).to_le_bytes());
+                bytes.extend(label.as_bytes());
+            }
+        }
+        bytes.extend(self.node.to_bytes());
+        bytes
+    }
+
+    /// Reads a relation written by [`Relation::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut rest = bytes
+            .strip_prefix(&MAGIC[..])
+            .ok_or(DecodeError::InvalidMagic)?;
+        let version = *take(&mut rest, 1)?.first().unwrap();
+        ensure!(
+            (1..=FORMAT_VERSION).contains(&version),
+            UnsupportedVersionSnafu { version }
+        );
+        let encoding = match version {
+            1 => Encoding::Concatenated,
+            _ => match *take(&mut rest, 1)?.first().unwrap() {
+                0 => Encoding::Concatenated,
+                1 => Encoding::Interleaved,
+                encoding => return InvalidEncodingSnafu { encoding }.fail(),
+            },
+        };
+
+        let x_domain = read_domain(&mut rest)?;
+        let y_domain = match (x_domain.labels.as_ref(), read_domain(&mut rest)?) {
+            (Some(x_labels), y_domain) if y_domain.labels.as_ref() == Some(x_labels) => {
+                Domain::labelled(Arc::clone(x_labels))
+            }
+            (_, y_domain) => y_domain,
+        };
+        let node = dd_manager().from_bytes(rest).context(DiagramSnafu)?;
+
+        let relation = Self {
+            domain: (x_domain, y_domain),
+            encoding,
+            node,
+        };
+        let in_domain = relation
+            .node
+            .implies(&Self::universal_encoded(relation.domain(), encoding).node)
+            .is_true();
+        if let Some(error) = dd_manager().take_error() {
+            return Err(DecodeError::Diagram {
+                source: dd::DecodeError::Aborted { error },
+            });
+        }
+        ensure!(in_domain, OutOfDomainSnafu);
+        Ok(relation)
+    }
+}
+
+/// Fails if a decision diagram operation has been aborted since the last check, in which case the
+/// relation that was built is a placeholder.
+fn check_manager() -> Result<(), Error> {
+    match dd_manager().take_error() {
+        Some(source) => Err(Error::Aborted { source }),
+        None => Ok(()),
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    ensure!(bytes.len() >= len, UnexpectedEndSnafu);
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+fn read_domain(bytes: &mut &[u8]) -> Result<Domain, DecodeError> {
+    let end = read_u32(bytes)?;
+    let count = read_u32(bytes)?;
+    if count == 0 {
+        return Ok(Domain::new(end));
     }
 
-    pub fn collapse_left(&self) -> Relation {
-        Relation::sparse((self.domain.0, ..1), self.iter().map(|(x, _)| (x, 0)))
+    let mut labels = Labels::new();
+    for _ in 0..count {
+        let len = read_u32(bytes)? as usize;
+        let name =
+            std::str::from_utf8(take(bytes, len)?).map_err(|_| DecodeError::InvalidLabels)?;
+        ensure!(labels.element(name).is_none(), InvalidLabelsSnafu);
+        labels.get_or_insert(name);
     }
+    ensure!(labels.len() == end, InvalidLabelsSnafu);
+    Ok(Domain::labelled(Arc::new(labels)))
+}
 
-    pub fn choose_one(&self) -> Relation {
-        Relation::sparse(self.domain, self.iter().take(1))
+/// Moves the variables of a relation's node from one encoding to another.
+fn reencode(node: &dd::Node, domain: &(Domain, Domain), from: Encoding, to: Encoding) -> dd::Node {
+    if from == to {
+        return node.clone();
     }
+    let num_vars = (num_vars(&domain.0), num_vars(&domain.1));
+    node.rename(|var| {
+        let (side, i) = from.side(num_vars, var);
+        to.var(num_vars, side, i)
+    })
 }
 
+/// Returns whether the domains of two relations are pairwise compatible.
+pub fn domains_compatible(lhs: &(Domain, Domain), rhs: &(Domain, Domain)) -> bool {
+    lhs.0.is_compatible(&rhs.0) && lhs.1.is_compatible(&rhs.1)
+}
+
+fn check_compatible(lhs: &(Domain, Domain), rhs: &(Domain, Domain)) -> Result<(), Error> {
+    ensure!(
+        domains_compatible(lhs, rhs),
+        DomainMismatchSnafu {
+            expected: lhs.clone(),
+            actual: rhs.clone(),
+        }
+    );
+    Ok(())
+}
+
+fn merge_domains(lhs: &(Domain, Domain), rhs: &(Domain, Domain)) -> (Domain, Domain) {
+    (lhs.0.merge(&rhs.0), lhs.1.merge(&rhs.1))
+}
+
+/// Relations with domains that do not match are never equal.
 impl PartialEq for Relation {
     fn eq(&self, other: &Self) -> bool {
-        debug_assert_eq!(
-            self.domain, other.domain,
-            "domains {:?} and {:?} do not match",
-            self.domain, other.domain,
-        );
-        self.node == other.node
+        self.try_eq(other).unwrap_or(false)
     }
 }
 
 impl Eq for Relation {}
 
+/// Panics if the decision diagram operations are aborted, see [`Relation::try_complement`].
 impl ops::Neg for Relation {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        Self {
-            node: self.node ^ Self::universal(self.domain).node,
-            ..self
-        }
+        self.complement()
     }
 }
 
+/// Panics if the domains do not match, see [`Relation::try_union`].
 impl ops::BitOr for Relation {
     type Output = Self;
 
     fn bitor(self, rhs: Self) -> Self::Output {
-        debug_assert_eq!(
-            self.domain, rhs.domain,
-            "domains {:?} and {:?} do not match",
-            self.domain, rhs.domain,
-        );
-        Self {
-            node: self.node | rhs.node,
-            ..self
-        }
+        self.try_union(rhs).unwrap_or_else(|e| panic!("{e}"))
     }
 }
 
+/// Panics if the domains do not match, see [`Relation::try_intersection`].
 impl ops::BitAnd for Relation {
     type Output = Self;
 
     fn bitand(self, rhs: Self) -> Self::Output {
-        debug_assert_eq!(
-            self.domain, rhs.domain,
-            "domains {:?} and {:?} do not match",
-            self.domain, rhs.domain,
-        );
-        Self {
-            node: self.node & rhs.node,
-            ..self
-        }
+        self.try_intersection(rhs).unwrap_or_else(|e| panic!("{e}"))
     }
 }
 
+/// Panics if the domains do not match, see [`Relation::try_compose`].
 impl ops::Mul for Relation {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        debug_assert_eq!(
-            self.domain.1, rhs.domain.0,
-            "domains {:?} and {:?} cannot be multiplied",
-            self.domain, rhs.domain,
-        );
-
-        // TODO: faster algorithm
-        let new_domain = (self.domain.0, rhs.domain.1);
-        let inner_dim = self.domain.1.end;
-        Self::sparse(
-            new_domain,
-            iter_domain_product(new_domain).filter(|&(i, k)| {
-                (0..inner_dim).any(|j| self.contains((i, j)) && rhs.contains((j, k)))
-            }),
-        )
+        self.try_compose(rhs).unwrap_or_else(|e| panic!("{e}"))
     }
 }
 
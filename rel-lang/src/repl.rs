@@ -1,7 +1,8 @@
-use std::{io, ops};
+use std::{collections::BTreeSet, io, ops};
 
 use crate::{
-    eval::{Globals, Locals, eval},
+    debugger::{Breakpoint, Debugger, Tracer},
+    eval::{EvalOptions, Globals, Locals, eval, eval_observed},
     parser::parse_expr,
     relation::Relation,
     repl_commands::Node,
@@ -14,10 +15,16 @@ pub struct Repl {
     pub state: State,
 }
 
+#[derive(Default)]
 pub struct State {
     pub globals: Globals,
     pub locals: Locals,
     pub last_result: Option<Relation>,
+    /// Whether the statements that procedures execute are written to the output.
+    pub trace: bool,
+    /// The lines at which the debugger stops.
+    pub breakpoints: BTreeSet<Breakpoint>,
+    pub debugger: Option<Debugger>,
 }
 
 impl Default for Repl {
@@ -30,11 +37,7 @@ impl Repl {
     pub fn new() -> Self {
         Self {
             commands: Node::root(),
-            state: State {
-                globals: Globals::default(),
-                locals: Locals::default(),
-                last_result: None,
-            },
+            state: State::default(),
         }
     }
 
@@ -59,7 +62,19 @@ impl Repl {
             writeln!(out, "Error parsing expression")?;
             return Ok(ops::ControlFlow::Continue(()));
         };
-        let result = eval(&self.state.globals, &self.state.locals, &expr);
+        let result = if self.state.trace {
+            let mut tracer = Tracer::new(&mut out);
+            let result = eval_observed(&self.state.globals, &self.state.locals, &expr, &mut tracer);
+            tracer.finish()?;
+            result
+        } else {
+            eval(
+                &self.state.globals,
+                &self.state.locals,
+                &expr,
+                &EvalOptions::default(),
+            )
+        };
         match result {
             Ok(value) => {
                 // Relation::display already adds a newline.
@@ -77,7 +92,7 @@ impl Repl {
         command: &str,
         mut out: impl io::Write,
     ) -> io::Result<ops::ControlFlow<()>> {
-        let args = command.split_whitespace().collect::<Vec<_>>();
+        let args = self.commands.split(command);
 
         let Some(node) = self.commands.traverse(&args) else {
             writeln!(out, "Unknown command or invalid syntax")?;
@@ -1,16 +1,58 @@
-use std::{collections::BTreeMap, io, ops};
+use std::{
+    collections::BTreeMap,
+    io, ops,
+    time::{Duration, Instant},
+};
 
-use crate::{load_file, load_matrix, load_relation, repl::State, save_matrix, save_relation};
+use crate::{
+    Encoding,
+    debugger::{Breakpoint, Debugger, Progress, Resume},
+    eval::{EvalOptions, eval, eval_profiled},
+    load_bdd, load_edge_list, load_file, load_labels, load_matrix, load_relation, load_session,
+    load_workspace,
+    parser::parse_expr,
+    profile::Profile,
+    repl::State,
+    save_bdd, save_edge_list, save_matrix, save_relation, save_session, save_workspace,
+    set_default_encoding, set_max_call_depth, set_max_iterations, set_node_limit, set_timeout,
+};
 
 const HELP_MESSAGE: &str = "Available commands:\n\
    .help - Show this help message\n\
    .exit - Exit the REPL\n\
    .store <variable> - Store the last result in a variable\n\
+   .label <variable> <filename> - Attach element labels from a file, one per line\n\
    .load prog <filename> - Load a program from a file\n\
    .load rel <variable> <filename> - Load a relation from a file\n\
    .load mat <variable> <filename> - Load a matrix from a file\n\
+   .load csv <variable> <filename> - Load a labelled edge list from a CSV or TSV file\n\
+   .load bdd <variable> <filename> - Load a relation from a binary decision diagram file\n\
+   .load xml <filename> - Load relations and programs from a rel-lang XML workspace file\n\
+   .load session <filename> - Replace the current session with a saved one\n\
    .save rel <variable> <filename> - Save a relation to a file\n\
-   .save mat <variable> <filename> - Save a matrix to a file";
+   .save mat <variable> <filename> - Save a matrix to a file\n\
+   .save csv <variable> <filename> - Save a homogeneous relation as an edge list to a CSV or TSV file\n\
+   .save bdd <variable> <filename> - Save a relation as a binary decision diagram file\n\
+   .save xml <filename> - Save all relations and programs to a rel-lang XML workspace file\n\
+   .save session <filename> - Save the current session, including the last result\n\
+   .set encoding concatenated|interleaved - Choose how new relations are stored as decision diagrams\n\
+   .set node-limit <count>|none - Limit the number of decision diagram nodes\n\
+   .set max-iterations <count> - Limit the number of iterations of loops and fixpoints\n\
+   .set max-call-depth <count> - Limit the number of nested calls of functions and procedures\n\
+   .set timeout <duration>|none - Limit the time of evaluations, e.g. 500ms, 10s or 2m\n\
+   .cache on|off - Reuse the results of calls to functions and procedures with the same arguments\n\
+   .cache clear - Forget the results of earlier calls\n\
+   .cache stats - Show how often the results of earlier calls were reused\n\
+   .trace on|off - Show the statements that procedures execute\n\
+   .debug run <expression> - Evaluate an expression and stop at the first statement\n\
+   .debug step - Execute the statement and stop at the next one\n\
+   .debug continue - Execute statements until the next breakpoint\n\
+   .debug locals - Show the variables of the procedure that stopped\n\
+   .debug break <filename> <line> - Stop before executing the statements on a line of a program\n\
+   .debug delete <filename> <line> - Remove a breakpoint\n\
+   .debug quit - Stop debugging\n\
+   .profile <expression> - Evaluate an expression and show where the time and nodes were spent\n\
+   .time <expression> - Evaluate an expression and show how long it took";
 
 /// The available commands form a rooted tree where the tokens are represented by edges.
 #[derive(Default)]
@@ -27,6 +69,10 @@ pub enum Edge {
     Keyword(&'static str),
     Filename,
     Variable,
+    /// Any other argument, such as a number, which is not completed.
+    Value,
+    /// The rest of the command, which is an expression that may contain whitespace.
+    Expression,
 }
 
 impl Node {
@@ -42,7 +88,7 @@ impl Node {
             .with_func(|_state, _out, [_]| Ok(ops::ControlFlow::Break(())));
         root.insert(Edge::Keyword(".store"))
             .insert(Edge::Variable)
-            .with_func(|state, out, [variable]| {
+            .with_func(|state, out, [_, variable]| {
                 if let Some(result) = &state.last_result {
                     state.locals.assign(variable, result.clone());
                     writeln!(out, "Stored in variable '{}'", variable)?;
@@ -51,6 +97,16 @@ impl Node {
                 }
                 Ok(ops::ControlFlow::Continue(()))
             });
+        root.insert(Edge::Keyword(".label"))
+            .insert(Edge::Variable)
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, variable, filename]| {
+                match load_labels(variable, filename, &mut state.locals) {
+                    Ok(()) => writeln!(out, "Labels for '{variable}' loaded from '{filename}'")?,
+                    Err(e) => writeln!(out, "Error loading labels: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
 
         let load = root.insert(Edge::Keyword(".load"));
         load.insert(Edge::Keyword("prog"))
@@ -88,6 +144,50 @@ impl Node {
                 }
                 Ok(ops::ControlFlow::Continue(()))
             });
+        load.insert(Edge::Keyword("csv"))
+            .insert(Edge::Variable)
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, variable, filename]| {
+                match load_edge_list(variable, filename, &mut state.locals) {
+                    Ok(()) => writeln!(
+                        out,
+                        "Edge list '{variable}' loaded successfully from '{filename}'"
+                    )?,
+                    Err(e) => writeln!(out, "Error loading edge list: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        load.insert(Edge::Keyword("bdd"))
+            .insert(Edge::Variable)
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, variable, filename]| {
+                match load_bdd(variable, filename, &mut state.locals) {
+                    Ok(()) => writeln!(
+                        out,
+                        "Relation '{variable}' loaded successfully from '{filename}'"
+                    )?,
+                    Err(e) => writeln!(out, "Error loading relation: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        load.insert(Edge::Keyword("xml"))
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, filename]| {
+                match load_workspace(filename, &mut state.globals, &mut state.locals) {
+                    Ok(()) => writeln!(out, "Workspace loaded successfully from '{filename}'")?,
+                    Err(e) => writeln!(out, "Error loading workspace: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        load.insert(Edge::Keyword("session"))
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, filename]| {
+                match load_session(filename, state) {
+                    Ok(()) => writeln!(out, "Session restored from '{filename}'")?,
+                    Err(e) => writeln!(out, "Error loading session: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
 
         let save = root.insert(Edge::Keyword(".save"));
         save.insert(Edge::Keyword("rel"))
@@ -110,6 +210,307 @@ impl Node {
                 }
                 Ok(ops::ControlFlow::Continue(()))
             });
+        save.insert(Edge::Keyword("csv"))
+            .insert(Edge::Variable)
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, variable, filename]| {
+                match save_edge_list(&state.locals, variable, filename) {
+                    Ok(()) => writeln!(out, "Edge list '{variable}' saved to '{filename}'")?,
+                    Err(e) => writeln!(out, "Error saving edge list: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        save.insert(Edge::Keyword("bdd"))
+            .insert(Edge::Variable)
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, variable, filename]| {
+                match save_bdd(&state.locals, variable, filename) {
+                    Ok(()) => writeln!(out, "Relation '{variable}' saved to '{filename}'")?,
+                    Err(e) => writeln!(out, "Error saving relation: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        save.insert(Edge::Keyword("xml"))
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, filename]| {
+                match save_workspace(&state.globals, &state.locals, filename) {
+                    Ok(()) => writeln!(out, "Workspace saved to '{filename}'")?,
+                    Err(e) => writeln!(out, "Error saving workspace: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        save.insert(Edge::Keyword("session"))
+            .insert(Edge::Filename)
+            .with_func(|state, out, [_, _, filename]| {
+                match save_session(state, filename) {
+                    Ok(()) => writeln!(out, "Session saved to '{filename}'")?,
+                    Err(e) => writeln!(out, "Error saving session: {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+
+        let set = root.insert(Edge::Keyword(".set"));
+        let encoding = set.insert(Edge::Keyword("encoding"));
+        for (keyword, value) in [
+            ("concatenated", Encoding::Concatenated),
+            ("interleaved", Encoding::Interleaved),
+        ] {
+            encoding
+                .insert(Edge::Keyword(keyword))
+                .with_func(move |_state, out, [_, _, _]| {
+                    set_default_encoding(value);
+                    writeln!(out, "New relations use the {keyword} encoding")?;
+                    Ok(ops::ControlFlow::Continue(()))
+                });
+        }
+        set.insert(Edge::Keyword("node-limit"))
+            .insert(Edge::Value)
+            .with_func(|_state, out, [_, _, value]| {
+                match value {
+                    "none" => {
+                        set_node_limit(None);
+                        writeln!(out, "The number of nodes is unlimited")?;
+                    }
+                    _ => match value.parse() {
+                        Ok(limit) => {
+                            set_node_limit(Some(limit));
+                            writeln!(out, "The number of nodes is limited to {limit}")?;
+                        }
+                        Err(e) => writeln!(out, "Invalid node limit '{value}': {e}")?,
+                    },
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        set.insert(Edge::Keyword("max-iterations"))
+            .insert(Edge::Value)
+            .with_func(|_state, out, [_, _, value]| {
+                match value.parse() {
+                    Ok(limit) => {
+                        set_max_iterations(limit);
+                        writeln!(out, "Loops and fixpoints stop after {limit} iterations")?;
+                    }
+                    Err(e) => writeln!(out, "Invalid iteration limit '{value}': {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        set.insert(Edge::Keyword("max-call-depth"))
+            .insert(Edge::Value)
+            .with_func(|_state, out, [_, _, value]| {
+                match value.parse() {
+                    Ok(limit) => {
+                        set_max_call_depth(limit);
+                        writeln!(out, "Calls can be nested {limit} deep")?;
+                    }
+                    Err(e) => writeln!(out, "Invalid call depth '{value}': {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        set.insert(Edge::Keyword("timeout"))
+            .insert(Edge::Value)
+            .with_func(|_state, out, [_, _, value]| {
+                match value {
+                    "none" => {
+                        set_timeout(None);
+                        writeln!(out, "Evaluations never time out")?;
+                    }
+                    _ => match parse_duration(value) {
+                        Some(timeout) => {
+                            set_timeout(Some(timeout));
+                            writeln!(out, "Evaluations time out after {timeout:?}")?;
+                        }
+                        None => writeln!(
+                            out,
+                            "Invalid timeout '{value}': expected a number followed by ms, s or m"
+                        )?,
+                    },
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+
+        let cache = root.insert(Edge::Keyword(".cache"));
+        for (keyword, enabled) in [("on", true), ("off", false)] {
+            cache
+                .insert(Edge::Keyword(keyword))
+                .with_func(move |state, out, [_, _]| {
+                    state.globals.memo().borrow_mut().set_enabled(enabled);
+                    writeln!(out, "The cache is {keyword}")?;
+                    Ok(ops::ControlFlow::Continue(()))
+                });
+        }
+        cache
+            .insert(Edge::Keyword("clear"))
+            .with_func(|state, out, [_, _]| {
+                state.globals.memo().borrow_mut().clear();
+                writeln!(out, "The cache is cleared")?;
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        cache
+            .insert(Edge::Keyword("stats"))
+            .with_func(|state, out, [_, _]| {
+                let memo = state.globals.memo().borrow();
+                let stats = memo.stats();
+                writeln!(
+                    out,
+                    "The cache is {}: {} hits, {} misses, {} entries",
+                    if memo.is_enabled() { "on" } else { "off" },
+                    stats.hits,
+                    stats.misses,
+                    stats.entries,
+                )?;
+                Ok(ops::ControlFlow::Continue(()))
+            });
+
+        let trace = root.insert(Edge::Keyword(".trace"));
+        for (keyword, enabled) in [("on", true), ("off", false)] {
+            trace
+                .insert(Edge::Keyword(keyword))
+                .with_func(move |state, out, [_, _]| {
+                    state.trace = enabled;
+                    writeln!(out, "Tracing is {keyword}")?;
+                    Ok(ops::ControlFlow::Continue(()))
+                });
+        }
+
+        let debug = root.insert(Edge::Keyword(".debug"));
+        // The expression has its own subcommand, since it could otherwise be mistaken for an
+        // abbreviated subcommand, like `s` for `step`.
+        debug
+            .insert(Edge::Keyword("run"))
+            .insert(Edge::Expression)
+            .with_func(|state, out, [_, _, expr]| {
+                let Ok(expr) = parse_expr(expr) else {
+                    writeln!(out, "Error parsing expression")?;
+                    return Ok(ops::ControlFlow::Continue(()));
+                };
+                // Dropping the previous debugger stops its evaluation.
+                state.debugger = None;
+                match Debugger::new(&state.globals, &state.locals, expr) {
+                    Ok(debugger) => state.debugger = Some(debugger),
+                    Err(e) => {
+                        writeln!(out, "Error starting the debugger: {e}")?;
+                        return Ok(ops::ControlFlow::Continue(()));
+                    }
+                }
+                resume(state, out, Resume::Step)
+            });
+        for (keyword, mode) in [("step", Resume::Step), ("continue", Resume::Continue)] {
+            debug
+                .insert(Edge::Keyword(keyword))
+                .with_func(move |state, out, [_, _]| resume(state, out, mode));
+        }
+        debug
+            .insert(Edge::Keyword("locals"))
+            .with_func(|state, out, [_, _]| {
+                match state.debugger.as_ref().and_then(Debugger::stop) {
+                    Some(stop) => {
+                        for (name, relation) in stop.locals.relations() {
+                            write!(out, "{}", relation.display(name))?;
+                        }
+                    }
+                    None => writeln!(out, "Not debugging")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        debug
+            .insert(Edge::Keyword("break"))
+            .insert(Edge::Filename)
+            .insert(Edge::Value)
+            .with_func(|state, out, [_, _, file, value]| {
+                match value.parse() {
+                    Ok(line) => {
+                        let breakpoint = Breakpoint {
+                            file: file.to_string(),
+                            line,
+                        };
+                        writeln!(out, "Breakpoint at {breakpoint}")?;
+                        state.breakpoints.insert(breakpoint);
+                    }
+                    Err(e) => writeln!(out, "Invalid line '{value}': {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        debug
+            .insert(Edge::Keyword("delete"))
+            .insert(Edge::Filename)
+            .insert(Edge::Value)
+            .with_func(|state, out, [_, _, file, value]| {
+                match value.parse() {
+                    Ok(line) => {
+                        let breakpoint = Breakpoint {
+                            file: file.to_string(),
+                            line,
+                        };
+                        if state.breakpoints.remove(&breakpoint) {
+                            writeln!(out, "Removed the breakpoint at {breakpoint}")?;
+                        } else {
+                            writeln!(out, "No breakpoint at {breakpoint}")?;
+                        }
+                    }
+                    Err(e) => writeln!(out, "Invalid line '{value}': {e}")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+        debug
+            .insert(Edge::Keyword("quit"))
+            .with_func(|state, out, [_, _]| {
+                match state.debugger.take() {
+                    Some(_) => writeln!(out, "Stopped debugging")?,
+                    None => writeln!(out, "Not debugging")?,
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
+
+        root.insert(Edge::Keyword(".profile"))
+            .insert(Edge::Expression)
+            .with_func(|state, out, [_, expr]| {
+                let Ok(expr) = parse_expr(expr) else {
+                    writeln!(out, "Error parsing expression")?;
+                    return Ok(ops::ControlFlow::Continue(()));
+                };
+                let mut profile = Profile::default();
+                match eval_profiled(&state.globals, &state.locals, &expr, &mut profile) {
+                    Ok(value) => {
+                        write!(out, "{}", value.display("<expr>"))?;
+                        state.last_result = Some(value);
+                    }
+                    Err(e) => writeln!(out, "Error: {e}")?,
+                }
+                write!(out, "{profile}")?;
+                Ok(ops::ControlFlow::Continue(()))
+            });
+
+        root.insert(Edge::Keyword(".time"))
+            .insert(Edge::Expression)
+            .with_func(|state, out, [_, expr]| {
+                let Ok(expr) = parse_expr(expr) else {
+                    writeln!(out, "Error parsing expression")?;
+                    return Ok(ops::ControlFlow::Continue(()));
+                };
+                let start = Instant::now();
+                let result = eval(
+                    &state.globals,
+                    &state.locals,
+                    &expr,
+                    &EvalOptions::default(),
+                );
+                let elapsed = start.elapsed();
+                match result {
+                    Ok(value) => {
+                        write!(out, "{}", value.display("<expr>"))?;
+                        writeln!(
+                            out,
+                            "Evaluated in {elapsed:.2?}, the result has {} nodes",
+                            value.node_count()
+                        )?;
+                        state.last_result = Some(value);
+                    }
+                    Err(e) => {
+                        writeln!(out, "Error: {e}")?;
+                        writeln!(out, "Failed after {elapsed:.2?}")?;
+                    }
+                }
+                Ok(ops::ControlFlow::Continue(()))
+            });
 
         root
     }
@@ -131,18 +532,42 @@ impl Node {
     pub fn traverse(&self, args: &[&str]) -> Option<&Self> {
         let mut node = self;
         for arg in args {
-            if let Some(edge) = node.edges.keys().find(|e| {
-                matches!(e, Edge::Keyword(k) if k.starts_with(arg))
-                    || matches!(e, Edge::Variable | Edge::Filename)
-            }) {
-                node = &node.edges[edge];
-            } else {
-                return None;
-            }
+            node = node.next(arg)?.1;
         }
         Some(node)
     }
 
+    /// Splits a command into its arguments at whitespace, except that an expression takes up the
+    /// rest of the command.
+    pub fn split<'a>(&self, command: &'a str) -> Vec<&'a str> {
+        let mut args = Vec::new();
+        let mut node = Some(self);
+        let mut rest = command.trim();
+        while !rest.is_empty() {
+            let next = node.and_then(|node| node.next(rest.split_whitespace().next()?));
+            if let Some((Edge::Expression, _)) = next {
+                args.push(rest);
+                break;
+            }
+
+            let (arg, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            args.push(arg);
+            node = next.map(|(_, node)| node);
+            rest = tail.trim_start();
+        }
+        args
+    }
+
+    fn next(&self, arg: &str) -> Option<(&Edge, &Self)> {
+        self.edges.iter().find(|(e, _)| {
+            matches!(e, Edge::Keyword(k) if k.starts_with(arg))
+                || matches!(
+                    e,
+                    Edge::Variable | Edge::Filename | Edge::Value | Edge::Expression
+                )
+        })
+    }
+
     pub fn next_keywords_by_prefix(&self, prefix: &str) -> impl Iterator<Item = &str> {
         self.edges.keys().filter_map(move |edge| {
             if let &Edge::Keyword(keyword) = edge {
@@ -157,3 +582,47 @@ impl Node {
         })
     }
 }
+
+/// Resumes the evaluation that is being debugged, and shows where it stopped or its result.
+fn resume(
+    state: &mut State,
+    out: &mut dyn io::Write,
+    mode: Resume,
+) -> io::Result<ops::ControlFlow<()>> {
+    let Some(debugger) = &mut state.debugger else {
+        writeln!(out, "Not debugging")?;
+        return Ok(ops::ControlFlow::Continue(()));
+    };
+
+    match debugger.resume(&state.breakpoints, mode) {
+        Progress::Stopped => {
+            if let Some(stop) = debugger.stop() {
+                writeln!(out, "Stopped at {stop}")?;
+            }
+        }
+        Progress::Finished(result) => {
+            state.debugger = None;
+            match result {
+                Ok(value) => {
+                    write!(out, "{}", value.display("<expr>"))?;
+                    state.last_result = Some(value);
+                }
+                Err(e) => writeln!(out, "Error: {e}")?,
+            }
+        }
+    }
+    Ok(ops::ControlFlow::Continue(()))
+}
+
+/// Parses a duration like `500ms`, `10s` or `1.5m`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = value.split_at(value.find(|c: char| c.is_ascii_alphabetic())?);
+    let number = number.parse::<f64>().ok()?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
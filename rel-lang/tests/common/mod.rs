@@ -0,0 +1,15 @@
+//! Helpers shared by the integration tests.
+
+use rel_lang::repl::Repl;
+
+/// Feeds the input to a new REPL line by line, and compares everything it printed with the
+/// expected transcript, ignoring the surrounding whitespace.
+pub fn assert_transcript(input: &str, expected: &str) {
+    let mut repl = Repl::new();
+    let mut output = Vec::new();
+    for line in input.lines() {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+
+    pretty_assertions::assert_eq!(String::from_utf8(output).unwrap().trim(), expected.trim());
+}
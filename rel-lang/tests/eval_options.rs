@@ -0,0 +1,65 @@
+//! Tests of the budget that [`EvalOptions`] gives a single evaluation. Its node limit replaces the
+//! global one while the evaluation runs, which would also stop the evaluations of other tests.
+
+use std::time::Duration;
+
+use rel_lang::{
+    eval::{Error, EvalOptions, Globals, Locals, eval},
+    parser::{parse_expr, parse_program},
+    relation::Relation,
+};
+
+#[test]
+fn test_eval_options() {
+    let src = r#"
+Loop(R)
+  DECL S
+  BEG
+    S = R
+    WHILE -empty(S) DO
+      S = S * R
+    OD
+    RETURN S
+  END.
+"#;
+    let mut globals = Globals::default();
+    globals.extend(parse_program("Loop.prog", src).unwrap().items);
+    let mut locals = Locals::default();
+    locals.assign("R", Relation::sparse((..2, ..2), [(0, 0), (1, 1)]));
+    let expr = parse_expr("Loop(R)").unwrap();
+
+    let options = EvalOptions {
+        timeout: Some(Duration::from_millis(50)),
+        max_iterations: Some(usize::MAX),
+        ..EvalOptions::default()
+    };
+    let result = eval(&globals, &locals, &expr, &options);
+    assert!(
+        matches!(result, Err(Error::Timeout { timeout }) if timeout == Duration::from_millis(50))
+    );
+
+    let options = EvalOptions {
+        max_iterations: Some(3),
+        ..EvalOptions::default()
+    };
+    let result = eval(&globals, &locals, &expr, &options);
+    assert!(matches!(
+        result,
+        Err(Error::IterationLimitExceeded { limit: 3 })
+    ));
+
+    let options = EvalOptions {
+        max_nodes: Some(1),
+        ..EvalOptions::default()
+    };
+    let result = eval(&globals, &locals, &parse_expr("R * R").unwrap(), &options);
+    assert!(matches!(result, Err(Error::NodeLimitExceeded { limit: 1 })));
+
+    // The budget also applies to calls outside of expressions, and ends with the evaluation.
+    let function = globals.function("Loop").unwrap();
+    let result = function.call(&globals, vec![locals.get("R").unwrap()], &options);
+    assert!(matches!(result, Err(Error::NodeLimitExceeded { limit: 1 })));
+    let empty = Relation::sparse((..2, ..2), []);
+    let result = function.call(&globals, vec![empty.clone()], &EvalOptions::default());
+    assert_eq!(result.unwrap(), empty);
+}
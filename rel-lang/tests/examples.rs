@@ -1,22 +1,18 @@
-use rel_lang::repl::Repl;
+use rel_lang::{
+    Domain, Encoding, load_session,
+    parser::parse_program,
+    relation::Relation,
+    repl::{Repl, State},
+    save_session,
+};
+
+mod common;
 
 macro_rules! test {
     ($name:ident, $input:literal, $expected:literal) => {
         #[test]
         fn $name() {
-            let input: &str = $input;
-            let expected: &str = $expected;
-
-            let mut repl = Repl::new();
-            let mut output = Vec::new();
-            for line in input.lines() {
-                let _ = repl.process_input(line, &mut output).unwrap();
-            }
-
-            pretty_assertions::assert_eq!(
-                String::from_utf8(output).unwrap().trim(),
-                expected.trim(),
-            );
+            common::assert_transcript($input, $expected);
         }
     };
 }
@@ -193,3 +189,607 @@ Relation 'R2' loaded successfully from 'examples/R2.ascii'
 5 : 1, 2, 3, 4, 5
 "#
 );
+
+test!(
+    test_example_reachable_vector,
+    r#"
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+Reachable1(R1, dom(R2))
+Reachable3(R1^, dom(R2))
+"#,
+    r#"
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+<expr> (5, 1)
+1 : 1
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+"#
+);
+
+test!(
+    test_example_fixpoints,
+    r#"
+.load prog examples/Fixpoints.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+lfp(Step, dom(R2), R1)
+gfp(Stay, Ln1(R1), R1, dom(R2))
+lfp(R1, dom(R2))
+lfp(Step)
+.set max-iterations 1
+lfp(Step, dom(R2), R1)
+"#,
+    r#"
+Program loaded successfully from 'examples/Fixpoints.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+<expr> (5, 1)
+5 : 1
+Error: Unknown function: R1
+Error: Arity mismatch: expected at least 2 args, got 1 args
+Loops and fixpoints stop after 1 iterations
+Error: Iteration limit of 1 exceeded
+"#
+);
+
+test!(
+    test_example_recursion,
+    r#"
+.load prog examples/Recursion.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+Reach(dom(R2), R1)
+.set max-call-depth 10
+Start(R1)
+"#,
+    r#"
+Program loaded successfully from 'examples/Recursion.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+Calls can be nested 10 deep
+Error: Stack overflow: maximum call depth of 10 exceeded
+  in Forever (10 calls)
+  in Start
+"#
+);
+
+// IF runs its THEN branch when the condition is non-empty, so TRed removes the pairs that the
+// transitive closure does not need.
+test!(
+    test_example_tred,
+    r#"
+.load prog examples/Examples.prog
+.load prog examples/Closures.prog
+.load rel R1 examples/R1.ascii
+.load rel R3 examples/R3.ascii
+TRed(R3)
+TRed(R1)
+"#,
+    r#"
+Program loaded successfully from 'examples/Examples.prog'
+Program loaded successfully from 'examples/Closures.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R3' loaded successfully from 'examples/R3.ascii'
+<expr> (5, 5)
+1 : 2
+2 : 3
+3 : 4
+4 : 5
+<expr> (5, 5)
+1 : 2
+2 : 3
+3 : 4
+4 : 2, 5
+"#
+);
+
+test!(
+    test_example_xml_workspace,
+    r#"
+.load xml examples/Examples.xml
+RTC2(R1)
+"#,
+    r#"
+Workspace loaded successfully from 'examples/Examples.xml'
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+"#
+);
+
+test!(
+    test_example_csv_labels,
+    r#"
+.load csv E examples/Edges.csv
+E^
+"#,
+    r#"
+Edge list 'E' loaded successfully from 'examples/Edges.csv'
+<expr> (5, 5)
+Wellington : Auckland, Dunedin
+Christchurch : Wellington
+Dunedin : Christchurch
+Queenstown, NZ : Dunedin
+"#
+);
+
+test!(
+    test_example_labels_survive,
+    r#"
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.label R1 examples/R1.labels
+RTC2(R1)
+Ln1(R1)^ * R1
+"#,
+    r#"
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Labels for 'R1' loaded from 'examples/R1.labels'
+<expr> (5, 5)
+one : one, two, three, four, five
+two : two, three, four, five
+three : two, three, four, five
+four : two, three, four, five
+five : five
+<expr> (1, 5)
+1 : two, three, four, five
+"#
+);
+
+test!(
+    test_example_labels_mismatch,
+    r#"
+.load rel R1 examples/R1.ascii
+.load rel R5 examples/R5.ascii
+.label R1 examples/R1.labels
+R1 | R5
+.label R5 examples/R1.labels
+"#,
+    r#"
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R5' loaded successfully from 'examples/R5.ascii'
+Labels for 'R1' loaded from 'examples/R1.labels'
+Error: Domain mismatch: expected (..5 (labelled), ..5 (labelled)), got (..7, ..7)
+Error loading labels: 5 labels do not fit the domains (..7, ..7)
+"#
+);
+
+test!(
+    test_example_identity_mismatch,
+    r#"
+.load rel R1 examples/R1.ascii
+I(Ln1(R1))
+"#,
+    r#"
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Error: Domain mismatch: expected (..5, ..5), got (..5, ..1)
+"#
+);
+
+#[test]
+fn test_example_session_roundtrip() {
+    let path = std::env::temp_dir().join(format!("rel-lang-session-{}.xml", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut output = Vec::new();
+    let mut repl = Repl::new();
+    for line in [
+        ".load prog examples/Examples.prog",
+        ".load rel R1 examples/R1.ascii",
+        ".label R1 examples/R1.labels",
+        "RTC2(R1)",
+        &format!(".save session {path}"),
+    ] {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+
+    let mut output = Vec::new();
+    let mut repl = Repl::new();
+    for line in [
+        &format!(".load session {path}"),
+        ".store T",
+        "T",
+        "RTC1(R1)",
+    ] {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+    let _ = std::fs::remove_file(path);
+
+    pretty_assertions::assert_eq!(
+        String::from_utf8(output).unwrap().trim(),
+        format!(
+            r#"
+Session restored from '{path}'
+Stored in variable 'T'
+<expr> (5, 5)
+one : one, two, three, four, five
+two : two, three, four, five
+three : two, three, four, five
+four : two, three, four, five
+five : five
+<expr> (5, 5)
+one : one, two, three, four, five
+two : two, three, four, five
+three : two, three, four, five
+four : two, three, four, five
+five : five
+"#
+        )
+        .trim(),
+    );
+}
+
+#[test]
+fn test_example_session_stores_decision_diagrams() {
+    let path =
+        std::env::temp_dir().join(format!("rel-lang-session-large-{}.xml", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut state = State::default();
+    let universal = Relation::universal((Domain::new(2048), Domain::new(2048)));
+    state.locals.assign("L", universal.clone());
+    save_session(&state, path).unwrap();
+    let size = std::fs::metadata(path).unwrap().len();
+
+    let mut loaded = State::default();
+    load_session(path, &mut loaded).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    // The session grows with the nodes of the relations rather than their pairs.
+    assert!(size < 1000, "the session has {size} bytes");
+    assert_eq!(loaded.locals.get("L").unwrap(), universal);
+}
+
+#[test]
+fn test_example_session_keeps_sources_and_encodings() {
+    let path = std::env::temp_dir().join(format!(
+        "rel-lang-session-sources-{}.xml",
+        std::process::id()
+    ));
+    let path = path.to_str().unwrap();
+
+    let mut output = Vec::new();
+    let mut repl = Repl::new();
+    for line in [
+        ".load prog examples/Examples.prog",
+        ".set encoding interleaved",
+        ".load rel R1 examples/R1.ascii",
+        "R1",
+        ".set encoding concatenated",
+        &format!(".save session {path}"),
+    ] {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+
+    let mut state = State::default();
+    load_session(path, &mut state).unwrap();
+    let _ = std::fs::remove_file(path);
+
+    // The programs keep their comments and layout.
+    let src = include_str!("../examples/Examples.prog");
+    let program = parse_program("Examples.prog", src).unwrap();
+    for item in &program.items {
+        let loaded = state
+            .globals
+            .items()
+            .find(|loaded| loaded.name() == item.name());
+        assert_eq!(loaded.map(|loaded| loaded.source()), Some(item.source()));
+    }
+    let rtc1 = state.globals.items().find(|item| item.name() == "RTC1");
+    assert!(
+        rtc1.unwrap()
+            .source()
+            .contains("{ P = current power of R }")
+    );
+
+    assert_eq!(
+        state.locals.get("R1").unwrap().encoding(),
+        Encoding::Interleaved
+    );
+    assert_eq!(state.last_result.unwrap().encoding(), Encoding::Interleaved);
+}
+
+#[test]
+fn test_example_edge_list_roundtrip() {
+    let path = std::env::temp_dir().join(format!("rel-lang-edges-{}.csv", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut output = Vec::new();
+    let mut repl = Repl::new();
+    for line in [
+        ".load rel R1 examples/R1.ascii",
+        "R1^",
+        ".store S",
+        &format!(".save csv S {path}"),
+        &format!(".load csv T {path}"),
+        "eq(S, T)",
+        "L1n(R1)",
+        ".store V",
+        &format!(".save csv V {path}"),
+    ] {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+    let _ = std::fs::remove_file(path);
+
+    pretty_assertions::assert_eq!(
+        String::from_utf8(output).unwrap().trim(),
+        format!(
+            r#"
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+<expr> (5, 5)
+2 : 1, 4
+3 : 2
+4 : 3
+5 : 4
+Stored in variable 'S'
+Edge list 'S' saved to '{path}'
+Edge list 'T' loaded successfully from '{path}'
+<expr> (1, 1)
+1 : 1
+<expr> (1, 5)
+1 : 1, 2, 3, 4, 5
+Stored in variable 'V'
+Error saving edge list: Edge lists need the same domain on both sides, got (..1, ..5)
+"#
+        )
+        .trim(),
+    );
+}
+
+#[test]
+fn test_example_bdd_roundtrip() {
+    let path = std::env::temp_dir().join(format!("rel-lang-bdd-{}.bdd", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut output = Vec::new();
+    let mut repl = Repl::new();
+    for line in [
+        ".load rel R1 examples/R1.ascii",
+        ".label R1 examples/R1.labels",
+        &format!(".save bdd R1 {path}"),
+        &format!(".load bdd S {path}"),
+        "S",
+        ".load bdd T examples/R1.ascii",
+    ] {
+        let _ = repl.process_input(line, &mut output).unwrap();
+    }
+    let _ = std::fs::remove_file(path);
+
+    pretty_assertions::assert_eq!(
+        String::from_utf8(output).unwrap().trim(),
+        format!(
+            r#"
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Labels for 'R1' loaded from 'examples/R1.labels'
+Relation 'R1' saved to '{path}'
+Relation 'S' loaded successfully from '{path}'
+<expr> (5, 5)
+one : two
+two : three
+three : four
+four : two, five
+Error loading relation: not a relation file
+"#
+        )
+        .trim(),
+    );
+}
+
+test!(
+    test_example_interleaved_encoding,
+    r#"
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.set encoding interleaved
+.load rel R2 examples/R1.ascii
+.load rel R6 examples/R6.ascii
+RTC1(R2)
+R1^ | R2
+-R2 & L(R1)
+dom(R6)^
+"#,
+    r#"
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+New relations use the interleaved encoding
+Relation 'R2' loaded successfully from 'examples/R1.ascii'
+Relation 'R6' loaded successfully from 'examples/R6.ascii'
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+<expr> (5, 5)
+1 : 2
+2 : 1, 3, 4
+3 : 2, 4
+4 : 2, 3, 5
+5 : 4
+<expr> (5, 5)
+1 : 1, 3, 4, 5
+2 : 1, 2, 4, 5
+3 : 1, 2, 3, 5
+4 : 1, 3, 4
+5 : 1, 2, 3, 4, 5
+<expr> (1, 9)
+1 : 1, 2, 3, 4, 5, 6, 7, 8, 9
+"#
+);
+
+test!(
+    test_example_cache,
+    r#"
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.cache on
+RTC1(R1)
+RTC1(R1)
+.cache stats
+.load prog examples/Examples.prog
+.cache stats
+"#,
+    r#"
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+The cache is on
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+The cache is on: 1 hits, 1 misses, 1 entries
+Program loaded successfully from 'examples/Examples.prog'
+The cache is on: 1 hits, 1 misses, 0 entries
+"#
+);
+
+test!(
+    test_example_trace,
+    r#"
+.load prog examples/Recursion.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+.trace on
+Reach(dom(R2), R1)
+"#,
+    r#"
+Program loaded successfully from 'examples/Recursion.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+Tracing is on
+Reach:5 N = (5, 1) with 3 pairs
+Reach:6 IF eq(N,Q) THEN false
+  Reach:5 N = (5, 1) with 4 pairs
+  Reach:6 IF eq(N,Q) THEN false
+    Reach:5 N = (5, 1) with 4 pairs
+    Reach:6 IF eq(N,Q) THEN true
+    Reach:7 RETURN (5, 1) with 4 pairs
+  Reach:9 RETURN (5, 1) with 4 pairs
+Reach:9 RETURN (5, 1) with 4 pairs
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+"#
+);
+
+test!(
+    test_example_debug_variable,
+    r#"
+.load rel s examples/R1.ascii
+.debug run s
+"#,
+    r#"
+Relation 's' loaded successfully from 'examples/R1.ascii'
+<expr> (5, 5)
+1 : 2
+2 : 3
+3 : 4
+4 : 2, 5
+"#
+);
+
+test!(
+    test_example_debug_breakpoint_file,
+    r#"
+.load prog examples/Recursion.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+.debug break Recursion.prog 7
+.debug run Reach(dom(R2), R1)
+.debug continue
+.debug delete Recursion.prog 7
+.debug delete Recursion.prog 7
+"#,
+    r#"
+Program loaded successfully from 'examples/Recursion.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+Breakpoint at Recursion.prog:7
+Stopped at Reach:5 N = Q | (R^) * Q
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+Removed the breakpoint at Recursion.prog:7
+No breakpoint at Recursion.prog:7
+"#
+);
+
+test!(
+    test_example_debug,
+    r#"
+.load prog examples/Recursion.prog
+.load rel R1 examples/R1.ascii
+.load rel R2 examples/R2.ascii
+.debug break examples/Recursion.prog 7
+.debug run Reach(dom(R2), R1)
+.debug s
+.debug locals
+.debug continue
+.debug c
+.debug step
+"#,
+    r#"
+Program loaded successfully from 'examples/Recursion.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Relation 'R2' loaded successfully from 'examples/R2.ascii'
+Breakpoint at examples/Recursion.prog:7
+Stopped at Reach:5 N = Q | (R^) * Q
+Stopped at Reach:6 IF eq(N,Q) THEN
+N (5, 1)
+2 : 1
+3 : 1
+5 : 1
+Q (5, 1)
+2 : 1
+5 : 1
+R (5, 5)
+1 : 2
+2 : 3
+3 : 4
+4 : 2, 5
+Stopped at Reach:7 RETURN Q
+<expr> (5, 1)
+2 : 1
+3 : 1
+4 : 1
+5 : 1
+Not debugging
+"#
+);
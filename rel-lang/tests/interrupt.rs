@@ -0,0 +1,67 @@
+//! The interrupt flag is global, so these tests run in their own process.
+
+use std::{sync::atomic::Ordering, thread, time::Duration};
+
+use rel_lang::{
+    clear_interrupt,
+    eval::{Error, EvalOptions, Globals, Locals, eval},
+    interrupt_flag,
+    parser::{parse_expr, parse_program},
+    relation::{self, Relation},
+};
+
+#[test]
+fn test_interrupt() {
+    let src = include_str!("../examples/Examples.prog");
+    let mut globals = Globals::default();
+    globals.extend(parse_program("Examples.prog", src).unwrap().items);
+    let mut locals = Locals::default();
+    locals.assign(
+        "R",
+        Relation::sparse((..64, ..64), (1..64).map(|x| (x - 1, x))),
+    );
+    let expr = parse_expr("RTC3(R)").unwrap();
+
+    let interrupter = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(100));
+        interrupt_flag().store(true, Ordering::Relaxed);
+    });
+    let result = eval(&globals, &locals, &expr, &EvalOptions::default());
+    interrupter.join().unwrap();
+    assert!(matches!(result, Err(Error::Interrupted)));
+
+    // The flag stays set until it is cleared, after which evaluation works as before.
+    assert!(matches!(
+        eval(&globals, &locals, &expr, &EvalOptions::default()),
+        Err(Error::Interrupted)
+    ));
+    clear_interrupt();
+    locals.assign(
+        "R",
+        Relation::sparse((..8, ..8), (1..8).map(|x| (x - 1, x))),
+    );
+    let result = eval(&globals, &locals, &expr, &EvalOptions::default()).unwrap();
+    assert_eq!(result.cardinality(), 8 * 9 / 2);
+
+    // Interrupted relation operations fail rather than returning partial results.
+    let successor = Relation::sparse((..64, ..64), (1..64).map(|x| (x - 1, x)));
+    locals.assign("R", successor.clone());
+    let expr = parse_expr("R * R").unwrap();
+
+    interrupt_flag().store(true, Ordering::Relaxed);
+    // The product stops right away, and the empty partial product is not returned.
+    let composed = successor.clone().try_compose(successor.clone());
+    assert_eq!(composed, Err(relation::Error::Stopped));
+    let result = eval(&Globals::default(), &locals, &expr, &EvalOptions::default());
+    assert!(matches!(result, Err(Error::Interrupted)));
+    clear_interrupt();
+
+    assert_eq!(
+        successor
+            .clone()
+            .try_compose(successor)
+            .unwrap()
+            .cardinality(),
+        62
+    );
+}
@@ -0,0 +1,60 @@
+//! Tests of the `.set node-limit` and `.set timeout` commands. The node limit is shared by all
+//! threads, so it must not cut short the evaluations of the other test files.
+
+mod common;
+
+#[test]
+fn test_node_limit() {
+    common::assert_transcript(
+        r#"
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.set node-limit 20
+RTC1(R1)
+.set node-limit none
+RTC1(R1)
+"#,
+        r#"
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+The number of nodes is limited to 20
+Error: Node limit of 20 exceeded
+The number of nodes is unlimited
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+"#,
+    );
+}
+
+#[test]
+fn test_timeout() {
+    common::assert_transcript(
+        r#"
+.set timeout 1.5m
+.set timeout 10
+.load prog examples/Examples.prog
+.load rel R1 examples/R1.ascii
+.set timeout 200ms
+RTC1(R1)
+.set timeout none
+"#,
+        r#"
+Evaluations time out after 90s
+Invalid timeout '10': expected a number followed by ms, s or m
+Program loaded successfully from 'examples/Examples.prog'
+Relation 'R1' loaded successfully from 'examples/R1.ascii'
+Evaluations time out after 200ms
+<expr> (5, 5)
+1 : 1, 2, 3, 4, 5
+2 : 2, 3, 4, 5
+3 : 2, 3, 4, 5
+4 : 2, 3, 4, 5
+5 : 5
+Evaluations never time out
+"#,
+    );
+}
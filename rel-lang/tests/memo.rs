@@ -0,0 +1,40 @@
+//! Tests that the memo of calls forgets its results when an evaluation exceeds the node limit,
+//! which is set for the whole process.
+
+use rel_lang::{
+    Domain,
+    eval::{Error, EvalOptions, Globals, Locals, eval},
+    parser::{parse_expr, parse_program},
+    relation::Relation,
+};
+
+#[test]
+fn test_memo_cleared_at_node_limit() {
+    let mut globals = Globals::default();
+    globals.extend(parse_program("Id.prog", "Id(R) = I(R).").unwrap().items);
+    globals.memo().borrow_mut().set_enabled(true);
+    let mut locals = Locals::default();
+    locals.assign("R", Relation::empty((Domain::new(4), Domain::new(4))));
+    locals.assign("S", Relation::sparse((..64, ..64), [(0, 63)]));
+
+    let options = EvalOptions::default();
+    let expr = parse_expr("Id(R)").unwrap();
+    assert_eq!(
+        eval(&globals, &locals, &expr, &options)
+            .unwrap()
+            .cardinality(),
+        4
+    );
+    assert_eq!(globals.memo().borrow().stats().entries, 1);
+
+    let options = EvalOptions {
+        max_nodes: Some(1),
+        ..EvalOptions::default()
+    };
+    let expr = parse_expr("Id(S)").unwrap();
+    assert!(matches!(
+        eval(&globals, &locals, &expr, &options),
+        Err(Error::NodeLimitExceeded { limit: 1 })
+    ));
+    assert_eq!(globals.memo().borrow().stats().entries, 0);
+}
@@ -0,0 +1,80 @@
+//! Tests that constructors and operations on relations report the node limit of the manager
+//! instead of returning placeholders. Other tests would fail under the low limits used here.
+
+use rel_lang::{
+    Domain,
+    eval::{self, EvalOptions, Globals, Locals, eval},
+    parser::parse_expr,
+    relation::{DecodeError, Error, Relation},
+    set_node_limit,
+};
+use rel_lang_dd as dd;
+
+#[test]
+fn test_node_limit_errors() {
+    let pairs = (0..64).map(|x| (x, (x * 7) % 64)).collect::<Vec<_>>();
+    let relation = Relation::sparse((..64, ..64), pairs.clone());
+    let bytes = relation.to_bytes();
+    drop(relation);
+
+    set_node_limit(Some(8));
+    let aborted = Err(Error::Aborted {
+        source: dd::Error::NodeLimit { limit: 8 },
+    });
+    assert_eq!(Relation::try_sparse((..64, ..64), pairs.clone()), aborted);
+    assert_eq!(Relation::try_identity(Domain::new(64)), aborted);
+    assert_eq!(
+        Relation::try_universal((Domain::new(50), Domain::new(50))),
+        aborted
+    );
+    assert!(matches!(
+        Relation::from_bytes(&bytes),
+        Err(DecodeError::Diagram {
+            source: dd::DecodeError::Aborted { .. }
+        })
+    ));
+
+    // The errors are returned rather than left behind for the next operation.
+    let small = Relation::try_sparse((..2, ..2), [(0, 1)]).unwrap();
+    assert_eq!(small.cardinality(), 1);
+
+    // Evaluations report the node limit rather than the relation error.
+    let mut locals = Locals::default();
+    locals.assign("R", Relation::empty((Domain::new(64), Domain::new(64))));
+    let expr = parse_expr("I(R)").unwrap();
+    let result = eval(&Globals::default(), &locals, &expr, &EvalOptions::default());
+    assert!(matches!(
+        result,
+        Err(eval::Error::NodeLimitExceeded { limit: 8 })
+    ));
+
+    // Operations fail as well, instead of returning the placeholder as their result.
+    set_node_limit(None);
+    let lhs = Relation::sparse((..64, ..64), pairs.clone());
+    let rhs = Relation::sparse((..64, ..64), (0..64).map(|x| (x, (x * 5 + 1) % 64)));
+    set_node_limit(Some(100));
+    let aborted = Err(Error::Aborted {
+        source: dd::Error::NodeLimit { limit: 100 },
+    });
+    assert_eq!(lhs.clone().try_union(rhs.clone()), aborted);
+    // An aborted implication is not mistaken for a subset.
+    assert_ne!(lhs.try_is_subset_of(&rhs), Ok(true));
+    assert_eq!(lhs.clone().try_converse(), aborted);
+    set_node_limit(None);
+    assert_eq!(
+        Relation::try_sparse((..2, ..2), [(0, 1)])
+            .unwrap()
+            .cardinality(),
+        1
+    );
+    assert_eq!((lhs | rhs).cardinality(), 128);
+
+    set_node_limit(None);
+    assert_eq!(
+        Relation::try_sparse((..64, ..64), pairs.clone())
+            .unwrap()
+            .cardinality(),
+        64
+    );
+    assert_eq!(Relation::from_bytes(&bytes).unwrap().cardinality(), 64);
+}
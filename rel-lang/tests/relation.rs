@@ -0,0 +1,114 @@
+use rel_lang::{
+    Domain, Encoding,
+    relation::{Error, Relation},
+    set_default_encoding,
+};
+
+#[test]
+fn test_image_preimage() {
+    for encoding in [Encoding::Concatenated, Encoding::Interleaved] {
+        set_default_encoding(encoding);
+        let relation =
+            Relation::sparse((..5, ..7), [(0, 1), (0, 6), (1, 2), (2, 2), (3, 0), (4, 5)]);
+        let sources = Relation::sparse((..5, ..1), [(0, 0), (2, 0)]);
+        let targets = Relation::sparse((..7, ..1), [(0, 0), (2, 0), (3, 0)]);
+
+        assert_eq!(relation.cardinality(), 6);
+
+        let image = relation.image(&sources);
+        assert_eq!(image.domain(), (Domain::new(7), Domain::new(1)));
+        assert_eq!(image.cardinality(), 3);
+        assert_eq!(
+            image,
+            Relation::sparse((..7, ..1), [(1, 0), (2, 0), (6, 0)])
+        );
+        assert_eq!(image, relation.clone().converse() * sources.clone());
+
+        let preimage = relation.preimage(&targets);
+        assert_eq!(preimage.domain(), (Domain::new(5), Domain::new(1)));
+        assert_eq!(
+            preimage,
+            Relation::sparse((..5, ..1), [(1, 0), (2, 0), (3, 0)])
+        );
+        assert_eq!(preimage, relation.clone() * targets.clone());
+
+        // Vectors in the other encoding are converted.
+        set_default_encoding(match encoding {
+            Encoding::Concatenated => Encoding::Interleaved,
+            Encoding::Interleaved => Encoding::Concatenated,
+        });
+        let sources = Relation::sparse((..5, ..1), [(0, 0), (2, 0)]);
+        assert_eq!(relation.image(&sources), image);
+    }
+}
+
+#[test]
+fn test_domain_errors() {
+    assert_eq!(
+        Relation::try_sparse((..2, ..3), [(0, 2), (2, 0)]).err(),
+        Some(Error::NotInDomain {
+            pair: (2, 0),
+            domain: (Domain::new(2), Domain::new(3)),
+        })
+    );
+
+    let square = Relation::sparse((..2, ..2), [(0, 1)]);
+    let wide = Relation::sparse((..2, ..3), [(0, 2)]);
+    let mismatch = Error::DomainMismatch {
+        expected: square.domain(),
+        actual: wide.domain(),
+    };
+    assert_eq!(
+        square.clone().try_union(wide.clone()).err(),
+        Some(mismatch.clone())
+    );
+    assert_eq!(
+        square.clone().try_intersection(wide.clone()).err(),
+        Some(mismatch.clone())
+    );
+    assert_eq!(square.try_eq(&wide).err(), Some(mismatch.clone()));
+    assert_eq!(square.try_is_subset_of(&wide).err(), Some(mismatch));
+    assert_ne!(square, wide);
+
+    assert_eq!(
+        wide.clone().try_compose(square.clone()).err(),
+        Some(Error::DomainMismatch {
+            expected: (Domain::new(3), Domain::new(2)),
+            actual: square.domain(),
+        })
+    );
+    assert_eq!(
+        square.clone().try_compose(wide.clone()).unwrap(),
+        Relation::sparse((..2, ..3), [])
+    );
+
+    let vector = Relation::sparse((..2, ..1), [(1, 0)]);
+    assert_eq!(
+        wide.try_image(&vector).unwrap(),
+        Relation::sparse((..3, ..1), [])
+    );
+    assert_eq!(
+        wide.try_preimage(&vector).err(),
+        Some(Error::DomainMismatch {
+            expected: (Domain::new(3), Domain::new(1)),
+            actual: vector.domain(),
+        })
+    );
+    assert_eq!(
+        wide.try_image(&square).err(),
+        Some(Error::DomainMismatch {
+            expected: (Domain::new(2), Domain::new(1)),
+            actual: square.domain(),
+        })
+    );
+
+    assert_eq!(wide.contains((0, 2)), Ok(true));
+    assert_eq!(wide.contains((1, 2)), Ok(false));
+    assert_eq!(
+        wide.contains((2, 0)),
+        Err(Error::NotInDomain {
+            pair: (2, 0),
+            domain: wide.domain(),
+        })
+    );
+}